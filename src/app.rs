@@ -1,17 +1,79 @@
-use crate::config::{AppConfig, ConfigManager, DataManager, UserData};
-use crate::file_entry::FileEntry;
+use crate::atomic_file::{self, BACKUP_GENERATIONS};
+use crate::auto_tag::{AutoTagRule, RuleKind, RuleSet};
+use crate::collections;
+use crate::config::{self, AppConfig, ConfigManager, DataManager, LoadedData, UserData};
+use crate::content_index::{self, ContentIndex, ContentIndexer, ContentSnippet};
+use crate::crypto::{self, EncryptionKey};
+use crate::file_entry::{CommandSpec, FileEntry, LaunchMode};
 use crate::fonts::setup_chinese_fonts;
+use crate::fuzzy::{self, EntryMatch};
+use crate::history::{Command, History};
+use crate::journal::{self, JournalEvent};
+use crate::launcher::{self, Target};
+use crate::listing::{self, SortOrder, TimeFilter};
+use crate::migration::CURRENT_SCHEMA_VERSION;
+use crate::paths;
+use crate::query;
+use crate::search_index::{EntryIndex, SearchIndex};
+use crate::storage::{self, EntryStore, StorageBackend};
+use crate::sync::{self, DataLock, MergeResult, Resolution};
+use crate::tags::{self, TagInfo, TagNode, TagRegistry};
 use crate::theme::{ModernTheme, ThemeMode};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use eframe::egui;
-use std::collections::HashSet;
-use std::path::PathBuf;
-use std::time::Instant;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 检查数据文件是否被外部修改的间隔
+const SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 开启内容索引时，重新扫描文件内容的间隔
+const CONTENT_SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 搜索框的语法提示
+const SEARCH_SYNTAX_HELP: &str = "多个词同时满足，OR 表示任意一个，-词 表示排除\n\
+\"完整短语\"  #标签  (分组)\n\
+type:dir|file|link|collection|command  path:~/src  url:github.com\n\
+added:>2026-01-01  updated:<7d  opened:>6m\n\
+content:\"季度 报告\"（需在设置中开启内容索引）";
+
+/// 标签管理中预览受影响条目时最多列出的数量
+const TAG_PREVIEW_LIMIT: usize = 10;
+
+/// 标签管理中等待确认的库范围操作
+enum TagOperation {
+    /// 重命名或移动标签及其下级
+    Rename { from: String },
+    /// 把多个标签合并为一个
+    Merge { sources: Vec<String> },
+    Delete { tag: String },
+}
+
+/// 在列表中展开的集合树上点击的操作，渲染结束后执行
+enum CollectionAction {
+    Open(usize),
+    /// 从集合中移除成员：(集合位置, 成员位置)
+    Remove(usize, usize),
+    /// 把下级集合移到顶层集合：(顶层集合位置, 所在集合位置, 下级集合ID)
+    Extract(usize, usize, String),
+}
+
+/// 等待用户处理冲突的外部修改
+struct PendingMerge {
+    result: MergeResult,
+    choices: Vec<Resolution>,
+    their_entries: Vec<FileEntry>,
+    disk_version: Option<String>,
+}
 
 pub struct FileManagerApp {
     entries: Vec<FileEntry>,
     search_query: String,
     config_manager: ConfigManager,
-    data_manager: DataManager,
+    data_store: Box<dyn EntryStore>,
     config: AppConfig,
     user_data: UserData,
     font_loaded: bool,
@@ -23,6 +85,19 @@ pub struct FileManagerApp {
 
     // 筛选相关
     filtered_indices: Vec<usize>,
+    /// 当前搜索词的匹配结果，用于高亮
+    search_matches: HashMap<usize, EntryMatch>,
+    /// 预先计算的搜索索引（小写、拼音、标签倒排）
+    search_index: SearchIndex,
+    /// 搜索框中查询的语法错误
+    query_error: Option<String>,
+    /// 文件内容索引，开启后由后台线程定期更新
+    content_index: Arc<ContentIndex>,
+    content_indexer: ContentIndexer,
+    /// 智能集合的成员，条目或索引变化时清空
+    smart_members: collections::SmartCache,
+    /// 上次开始扫描的时间，None 表示需要尽快扫描（刚开启或条目刚被修改）
+    last_content_scan: Option<Instant>,
     last_search_query: String,
     last_filter_time: Instant,
 
@@ -35,6 +110,9 @@ pub struct FileManagerApp {
     show_add_dialog: bool,
     add_entry_type: crate::file_entry::EntryType,
 
+    // 按时间筛选（排序方式保存在配置中）
+    time_filter: TimeFilter,
+
     // 标签编辑相关
     show_tag_editor: bool,
     editing_entry_index: Option<usize>,
@@ -48,11 +126,29 @@ pub struct FileManagerApp {
     import_merge_mode: bool,
     export_status: String,
     import_status: String,
+    export_encrypted: bool,
+    export_passphrase: String,
+    import_passphrase: String,
 
     // 标签管理相关
     show_tag_manager: bool,
     tag_cloud_filter: String,
     selected_tags: HashSet<String>,
+    /// 等待确认的标签操作，以及重命名或合并的目标标签
+    tag_operation: Option<TagOperation>,
+    tag_operation_target: String,
+    tag_alias_input: String,
+    tag_alias_target: String,
+    tag_status: String,
+    /// 正在编辑显示信息的标签
+    tag_style_editing: Option<(String, TagInfo)>,
+    show_hidden_tags: bool,
+    /// 正在添加的自动标签规则
+    auto_tag_kind: RuleKind,
+    auto_tag_pattern: String,
+    auto_tag_tags: String,
+    /// 对已有条目试运行自动标签规则的结果：条目位置及将添加的标签
+    auto_tag_preview: Option<Vec<(usize, Vec<String>)>>,
     batch_tag_input: String,
     show_tag_suggestions: bool,
 
@@ -65,6 +161,19 @@ pub struct FileManagerApp {
     show_collection_manager: bool,
     editing_collection_index: Option<usize>,
     collection_child_selection: HashSet<usize>,
+    /// 正在编辑的智能集合的搜索条件
+    collection_query_input: String,
+    collection_status: String,
+    /// 集合管理器中正在拖动的成员位置
+    collection_drag: Option<usize>,
+    /// 添加对话框：创建智能集合及其搜索条件
+    add_smart_collection: bool,
+    add_smart_query_input: String,
+    /// 添加或编辑命令条目：参数、工作目录、环境变量（程序使用路径输入框）
+    add_command_args: String,
+    add_command_dir: String,
+    add_command_env: String,
+    add_command_terminal: bool,
     
     // 多选相关
     selected_entries: HashSet<usize>,
@@ -76,6 +185,49 @@ pub struct FileManagerApp {
     search_has_focus: bool,
     search_currently_focused: bool,
     multi_select_mode: bool,
+
+    // 数据加载提示相关
+    data_notice: Option<String>,
+    data_save_blocked: bool,
+    storage_status: String,
+
+    // 资料库相关
+    library_status: String,
+    new_library_name: String,
+    new_library_path: String,
+
+    // 多实例同步相关：上次与磁盘同步时的条目、元数据和磁盘版本
+    base_entries: Vec<FileEntry>,
+    base_user_data: UserData,
+    last_disk_version: Option<String>,
+    last_sync_check: Instant,
+    pending_merge: Option<PendingMerge>,
+
+    // 撤销/重做
+    history: History,
+
+    // 变更日志相关
+    actor: String,
+    entry_history: Option<(String, Vec<JournalEvent>)>,
+
+    // 命令运行日志
+    run_log: launcher::RunLog,
+    show_run_log: bool,
+    run_log_selected: Option<u64>,
+    /// 等待确认运行的条目ID：其中有本机没有确认过的命令
+    confirm_run_entry: Option<String>,
+    restore_date_input: String,
+    restore_preview: Option<(DateTime<Utc>, Vec<FileEntry>)>,
+    journal_status: String,
+
+    // 数据加密相关：locked 为 true 时条目尚未加载（或已清除），需要输入密码解锁
+    locked: bool,
+    unlock_input: String,
+    unlock_error: String,
+    last_activity: Instant,
+    new_passphrase_input: String,
+    confirm_passphrase_input: String,
+    encryption_status: String,
 }
 
 impl Default for FileManagerApp {
@@ -85,38 +237,6 @@ impl Default for FileManagerApp {
 }
 
 impl FileManagerApp {
-    /// 迁移旧的索引系统到新的ID系统
-    fn migrate_entries_to_id_system(entries: &mut Vec<FileEntry>) {
-        // 第一步：确保所有条目都有ID
-        for entry in entries.iter_mut() {
-            if entry.id.is_empty() {
-                entry.id = uuid::Uuid::new_v4().to_string();
-            }
-        }
-        
-        // 第二步：迁移集合的子项目引用从索引到ID
-        let mut collections_to_migrate = Vec::new();
-        for (i, entry) in entries.iter().enumerate() {
-            if entry.entry_type == crate::file_entry::EntryType::Collection && entry.has_legacy_child_entries() {
-                collections_to_migrate.push(i);
-            }
-        }
-        
-        for collection_idx in collections_to_migrate {
-            let legacy_indices = entries[collection_idx].get_legacy_child_entries().clone();
-            let mut new_child_ids = Vec::new();
-            
-            for &legacy_index in &legacy_indices {
-                if legacy_index < entries.len() {
-                    new_child_ids.push(entries[legacy_index].id.clone());
-                }
-            }
-            
-            entries[collection_idx].child_entries = new_child_ids;
-            entries[collection_idx].clear_legacy_child_entries();
-        }
-    }
-
     fn toggle_panel(&mut self, panel: &str) {
         // 关闭所有面板
         self.show_add_dialog = false;
@@ -141,30 +261,76 @@ impl FileManagerApp {
     }
 
     pub fn new() -> Self {
+        Self::with_library(None)
+    }
+
+    /// 创建应用，`library` 为启动时要打开的资料库名称（命令行 `--library` 参数）
+    pub fn with_library(library: Option<String>) -> Self {
         let config_manager = ConfigManager::new();
-        let config = config_manager.load_config().unwrap_or_default();
-
-        // 创建数据管理器
-        let data_manager = if let Some(data_path) = &config.data_file_path {
-            if !data_path.is_empty() {
-                let data_path_buf = PathBuf::from(data_path);
-                if data_path_buf.exists() || data_path_buf.parent().map_or(false, |p| p.exists()) {
-                    DataManager::new_with_path(data_path_buf)
-                } else {
-                    DataManager::new()
-                }
+        let (mut config, config_notice) = match config_manager.load_config() {
+            Ok(loaded) => {
+                let notice = loaded
+                    .recovered_from
+                    .as_deref()
+                    .map(|backup| Self::recovered_notice("配置文件", backup));
+                (loaded.config, notice)
+            }
+            Err(_) => (AppConfig::default(), None),
+        };
+
+        let mut library_notice = None;
+        if let Some(name) = library {
+            if name == AppConfig::DEFAULT_LIBRARY_NAME {
+                config.active_library = None;
+            } else if config.find_library(&name).is_some() {
+                config.active_library = Some(name);
             } else {
-                DataManager::new()
+                library_notice = Some(format!(
+                    "找不到名为“{}”的资料库，已打开资料库“{}”。",
+                    name,
+                    config.active_library_name()
+                ));
             }
-        } else {
-            DataManager::new()
+        }
+
+        // 创建数据存储
+        let (mut data_store, open_error) = match Self::open_data_store(&config) {
+            Ok(store) => (store, None),
+            Err(e) => (Box::new(DataManager::new()) as Box<dyn EntryStore>, Some(e)),
         };
 
-        let user_data = data_manager.load_data().unwrap_or_default();
-        let mut entries = user_data.entries.clone();
-        
-        // 执行数据迁移：为旧数据添加ID，转换集合的索引引用为ID引用
-        Self::migrate_entries_to_id_system(&mut entries);
+        // 加载用户数据：主文件损坏时从备份恢复，完全无法恢复时暂停保存以免覆盖原文件
+        // 加密的数据文件要等用户输入密码后再加载
+        let locked = open_error.is_none() && data_store.is_locked();
+        let load_result = match open_error {
+            Some(e) => Err(e),
+            None if locked => Ok(LoadedData {
+                data: UserData::default(),
+                recovered_from: None,
+            }),
+            None => data_store.load(),
+        };
+        let (mut user_data, data_notice, data_save_blocked) = match load_result {
+            Ok(loaded) => {
+                let notice = loaded
+                    .recovered_from
+                    .as_deref()
+                    .map(|backup| Self::recovered_notice("数据文件", backup));
+                (loaded.data, notice, false)
+            }
+            Err(e) => (
+                UserData::default(),
+                Some(format!("{}\n\n为避免覆盖原数据文件，已暂停自动保存。", e)),
+                true,
+            ),
+        };
+        // 条目单独保存在 entries 中，user_data 只保留元数据
+        let mut entries = std::mem::take(&mut user_data.entries);
+        let cycle_notice = Self::cycle_notice(collections::break_cycles(&mut entries));
+        let data_notice = [config_notice, library_notice, data_notice, cycle_notice]
+            .into_iter()
+            .flatten()
+            .reduce(|a, b| format!("{}\n\n{}", a, b));
 
         let mut all_tags = HashSet::new();
         for entry in &entries {
@@ -174,31 +340,41 @@ impl FileManagerApp {
         }
 
         let filtered_indices: Vec<usize> = (0..entries.len()).collect();
+        let search_index = SearchIndex::new(&entries);
+        let base_entries = entries.clone();
+        let last_disk_version = data_store.disk_version();
+        let history = Self::load_history(&config, data_store.as_ref());
+        let content_index = Self::load_content_index(&config, data_store.as_ref());
 
-        // 从配置中恢复主题模式和紧凑模式
-        let theme_mode = match config.theme_mode.as_str() {
-            "Dark" => ThemeMode::Dark,
-            "System" => ThemeMode::System,
-            _ => ThemeMode::Light,
-        };
-        let _compact_mode = config.compact_mode;
+        // 从配置中恢复主题模式和紧凑模式（资料库可覆盖）
+        let theme_mode = Self::theme_mode_from_config(config.active_theme_mode());
+        let compact_mode = config.active_compact_mode().unwrap_or(true); // 默认使用紧凑模式
 
         Self {
             entries,
             search_query: String::new(),
             config_manager,
-            data_manager,
+            data_store,
             config: config.clone(),
+            base_user_data: user_data.clone(),
             user_data,
             font_loaded: false,
             show_settings: false,
             all_tags,
             theme_mode,
-            compact_mode: true, // 默认使用紧凑模式
+            compact_mode,
             expanded_entries: HashSet::new(),
             filtered_indices,
+            search_matches: HashMap::new(),
+            search_index,
+            query_error: None,
+            content_index,
+            content_indexer: ContentIndexer::default(),
+            smart_members: collections::SmartCache::default(),
+            last_content_scan: None,
             last_search_query: String::new(),
             last_filter_time: Instant::now(),
+            time_filter: TimeFilter::All,
             add_path_input: String::new(),
             add_name_input: String::new(),
             add_nickname_input: String::new(),
@@ -208,18 +384,36 @@ impl FileManagerApp {
             show_tag_editor: false,
             editing_entry_index: None,
             custom_config_path: String::new(),
-            custom_data_path: config.data_file_path.clone().unwrap_or_default(),
+            custom_data_path: config
+                .active_data_location()
+                .0
+                .cloned()
+                .unwrap_or_default(),
 
             // 导入导出功能
             show_import_export: false,
             import_merge_mode: true,
             export_status: String::new(),
             import_status: String::new(),
+            export_encrypted: false,
+            export_passphrase: String::new(),
+            import_passphrase: String::new(),
 
             // 增强的标签管理
             show_tag_manager: false,
             tag_cloud_filter: String::new(),
             selected_tags: HashSet::new(),
+            tag_operation: None,
+            tag_operation_target: String::new(),
+            tag_alias_input: String::new(),
+            tag_alias_target: String::new(),
+            tag_status: String::new(),
+            tag_style_editing: None,
+            show_hidden_tags: false,
+            auto_tag_kind: RuleKind::Extension,
+            auto_tag_pattern: String::new(),
+            auto_tag_tags: String::new(),
+            auto_tag_preview: None,
             batch_tag_input: String::new(),
             show_tag_suggestions: false,
             add_entry_type: crate::file_entry::EntryType::File,
@@ -232,6 +426,15 @@ impl FileManagerApp {
             show_collection_manager: false,
             editing_collection_index: None,
             collection_child_selection: HashSet::new(),
+            collection_query_input: String::new(),
+            collection_status: String::new(),
+            collection_drag: None,
+            add_smart_collection: false,
+            add_smart_query_input: String::new(),
+            add_command_args: String::new(),
+            add_command_dir: String::new(),
+            add_command_env: String::new(),
+            add_command_terminal: false,
             
             selected_entries: HashSet::new(),
             show_batch_collection_dialog: false,
@@ -241,7 +444,88 @@ impl FileManagerApp {
             search_has_focus: false,
             search_currently_focused: false,
             multi_select_mode: false,
+
+            data_notice,
+            data_save_blocked,
+            storage_status: String::new(),
+
+            library_status: String::new(),
+            new_library_name: String::new(),
+            new_library_path: String::new(),
+
+            base_entries,
+            last_disk_version,
+            last_sync_check: Instant::now(),
+            pending_merge: None,
+
+            history,
+
+            actor: journal::current_actor(),
+            entry_history: None,
+
+            run_log: launcher::RunLog::default(),
+            show_run_log: false,
+            run_log_selected: None,
+            confirm_run_entry: None,
+            restore_date_input: String::new(),
+            restore_preview: None,
+            journal_status: String::new(),
+
+            locked,
+            unlock_input: String::new(),
+            unlock_error: String::new(),
+            last_activity: Instant::now(),
+            new_passphrase_input: String::new(),
+            confirm_passphrase_input: String::new(),
+            encryption_status: String::new(),
+        }
+    }
+
+    fn theme_mode_from_config(theme_mode: &str) -> ThemeMode {
+        match theme_mode {
+            "Dark" => ThemeMode::Dark,
+            "System" => ThemeMode::System,
+            _ => ThemeMode::Light,
+        }
+    }
+
+    /// 加载时移除了集合的循环引用时提示用户
+    fn cycle_notice(removed: usize) -> Option<String> {
+        (removed > 0).then(|| {
+            format!(
+                "发现 {} 处集合循环引用（集合直接或间接包含了自己），已自动移除。",
+                removed
+            )
+        })
+    }
+
+    /// `what` 为损坏的文件，例如“数据文件”“配置文件”
+    fn recovered_notice(what: &str, backup: &std::path::Path) -> String {
+        format!(
+            "{}已损坏，已自动从备份恢复:\n{}\n\n损坏的文件已另存为 .corrupt 文件。",
+            what,
+            backup.display()
+        )
+    }
+
+    /// 根据配置中的数据路径和存储后端打开数据存储
+    fn open_data_store(config: &AppConfig) -> Result<Box<dyn EntryStore>, String> {
+        let (data_file_path, backend) = config.active_data_location();
+        let mut default_path = match config.active_library() {
+            Some(lib) => DataManager::library_data_path(&lib.name),
+            None => DataManager::new().get_data_path().clone(),
+        };
+        if backend == StorageBackend::Sqlite {
+            default_path.set_extension("db");
         }
+
+        let data_path = data_file_path
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .filter(|p| p.exists() || p.parent().is_some_and(|parent| parent.exists()))
+            .unwrap_or(default_path);
+
+        storage::open_store(backend, data_path)
     }
 
     fn apply_theme(&self, ctx: &egui::Context) {
@@ -260,81 +544,397 @@ impl FileManagerApp {
     fn update_filter(&mut self) {
         // 只有搜索查询改变时才重新过滤
         if self.search_query != self.last_search_query {
-            self.filtered_indices = self
-                .entries
-                .iter()
-                .enumerate()
-                .filter(|(_, entry)| self.matches_search_query(entry))
-                .map(|(i, _)| i)
-                .collect();
+            (self.filtered_indices, self.search_matches) = self.compute_filtered_indices();
+            self.query_error = query::parse(&self.search_query).err();
 
             self.last_search_query = self.search_query.clone();
             self.last_filter_time = Instant::now();
         }
     }
 
+    /// 按当前的搜索索引、标签别名和内容索引求集合成员
+    fn members(&self) -> collections::Members<'_> {
+        collections::Members {
+            entries: &self.entries,
+            search_index: &self.search_index,
+            aliases: &self.user_data.tag_aliases,
+            content: Some(self.content_index.as_ref()),
+            cache: &self.smart_members,
+        }
+    }
+
     fn force_update_filter(&mut self) {
-        // 强制重新过滤，不管搜索查询是否改变
-        self.filtered_indices = self
-            .entries
-            .iter()
-            .enumerate()
-            .filter(|(_, entry)| self.matches_search_query(entry))
-            .map(|(i, _)| i)
-            .collect();
+        // 强制重新过滤，不管搜索查询是否改变；条目可能已修改，先更新搜索索引
+        self.search_index.sync(&self.entries);
+        self.smart_members.clear();
+        (self.filtered_indices, self.search_matches) = self.compute_filtered_indices();
+        self.query_error = query::parse(&self.search_query).err();
 
         self.last_search_query = self.search_query.clone();
         self.last_filter_time = Instant::now();
     }
 
-    // 统一的搜索匹配函数，支持文件名、标签和描述搜索
-    fn matches_search_query(&self, entry: &FileEntry) -> bool {
-        if self.search_query.is_empty() {
-            return true;
-        }
-
-        let query_lower = self.search_query.to_lowercase();
-        let query_parts: Vec<&str> = query_lower.split_whitespace().collect();
-
-        for part in query_parts {
-            let found = if part.starts_with('#') {
-                // 标签搜索
-                let (hash_tags, _) = entry.get_tag_categories();
-                hash_tags
-                    .iter()
-                    .any(|tag| tag.to_lowercase().contains(part))
-            } else {
-                // 普通搜索：文件名、昵称、描述
-                entry.matches_query(part)
+    /// 按搜索条件和时间筛选条目，再按当前排序方式排序
+    ///
+    /// 有搜索词时按匹配得分从高到低排列，得分相同的保持当前排序方式的顺序，置顶的条目仍在最前。
+    /// 查询语法有误时退回到把整个输入当作普通搜索词。
+    fn compute_filtered_indices(&self) -> (Vec<usize>, HashMap<usize, EntryMatch>) {
+        let now = Utc::now();
+        let parsed = query::parse(&self.search_query).map(|mut query| {
+            query.resolve_tag_aliases(&self.user_data.tag_aliases);
+            query
+        });
+        // 按标签搜索时只需检查倒排索引给出的条目
+        let candidates = parsed
+            .as_ref()
+            .ok()
+            .and_then(|query| query.candidates(&self.search_index))
+            .unwrap_or_else(|| (0..self.entries.len()).collect());
+
+        let mut matches = HashMap::new();
+        let mut indices: Vec<usize> = Vec::new();
+        for i in candidates {
+            let Some(entry) = self.entries.get(i) else {
+                continue;
             };
-
-            // 如果任何一个搜索词没有匹配，则不显示该条目
-            if !found {
-                return false;
+            if !self.time_filter.matches(entry, now) {
+                continue;
+            }
+            if self.search_query.trim().is_empty() {
+                indices.push(i);
+                continue;
+            }
+            // 索引过期时（条目刚被修改、尚未同步）临时建立
+            let fresh;
+            let index = match self.search_index.get(i, entry) {
+                Some(index) => index,
+                None => {
+                    fresh = EntryIndex::new(entry);
+                    &fresh
+                }
+            };
+            let entry_match = match &parsed {
+                Ok(query) => query.evaluate(entry, index, Some(self.content_index.as_ref()), now),
+                Err(_) => fuzzy::score_entry(index, &self.search_query),
+            };
+            if let Some(m) = entry_match {
+                matches.insert(i, m);
+                indices.push(i);
             }
         }
 
-        true
+        if matches.is_empty() {
+            self.config.sort_order.sort(
+                &self.entries,
+                &mut indices,
+                self.config.frecency_half_life_days,
+            );
+        } else {
+            self.config.sort_order.sort_matches(
+                &self.entries,
+                &mut indices,
+                self.config.frecency_half_life_days,
+                |i| matches[&i].score,
+            );
+        }
+        (indices, matches)
     }
 
     fn save_config(&mut self) -> Result<(), String> {
         // 保存主题设置到配置
-        self.config.theme_mode = match self.theme_mode {
-            ThemeMode::Light => "Light".to_string(),
-            ThemeMode::Dark => "Dark".to_string(),
-            ThemeMode::System => "System".to_string(),
-        };
-        self.config.compact_mode = self.compact_mode;
+        self.config.set_active_theme_mode(
+            match self.theme_mode {
+                ThemeMode::Light => "Light",
+                ThemeMode::Dark => "Dark",
+                ThemeMode::System => "System",
+            }
+            .to_string(),
+        );
+        self.config.set_active_compact_mode(self.compact_mode);
         self.config_manager.save_config(&self.config)
     }
 
     fn save_user_data(&mut self) -> Result<(), String> {
-        self.user_data.entries = self.entries.clone();
-        self.data_manager.save_data(&self.user_data)
+        self.persist_entries(Some("修改条目"))
+    }
+
+    /// 保存条目修改，并以 `label` 为名记入撤销历史
+    fn commit_entries(&mut self, label: &str) -> Result<(), String> {
+        self.persist_entries(Some(label))
+    }
+
+    /// 写入数据文件；`history_label` 为 None 时不记录历史（撤销/重做本身）
+    fn persist_entries(&mut self, history_label: Option<&str>) -> Result<(), String> {
+        if self.locked {
+            return Err("数据文件已锁定，请先解锁".to_string());
+        }
+        if self.data_save_blocked {
+            return Err("数据文件加载失败，已暂停保存".to_string());
+        }
+        if self.pending_merge.is_some() {
+            return Err("数据文件有待处理的冲突，已暂停保存".to_string());
+        }
+
+        // 持有写锁期间完成“检查外部修改 → 合并 → 写入”，避免与其他实例交错写入
+        let _lock = DataLock::acquire(self.data_store.location())?;
+        if self.data_store.disk_version() != self.last_disk_version
+            && self.merge_external_changes()?
+        {
+            return Err("数据文件已被其他实例修改且存在冲突，请先处理冲突".to_string());
+        }
+
+        self.user_data.version = env!("CARGO_PKG_VERSION").to_string();
+        self.user_data.schema_version = CURRENT_SCHEMA_VERSION;
+        self.data_store.save(&self.user_data, &self.entries)?;
+        self.search_index.sync(&self.entries);
+        self.smart_members.clear();
+        // 文件或文件夹条目有变化时尽快重新扫描，其余修改等下一次定期扫描
+        if content_index::roots(&self.entries) != content_index::roots(&self.base_entries) {
+            self.last_content_scan = None;
+        }
+
+        // 审计日志：记录每个发生变化的条目（包括撤销/重做）；加密的数据不写明文日志
+        if !self.data_store.is_encrypted() {
+            let events =
+                journal::diff_events(&self.base_entries, &self.entries, &self.actor, Utc::now());
+            if let Err(e) =
+                journal::append(&journal::journal_path(self.data_store.location()), &events)
+            {
+                self.journal_status = e;
+            }
+        }
+
+        // base_entries 是上次保存后的内容，与之对比即得到本次操作
+        if let Some(label) = history_label
+            && let Some(command) = Command::from_diff(
+                label,
+                &self.base_entries,
+                &self.entries,
+                &self.base_user_data,
+                &self.user_data,
+            )
+        {
+            self.history.record(command);
+            self.save_history_journal();
+        }
+        self.base_entries = self.entries.clone();
+        self.base_user_data = self.user_data.clone();
+        self.last_disk_version = self.data_store.disk_version();
+        Ok(())
+    }
+
+    /// 撤销上一步操作
+    fn undo(&mut self) {
+        let Some(command) = self.history.take_undo() else {
+            return;
+        };
+        command.undo(&mut self.entries, &mut self.user_data);
+        self.library_status = match self.persist_entries(None) {
+            Ok(()) => format!("已撤销: {}", command.label),
+            Err(e) => format!("已撤销: {}（保存失败: {}）", command.label, e),
+        };
+        self.history.push_redo(command);
+        self.after_history_step();
+    }
+
+    /// 重做上一步被撤销的操作
+    fn redo(&mut self) {
+        let Some(command) = self.history.take_redo() else {
+            return;
+        };
+        command.redo(&mut self.entries, &mut self.user_data);
+        self.library_status = match self.persist_entries(None) {
+            Ok(()) => format!("已重做: {}", command.label),
+            Err(e) => format!("已重做: {}（保存失败: {}）", command.label, e),
+        };
+        self.history.push_undo(command);
+        self.after_history_step();
+    }
+
+    fn after_history_step(&mut self) {
+        self.save_history_journal();
+        self.reset_entry_view_state();
+        self.rebuild_tag_set();
+        self.force_update_filter();
+    }
+
+    /// 开启“保存撤销历史”时，把历史写到数据文件旁
+    fn save_history_journal(&self) {
+        if self.config.persist_history && !self.data_store.is_encrypted() {
+            let _ = self
+                .history
+                .save(&History::journal_path(self.data_store.location()));
+        }
+    }
+
+    /// 读取当前数据文件的持久化历史（未开启时返回空历史）
+    fn load_history(config: &AppConfig, data_store: &dyn EntryStore) -> History {
+        if config.persist_history && !data_store.is_encrypted() && !data_store.is_locked() {
+            History::load(&History::journal_path(data_store.location()))
+        } else {
+            History::default()
+        }
+    }
+
+    /// 读取当前数据文件的内容索引（未开启时返回空索引）；加密的数据不保存明文索引
+    fn load_content_index(config: &AppConfig, data_store: &dyn EntryStore) -> Arc<ContentIndex> {
+        Arc::new(match Self::content_index_path(config, data_store) {
+            Some(path) => ContentIndex::load(&path),
+            None => ContentIndex::default(),
+        })
+    }
+
+    /// 内容索引文件的路径；未开启或数据已加密时不保存
+    fn content_index_path(config: &AppConfig, data_store: &dyn EntryStore) -> Option<PathBuf> {
+        (config.content_indexing && !data_store.is_encrypted())
+            .then(|| ContentIndex::index_path(data_store.location()))
+    }
+
+    /// 丢弃内存中的内容索引和正在进行的扫描（切换数据或锁定时调用）
+    fn reset_content_index(&mut self) {
+        self.content_indexer = ContentIndexer::default();
+        self.content_index = Self::load_content_index(&self.config, self.data_store.as_ref());
+        self.smart_members.clear();
+        self.last_content_scan = None;
+    }
+
+    /// 开启内容索引时，定期在后台扫描文件内容并取回结果
+    fn check_content_index(&mut self) {
+        if !self.config.content_indexing || self.locked {
+            return;
+        }
+
+        // 索引在后台线程中更新并保存，这里只换成新的索引
+        if let Some(index) = self.content_indexer.poll() {
+            self.content_index = Arc::new(index);
+            self.smart_members.clear();
+            if self.search_query.contains("content:") {
+                self.force_update_filter();
+            }
+        }
+
+        let due = self
+            .last_content_scan
+            .is_none_or(|at| at.elapsed() >= CONTENT_SCAN_INTERVAL);
+        if due && !self.content_indexer.is_running() {
+            self.content_indexer.start(
+                content_index::roots(&self.entries),
+                Arc::clone(&self.content_index),
+                Self::content_index_path(&self.config, self.data_store.as_ref()),
+            );
+            self.last_content_scan = Some(Instant::now());
+        }
+    }
+
+    /// 以当前存储为准重新记录同步状态（切换数据文件后调用，不与新文件合并）
+    fn reset_sync_state(&mut self) {
+        self.base_entries = self.entries.clone();
+        self.base_user_data = self.user_data.clone();
+        self.last_disk_version = self.data_store.disk_version();
+        self.pending_merge = None;
+    }
+
+    /// 定期检查数据文件是否被其他实例或程序修改
+    fn check_external_changes(&mut self) {
+        if self.last_sync_check.elapsed() < SYNC_CHECK_INTERVAL {
+            return;
+        }
+        self.last_sync_check = Instant::now();
+
+        if self.locked
+            || self.data_save_blocked
+            || self.pending_merge.is_some()
+            || self.data_store.disk_version() == self.last_disk_version
+        {
+            return;
+        }
+
+        match self.merge_external_changes() {
+            // 本地还有未写入的修改时，把合并结果写回
+            Ok(false)
+                if self.entries != self.base_entries
+                    || !sync::same_metadata(&self.user_data, &self.base_user_data) =>
+            {
+                if let Err(e) = self.save_user_data() {
+                    self.library_status = format!("保存合并结果失败: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.library_status = format!("读取外部修改失败: {}", e);
+            }
+        }
+    }
+
+    /// 读取磁盘上的数据并与本地三方合并，返回是否存在需要用户处理的冲突
+    fn merge_external_changes(&mut self) -> Result<bool, String> {
+        let disk_version = self.data_store.disk_version();
+        let mut theirs = self.data_store.load()?.data;
+        let their_entries = std::mem::take(&mut theirs.entries);
+        let result = sync::merge_entries(&self.base_entries, &self.entries, &their_entries);
+
+        // 元数据同样三方合并；双方改了同一项时保留本地的值并提示
+        let metadata_conflicts =
+            sync::merge_user_data(&self.base_user_data, &mut self.user_data, &theirs);
+        self.base_user_data = theirs;
+        if !metadata_conflicts.is_empty() {
+            self.library_status = format!(
+                "数据文件已被其他实例修改，以下设置两边都有改动，已保留本地的设置: {}",
+                metadata_conflicts.join("、")
+            );
+        }
+
+        if result.conflicts.is_empty() {
+            self.apply_merged_entries(result.entries, their_entries, disk_version);
+            Ok(false)
+        } else {
+            let choices = vec![Resolution::Ours; result.conflicts.len()];
+            self.pending_merge = Some(PendingMerge {
+                result,
+                choices,
+                their_entries,
+                disk_version,
+            });
+            Ok(true)
+        }
+    }
+
+    fn apply_merged_entries(
+        &mut self,
+        mut merged: Vec<FileEntry>,
+        their_entries: Vec<FileEntry>,
+        disk_version: Option<String>,
+    ) {
+        // 两边各自修改集合成员时，合并结果可能出现循环引用
+        if let Some(notice) = Self::cycle_notice(collections::break_cycles(&mut merged)) {
+            self.data_notice = Some(notice);
+        }
+        if merged != self.entries {
+            self.entries = merged;
+            self.reset_entry_view_state();
+            self.rebuild_tag_set();
+            self.force_update_filter();
+        }
+        self.base_entries = their_entries;
+        self.last_disk_version = disk_version;
+    }
+
+    /// 清除依赖条目索引的界面状态（条目列表被整体替换后调用）
+    fn reset_entry_view_state(&mut self) {
+        self.selected_entries.clear();
+        self.multi_select_mode = false;
+        self.expanded_entries.clear();
+        self.focused_entry = None;
+        self.show_tag_editor = false;
+        self.editing_entry_index = None;
+        self.editing_collection_index = None;
+        self.collection_child_selection.clear();
+        self.show_delete_confirm = false;
+        self.delete_entry_index = None;
     }
 
     fn add_entry(&mut self) {
-        // 对于集合类型，不需要路径检查
+        // 对于集合类型，不需要路径检查（命令类型的路径为程序）
         if self.add_entry_type != crate::file_entry::EntryType::Collection
             && self.add_path_input.is_empty()
         {
@@ -348,7 +948,7 @@ impl FileManagerApp {
             return;
         }
 
-        let tags = FileEntry::parse_tags(&self.add_tags_input);
+        let tags = self.parse_tag_input(&self.add_tags_input);
         let description = if self.add_description_input.is_empty() {
             None
         } else {
@@ -361,7 +961,7 @@ impl FileManagerApp {
             Some(self.add_nickname_input.clone())
         };
 
-        let entry = match self.add_entry_type {
+        let mut entry = match self.add_entry_type {
             crate::file_entry::EntryType::WebLink => {
                 let name = if self.add_name_input.is_empty() {
                     // 从URL中提取网站名称作为默认名称
@@ -378,6 +978,28 @@ impl FileManagerApp {
                     tags.clone(),
                 )
             }
+            crate::file_entry::EntryType::Command => {
+                let Ok(command) = self.command_from_inputs() else {
+                    return;
+                };
+                let name = if self.add_name_input.is_empty() {
+                    Path::new(&command.program)
+                        .file_stem()
+                        .map_or_else(|| command.program.clone(), |n| n.to_string_lossy().to_string())
+                } else {
+                    self.add_name_input.clone()
+                };
+                FileEntry::new_command(name, command, nickname, description, tags.clone())
+            }
+            crate::file_entry::EntryType::Collection if self.add_smart_collection => {
+                FileEntry::new_smart_collection(
+                    self.add_name_input.clone(),
+                    nickname,
+                    description,
+                    tags.clone(),
+                    self.add_smart_query_input.trim().to_string(),
+                )
+            }
             crate::file_entry::EntryType::Collection => {
                 let mut child_entry_ids = Vec::new();
                 for &idx in &self.collection_child_selection {
@@ -420,13 +1042,21 @@ impl FileManagerApp {
             }
         };
 
+        RuleSet::new(&self.user_data.auto_tag_rules).apply(&mut entry);
+
+        // 本机添加的命令运行前不需要再确认
+        if entry.command.is_some() {
+            self.config.trust_command(&entry);
+            let _ = self.save_config();
+        }
+
         // 更新标签集合
-        for tag in &tags {
+        for tag in &entry.tags {
             self.all_tags.insert(tag.clone());
         }
 
         self.entries.push(entry);
-        let _ = self.save_user_data();
+        let _ = self.commit_entries("添加条目");
 
         // 清空输入框
         self.add_path_input.clear();
@@ -436,6 +1066,9 @@ impl FileManagerApp {
         self.add_description_input.clear();
         self.add_entry_type = crate::file_entry::EntryType::File;
         self.collection_child_selection.clear();
+        self.add_smart_collection = false;
+        self.add_smart_query_input.clear();
+        self.clear_command_inputs();
         self.show_add_dialog = false;
 
         // 强制重新过滤并更新索引
@@ -457,91 +1090,569 @@ impl FileManagerApp {
             // 更新标签集合，移除不再使用的标签
             self.rebuild_tag_set();
 
-            let _ = self.save_user_data();
+            let _ = self.commit_entries("删除条目");
             self.force_update_filter();
         }
     }
 
-    fn rebuild_tag_set(&mut self) {
-        self.all_tags.clear();
-        for entry in &self.entries {
-            for tag in &entry.tags {
-                self.all_tags.insert(tag.clone());
-            }
-        }
+    /// 打开条目的变更历史窗口
+    fn show_entry_history(&mut self, index: usize) {
+        let Some(entry) = self.entries.get(index) else {
+            return;
+        };
+        let entry_id = entry.id.clone();
+        let events = self.load_entry_events(&entry_id);
+        self.entry_history = Some((entry_id, events));
     }
 
-    fn open_path(&self, path: &PathBuf) {
-        #[cfg(target_os = "windows")]
-        {
-            let _ = std::process::Command::new("explorer").arg(path).spawn();
-        }
+    fn load_entry_events(&self, entry_id: &str) -> Vec<JournalEvent> {
+        journal::read_events(&journal::journal_path(self.data_store.location()))
+            .into_iter()
+            .filter(|e| e.entry_id == entry_id)
+            .collect()
+    }
 
-        #[cfg(target_os = "macos")]
-        {
-            let _ = std::process::Command::new("open").arg(path).spawn();
+    /// 把条目恢复为日志中记录的某个版本
+    fn restore_entry_version(&mut self, version: FileEntry) {
+        match self.entries.iter().position(|e| e.id == version.id) {
+            Some(position) => self.entries[position] = version,
+            None => self.entries.push(version),
         }
+        self.rebuild_tag_set();
+        let _ = self.commit_entries("恢复历史版本");
+        self.force_update_filter();
+    }
 
-        #[cfg(target_os = "linux")]
-        {
-            let _ = std::process::Command::new("xdg-open").arg(path).spawn();
-        }
+    /// 解析恢复时间：支持 `YYYY-MM-DD HH:MM` 和 `YYYY-MM-DD`（当天结束时），按本地时间解释
+    fn parse_restore_time(input: &str) -> Option<DateTime<Utc>> {
+        let input = input.trim();
+        let naive = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(23, 59, 59))
+            })?;
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
     }
 
-    fn open_entry(&self, entry: &FileEntry) {
-        match entry.entry_type {
-            crate::file_entry::EntryType::WebLink => {
-                if let Some(url) = &entry.url {
-                    self.open_url(url);
+    fn render_journal_settings(&mut self, ui: &mut egui::Ui) {
+        let path = journal::journal_path(self.data_store.location());
+        ui.label(format!("日志文件: {}", path.display()));
+        ui.label(format!("记录者: {}", self.actor));
+
+        ui.add_space(8.0);
+        ui.label("将资料库恢复到指定时间:");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.restore_date_input)
+                    .hint_text("2024-05-01 18:00")
+                    .desired_width(140.0),
+            );
+            if ui.button("预览").clicked() {
+                match Self::parse_restore_time(&self.restore_date_input) {
+                    Some(at) => {
+                        let events = journal::read_events(&path);
+                        let restored = journal::state_at(&self.entries, &events, at);
+                        self.restore_preview = Some((at, restored));
+                        self.journal_status.clear();
+                    }
+                    None => {
+                        self.restore_preview = None;
+                        self.journal_status = "时间格式应为 YYYY-MM-DD 或 YYYY-MM-DD HH:MM".to_string();
+                    }
                 }
             }
-            crate::file_entry::EntryType::Collection => {
-                self.open_collection(entry);
-            }
-            _ => {
-                self.open_path(&entry.path);
-            }
-        }
-    }
+        });
 
-    fn open_url(&self, url: &str) {
-        #[cfg(target_os = "windows")]
-        {
-            let _ = std::process::Command::new("cmd")
-                .args(&["/C", "start", url])
-                .spawn();
+        let mut apply = false;
+        let mut cancel = false;
+        if let Some((at, restored)) = &self.restore_preview {
+            ui.label(format!(
+                "{} 时有 {} 个条目（当前 {} 个）",
+                at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                restored.len(),
+                self.entries.len()
+            ));
+            ui.horizontal(|ui| {
+                apply = ui.button("恢复").on_hover_text("恢复后可以撤销").clicked();
+                cancel = ui.button("取消").clicked();
+            });
+        }
+        if cancel {
+            self.restore_preview = None;
         }
 
-        #[cfg(target_os = "macos")]
-        {
-            let _ = std::process::Command::new("open").arg(url).spawn();
+        if apply && let Some((at, restored)) = self.restore_preview.take() {
+            self.entries = restored;
+            self.reset_entry_view_state();
+            self.rebuild_tag_set();
+            let label = format!("恢复到 {}", at.with_timezone(&Local).format("%Y-%m-%d %H:%M"));
+            self.journal_status = match self.commit_entries(&label) {
+                Ok(()) => format!("已{}", label),
+                Err(e) => format!("保存失败: {}", e),
+            };
+            self.force_update_filter();
         }
 
-        #[cfg(target_os = "linux")]
-        {
-            let _ = std::process::Command::new("xdg-open").arg(url).spawn();
+        if !self.journal_status.is_empty() {
+            ui.label(&self.journal_status);
         }
     }
 
-    fn open_collection(&self, collection: &FileEntry) {
-        // 依次打开集合中的所有子项目，现在使用ID而不是索引
-        for child_id in &collection.child_entries {
-            if let Some(child_entry) = self.entries.iter().find(|e| &e.id == child_id) {
-                self.open_entry(child_entry);
+    /// 单个条目的变更历史窗口
+    fn render_entry_history_window(&mut self, ctx: &egui::Context) {
+        let Some((entry_id, events)) = &self.entry_history else {
+            return;
+        };
+
+        let title = self
+            .entries
+            .iter()
+            .find(|e| &e.id == entry_id)
+            .map(|e| e.nickname.clone().unwrap_or_else(|| e.name.clone()))
+            .or_else(|| events.last().map(|e| e.entry_name()))
+            .unwrap_or_default();
+
+        let mut open = true;
+        let mut restore = None;
+        egui::Window::new(format!("历史记录 - {}", title))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(true)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                if events.is_empty() {
+                    ui.label("暂无记录");
+                    return;
+                }
 
-                // 在打开多个项目之间添加短暂延迟，避免系统过载
-                std::thread::sleep(std::time::Duration::from_millis(50));
-            }
+                egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                    for event in events.iter().rev() {
+                        ui.group(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.strong(match event.kind {
+                                    journal::ChangeKind::Added => "添加",
+                                    journal::ChangeKind::Updated => "修改",
+                                    journal::ChangeKind::Removed => "删除",
+                                });
+                                ui.label(
+                                    event
+                                        .ts
+                                        .with_timezone(&Local)
+                                        .format("%Y-%m-%d %H:%M:%S")
+                                        .to_string(),
+                                );
+                                ui.small(&event.actor);
+                            });
+                            ui.label(event.summary());
+
+                            // 删除记录恢复删除前的版本，其余恢复修改后的版本
+                            if let Some(version) = event.after.as_ref().or(event.before.as_ref())
+                                && ui.small_button("恢复到此版本").clicked()
+                            {
+                                restore = Some(version.clone());
+                            }
+                        });
+                    }
+                });
+            });
+
+        if let Some(version) = restore {
+            let entry_id = version.id.clone();
+            self.restore_entry_version(version);
+            // 刷新窗口中的记录
+            let events = self.load_entry_events(&entry_id);
+            self.entry_history = Some((entry_id, events));
+        }
+        if !open {
+            self.entry_history = None;
         }
     }
 
-    fn edit_entry_tags(&mut self, index: usize) {
-        if index < self.entries.len() {
-            self.editing_entry_index = Some(index);
-            let entry = &self.entries[index];
-            self.add_tags_input = entry.tags.join(" ");
+    /// 把条目与前一个/后一个条目交换位置
+    fn move_entry(&mut self, index: usize, up: bool) {
+        let target = if up {
+            index.checked_sub(1)
+        } else {
+            Some(index + 1).filter(|&i| i < self.entries.len())
+        };
+        let Some(target) = target else {
+            return;
+        };
+
+        self.entries.swap(index, target);
+        // 依赖索引的界面状态跟随条目移动
+        let index_expanded = self.expanded_entries.remove(&index);
+        if self.expanded_entries.remove(&target) {
+            self.expanded_entries.insert(index);
+        }
+        if index_expanded {
+            self.expanded_entries.insert(target);
+        }
+        if self.focused_entry == Some(index) {
+            self.focused_entry = Some(target);
+        }
+
+        let _ = self.commit_entries("调整顺序");
+        self.force_update_filter();
+    }
+
+    /// 置顶或取消置顶
+    fn toggle_pin(&mut self, index: usize) {
+        let Some(entry) = self.entries.get_mut(index) else {
+            return;
+        };
+        entry.pinned = !entry.pinned;
+        let label = if entry.pinned { "置顶" } else { "取消置顶" };
+        let _ = self.commit_entries(label);
+        self.force_update_filter();
+    }
+
+    fn rebuild_tag_set(&mut self) {
+        self.all_tags.clear();
+        for entry in &self.entries {
+            for tag in &entry.tags {
+                self.all_tags.insert(tag.clone());
+            }
+        }
+    }
+
+    /// 打开条目，并记录打开时间和次数（集合中被打开的子项目也会记录）
+    fn open_entry(&mut self, index: usize) {
+        let Some(entry) = self.entries.get(index).cloned() else {
+            return;
+        };
+        // 导入或同步得到的命令在本机第一次运行前需要确认
+        if !self.untrusted_commands(&entry).is_empty() {
+            self.confirm_run_entry = Some(entry.id.clone());
+            return;
+        }
+        let opened_ids = self.launch_entry(&entry);
+
+        // 运行了需要记录输出的命令时打开运行日志
+        if self.entries.iter().any(|e| {
+            opened_ids.contains(&e.id) && e.command.as_ref().is_some_and(|c| !c.in_terminal)
+        }) {
+            self.show_run_log = true;
+        }
+
+        let now = Utc::now();
+        for entry in self
+            .entries
+            .iter_mut()
+            .filter(|e| opened_ids.contains(&e.id))
+        {
+            entry.mark_opened(now);
+        }
+        // 打开不是可撤销的修改，不记入撤销历史
+        if let Err(e) = self.persist_entries(None) {
+            self.library_status = format!("记录打开时间失败: {}", e);
+        }
+        if self.config.sort_order != SortOrder::Manual || self.time_filter != TimeFilter::All {
+            self.force_update_filter();
+        }
+    }
+
+    /// 打开条目时将要运行、但本机还没有确认过的命令条目（集合为其中的命令）
+    fn untrusted_commands<'a>(&'a self, entry: &'a FileEntry) -> Vec<&'a FileEntry> {
+        let targets = if entry.entry_type == crate::file_entry::EntryType::Collection {
+            collections::launch_items(&self.members(), entry)
+                .0
+                .into_iter()
+                .map(|item| item.entry)
+                .collect()
+        } else {
+            vec![entry]
+        };
+        targets
+            .into_iter()
+            .filter(|e| !self.config.is_trusted_command(e))
+            .collect()
+    }
+
+    /// 列出本机没有确认过的命令，确认后记住这些命令并打开条目
+    fn render_command_confirm_dialog(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.confirm_run_entry.clone() else {
+            return;
+        };
+        let Some(index) = self.entries.iter().position(|e| e.id == id) else {
+            self.confirm_run_entry = None;
+            return;
+        };
+        let untrusted: Vec<FileEntry> = self
+            .untrusted_commands(&self.entries[index])
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new("确认运行命令")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("以下命令来自导入或同步的数据，还没有在本机运行过：");
+                ui.add_space(8.0);
+                for entry in &untrusted {
+                    let Some(command) = &entry.command else {
+                        continue;
+                    };
+                    ui.strong(&entry.name);
+                    ui.monospace(launcher::command_line(command));
+                    if let Some(dir) = &command.working_dir {
+                        ui.small(format!("工作目录: {}", dir.display()));
+                    }
+                    if !command.env.is_empty() {
+                        ui.small(format!(
+                            "环境变量: {}",
+                            launcher::format_env(&command.env).replace('\n', " ")
+                        ));
+                    }
+                    ui.add_space(4.0);
+                }
+                ui.add_space(8.0);
+                ui.label("请确认命令来源可信后再运行。");
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("取消").clicked() {
+                        cancelled = true;
+                    }
+                    ui.add_space(20.0);
+                    if ui.button("运行").clicked() {
+                        confirmed = true;
+                    }
+                });
+            });
+
+        if confirmed {
+            for entry in &untrusted {
+                self.config.trust_command(entry);
+            }
+            let _ = self.save_config();
+            self.confirm_run_entry = None;
+            self.open_entry(index);
+        } else if cancelled {
+            self.confirm_run_entry = None;
+        }
+    }
+
+    /// 启动条目对应的文件、网页或集合，返回被打开的条目ID。集合（智能集合为当前满足
+    /// 搜索条件的条目）按打开方式和各子项目的延迟在后台线程中启动
+    fn launch_entry(&self, entry: &FileEntry) -> Vec<String> {
+        let mut opened = vec![entry.id.clone()];
+        if entry.entry_type == crate::file_entry::EntryType::Collection {
+            let (items, nested) = collections::launch_items(&self.members(), entry);
+            opened.extend(nested.iter().map(|c| c.id.clone()));
+            opened.extend(items.iter().map(|item| item.entry.id.clone()));
+            launcher::launch(
+                items
+                    .iter()
+                    .filter_map(|item| Some((item.delay, Target::of(item.entry)?)))
+                    .collect(),
+                entry.launch_mode,
+                &self.run_log,
+            );
+        } else if let Some(target) = Target::of(entry) {
+            target.open(&self.run_log);
+        }
+        opened
+    }
+
+    /// 添加或编辑命令条目时的输入项
+    fn render_command_fields(&mut self, ui: &mut egui::Ui) {
+        ui.label("程序:");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.add_path_input);
+            if ui.button("选择程序").clicked()
+                && let Some(path) = rfd::FileDialog::new().pick_file()
+            {
+                self.add_path_input = path.to_string_lossy().to_string();
+            }
+        });
+        ui.small("程序路径或 PATH 中的程序名，如: cargo");
+
+        ui.add_space(8.0);
+        ui.label("参数:");
+        ui.text_edit_singleline(&mut self.add_command_args);
+        ui.small("用空格分隔，含空格的参数用引号括起，如: build --release \"my file\"");
+
+        ui.add_space(8.0);
+        ui.label("工作目录 (可选):");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.add_command_dir);
+            if ui.button("选择文件夹").clicked()
+                && let Some(path) = rfd::FileDialog::new().pick_folder()
+            {
+                self.add_command_dir = path.to_string_lossy().to_string();
+            }
+        });
+
+        ui.add_space(8.0);
+        ui.label("环境变量 (可选):");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.add_command_env)
+                .desired_rows(2)
+                .hint_text("每行一个，如: RUST_LOG=debug"),
+        );
+
+        ui.checkbox(&mut self.add_command_terminal, "在终端中运行")
+            .on_hover_text("输出显示在终端窗口中，不记录到运行日志");
+
+        if !self.add_path_input.trim().is_empty()
+            && let Err(e) = self.command_from_inputs()
+        {
+            ui.colored_label(egui::Color32::from_rgb(200, 50, 50), e);
+        }
+    }
+
+    /// 由输入项生成命令，参数或环境变量写法有误时返回错误
+    fn command_from_inputs(&self) -> Result<CommandSpec, String> {
+        let program = self.add_path_input.trim();
+        if program.is_empty() {
+            return Err("请填写程序".to_string());
+        }
+        let working_dir = self.add_command_dir.trim();
+        Ok(CommandSpec {
+            program: program.to_string(),
+            args: launcher::split_args(&self.add_command_args)?,
+            working_dir: (!working_dir.is_empty()).then(|| PathBuf::from(working_dir)),
+            env: launcher::parse_env(&self.add_command_env)?,
+            in_terminal: self.add_command_terminal,
+        })
+    }
+
+    fn clear_command_inputs(&mut self) {
+        self.add_command_args.clear();
+        self.add_command_dir.clear();
+        self.add_command_env.clear();
+        self.add_command_terminal = false;
+    }
+
+    /// 命令运行日志窗口：左侧为运行记录，右侧为选中记录的输出
+    fn render_run_log_window(&mut self, ctx: &egui::Context) {
+        if !self.show_run_log {
+            return;
+        }
+        if self.run_log.is_running() {
+            // 后台线程不能直接刷新界面，运行期间定时刷新以显示新的输出
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+
+        let mut open = true;
+        let mut clear = false;
+        egui::Window::new("运行日志")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(true)
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                let runs = self.run_log.runs();
+                if runs.is_empty() {
+                    ui.label("还没有运行过命令");
+                    return;
+                }
+                let selected = self
+                    .run_log_selected
+                    .filter(|id| runs.iter().any(|run| run.id == *id))
+                    .or_else(|| runs.last().map(|run| run.id));
+
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width(200.0);
+                        egui::ScrollArea::vertical()
+                            .id_source("run_list")
+                            .max_height(360.0)
+                            .show(ui, |ui| {
+                                for run in runs.iter().rev() {
+                                    let text = egui::RichText::new(format!(
+                                        "{} {}\n{}",
+                                        run.started_at.format("%H:%M:%S"),
+                                        run.name,
+                                        run.status.label()
+                                    ));
+                                    let text = match run.status {
+                                        launcher::RunStatus::Running => text,
+                                        _ if run.status.is_success() => text,
+                                        _ => text.color(egui::Color32::from_rgb(200, 50, 50)),
+                                    };
+                                    if ui.selectable_label(selected == Some(run.id), text).clicked() {
+                                        self.run_log_selected = Some(run.id);
+                                    }
+                                }
+                            });
+                        if ui.small_button("清除已结束").clicked() {
+                            clear = true;
+                        }
+                    });
+                    ui.separator();
+
+                    let Some(run) = runs.iter().find(|run| Some(run.id) == selected) else {
+                        return;
+                    };
+                    ui.vertical(|ui| {
+                        ui.monospace(&run.command_line);
+                        ui.horizontal(|ui| {
+                            ui.strong(run.status.label());
+                            if let Some(finished_at) = run.finished_at {
+                                let seconds = (finished_at - run.started_at).num_milliseconds()
+                                    as f64
+                                    / 1000.0;
+                                ui.small(format!("用时 {:.1} 秒", seconds));
+                            }
+                        });
+                        if run.dropped_lines > 0 {
+                            ui.small(format!("（前 {} 行输出已丢弃）", run.dropped_lines));
+                        }
+                        ui.separator();
+                        egui::ScrollArea::both()
+                            .id_source("run_output")
+                            .max_height(320.0)
+                            .stick_to_bottom(true)
+                            .show(ui, |ui| {
+                                for line in &run.output {
+                                    let text = egui::RichText::new(&line.text).monospace();
+                                    ui.label(if line.stderr {
+                                        text.color(egui::Color32::from_rgb(200, 50, 50))
+                                    } else {
+                                        text
+                                    });
+                                }
+                            });
+                    });
+                });
+            });
+
+        if clear {
+            self.run_log.clear_finished();
+        }
+        if !open {
+            self.show_run_log = false;
+        }
+    }
+
+    fn edit_entry_tags(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.editing_entry_index = Some(index);
+            let entry = &self.entries[index];
+            self.add_tags_input = entry.tags.join(" ");
             self.add_nickname_input = entry.nickname.clone().unwrap_or_default();
             self.add_description_input = entry.description.clone().unwrap_or_default();
+            if let Some(command) = &entry.command {
+                self.add_path_input = command.program.clone();
+                self.add_command_args = launcher::join_args(&command.args);
+                self.add_command_dir = command
+                    .working_dir
+                    .as_ref()
+                    .map(|dir| dir.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                self.add_command_env = launcher::format_env(&command.env);
+                self.add_command_terminal = command.in_terminal;
+            }
             self.show_tag_editor = true;
         }
     }
@@ -549,7 +1660,7 @@ impl FileManagerApp {
     fn save_entry_edit(&mut self) {
         if let Some(index) = self.editing_entry_index {
             if index < self.entries.len() {
-                let new_tags = FileEntry::parse_tags(&self.add_tags_input);
+                let new_tags = self.parse_tag_input(&self.add_tags_input);
                 let new_nickname = if self.add_nickname_input.is_empty() {
                     None
                 } else {
@@ -562,9 +1673,19 @@ impl FileManagerApp {
                 };
 
                 // 更新条目
+                if self.entries[index].command.is_some() {
+                    let Ok(command) = self.command_from_inputs() else {
+                        return;
+                    };
+                    self.entries[index].path = PathBuf::from(&command.program);
+                    self.entries[index].command = Some(command);
+                    self.config.trust_command(&self.entries[index]);
+                    let _ = self.save_config();
+                }
                 self.entries[index].tags = new_tags.clone();
                 self.entries[index].nickname = new_nickname;
                 self.entries[index].description = new_description;
+                self.entries[index].touch();
 
                 // 重建标签集合
                 self.rebuild_tag_set();
@@ -572,7 +1693,7 @@ impl FileManagerApp {
                     self.all_tags.insert(tag.clone());
                 }
 
-                let _ = self.save_user_data();
+                let _ = self.commit_entries("编辑条目");
                 self.force_update_filter();
             }
         }
@@ -582,6 +1703,8 @@ impl FileManagerApp {
         self.editing_entry_index = None;
         self.add_tags_input.clear();
         self.add_description_input.clear();
+        self.add_path_input.clear();
+        self.clear_command_inputs();
     }
 
     fn export_data(&mut self) {
@@ -590,43 +1713,69 @@ impl FileManagerApp {
             .set_file_name("file_manager_export.json")
             .save_file()
         {
-            let export_data = UserData {
-                entries: self.entries.clone(),
-                version: env!("CARGO_PKG_VERSION").to_string(),
+            let export_data = self.export_snapshot();
+
+            self.export_status = match self.write_export_file(&path, &export_data) {
+                Ok(()) => format!("导出成功: {}", path.display()),
+                Err(e) => format!("导出失败: {}", e),
             };
+        }
+    }
 
-            match serde_json::to_string_pretty(&export_data) {
-                Ok(json) => match std::fs::write(&path, json) {
-                    Ok(_) => {
-                        self.export_status = format!("导出成功: {}", path.display());
-                    }
-                    Err(e) => {
-                        self.export_status = format!("导出失败: {}", e);
-                    }
-                },
-                Err(e) => {
-                    self.export_status = format!("序列化失败: {}", e);
-                }
-            }
+    /// 导出或备份的数据：条目连同标签别名和标签显示信息
+    fn export_snapshot(&self) -> UserData {
+        UserData {
+            tag_aliases: self.user_data.tag_aliases.clone(),
+            tag_registry: self.user_data.tag_registry.clone(),
+            auto_tag_rules: self.user_data.auto_tag_rules.clone(),
+            ..UserData::with_entries(self.entries.clone())
         }
     }
 
+    /// 写出导出/备份文件，勾选“加密导出”时使用导出密码加密
+    fn write_export_file(&self, path: &Path, data: &UserData) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(data).map_err(|e| format!("序列化失败: {}", e))?;
+        let content = if self.export_encrypted {
+            if self.export_passphrase.is_empty() {
+                return Err("请先输入导出密码".to_string());
+            }
+            crypto::encrypt(&json, &EncryptionKey::derive(&self.export_passphrase)?)?
+        } else {
+            json
+        };
+        std::fs::write(path, content).map_err(|e| e.to_string())
+    }
+
     fn import_data(&mut self) {
         if let Some(path) = rfd::FileDialog::new()
             .add_filter("JSON文件", &["json"])
             .pick_file()
         {
-            match std::fs::read_to_string(&path) {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("读取文件失败: {}", e))
+                .and_then(|content| {
+                    if !crypto::is_encrypted(&content) {
+                        Ok(content)
+                    } else if self.import_passphrase.is_empty() {
+                        Err("文件已加密，请先输入导入密码".to_string())
+                    } else {
+                        crypto::decrypt(&content, &self.import_passphrase)
+                            .map(|(plaintext, _)| plaintext)
+                            .map_err(|e| format!("解密失败: {}", e))
+                    }
+                });
+            match content {
                 Ok(content) => {
-                    match serde_json::from_str::<UserData>(&content) {
+                    // 导入的文件同样经过迁移管道，兼容各个历史版本的格式
+                    match config::parse_user_data(&content) {
                         Ok(import_data) => {
                             let import_count = import_data.entries.len();
 
                             if self.import_merge_mode {
                                 // 合并模式：添加到现有数据
                                 for entry in import_data.entries {
-                                    // 检查是否已存在相同路径的条目
-                                    if !self.entries.iter().any(|e| e.path == entry.path) {
+                                    // 跳过已有的相同条目
+                                    if !self.entries.iter().any(|e| entry.is_duplicate_of(e)) {
                                         // 更新标签集合
                                         for tag in &entry.tags {
                                             self.all_tags.insert(tag.clone());
@@ -634,59 +1783,62 @@ impl FileManagerApp {
                                         self.entries.push(entry);
                                     }
                                 }
+                                // 标签别名、显示信息和自动标签规则只补充本地没有的
+                                for (alias, canonical) in import_data.tag_aliases {
+                                    self.user_data
+                                        .tag_aliases
+                                        .entry(alias)
+                                        .or_insert(canonical);
+                                }
+                                for (tag, info) in import_data.tag_registry {
+                                    self.user_data.tag_registry.entry(tag).or_insert(info);
+                                }
+                                for rule in import_data.auto_tag_rules {
+                                    if !self.user_data.auto_tag_rules.contains(&rule) {
+                                        self.user_data.auto_tag_rules.push(rule);
+                                    }
+                                }
                                 self.import_status =
                                     format!("合并导入成功: {} 个条目", import_count);
                             } else {
                                 // 替换模式：替换所有数据
                                 self.entries = import_data.entries;
+                                self.user_data.tag_aliases = import_data.tag_aliases;
+                                self.user_data.tag_registry = import_data.tag_registry;
+                                self.user_data.auto_tag_rules = import_data.auto_tag_rules;
                                 self.rebuild_tag_set();
                                 self.import_status =
                                     format!("替换导入成功: {} 个条目", import_count);
                             }
 
-                            let _ = self.save_user_data();
+                            collections::break_cycles(&mut self.entries);
+                            let _ = self.commit_entries("导入数据");
                             self.force_update_filter();
                         }
                         Err(e) => {
-                            // 尝试兼容旧格式
-                            if let Ok(entries) = serde_json::from_str::<Vec<FileEntry>>(&content) {
-                                let import_count = entries.len();
-
-                                if self.import_merge_mode {
-                                    for entry in entries {
-                                        if !self.entries.iter().any(|e| e.path == entry.path) {
-                                            for tag in &entry.tags {
-                                                self.all_tags.insert(tag.clone());
-                                            }
-                                            self.entries.push(entry);
-                                        }
-                                    }
-                                    self.import_status =
-                                        format!("合并导入成功(旧格式): {} 个条目", import_count);
-                                } else {
-                                    self.entries = entries;
-                                    self.rebuild_tag_set();
-                                    self.import_status =
-                                        format!("替换导入成功(旧格式): {} 个条目", import_count);
-                                }
-
-                                let _ = self.save_user_data();
-                                self.force_update_filter();
-                            } else {
-                                self.import_status = format!("文件格式错误: {}", e);
-                            }
+                            self.import_status = format!("文件格式错误: {}", e);
                         }
                     }
                 }
                 Err(e) => {
-                    self.import_status = format!("读取文件失败: {}", e);
+                    self.import_status = e;
                 }
             }
         }
     }
 
+    /// 解析输入的标签，别名换成对应的标准标签
+    fn parse_tag_input(&self, tag_text: &str) -> Vec<String> {
+        let mut tags: Vec<String> = FileEntry::parse_tags(tag_text)
+            .iter()
+            .map(|tag| tags::resolve_alias(tag, &self.user_data.tag_aliases))
+            .collect();
+        tags::dedup(&mut tags);
+        tags
+    }
+
     fn batch_add_tags(&mut self, tag_text: &str) {
-        let new_tags = FileEntry::parse_tags(tag_text);
+        let new_tags = self.parse_tag_input(tag_text);
         if new_tags.is_empty() {
             return;
         }
@@ -703,21 +1855,20 @@ impl FileManagerApp {
                     }
                 }
                 if entry_modified {
-                    entry.tags.sort();
-                    entry.tags.dedup();
+                    entry.touch();
                     modified_count += 1;
                 }
             }
         }
 
         if modified_count > 0 {
-            let _ = self.save_user_data();
+            let _ = self.commit_entries("批量添加标签");
             self.force_update_filter();
         }
     }
 
     fn batch_remove_tags(&mut self, tag_text: &str) {
-        let remove_tags = FileEntry::parse_tags(tag_text);
+        let remove_tags = self.parse_tag_input(tag_text);
         if remove_tags.is_empty() {
             return;
         }
@@ -728,6 +1879,7 @@ impl FileManagerApp {
                 let original_len = entry.tags.len();
                 entry.tags.retain(|tag| !remove_tags.contains(tag));
                 if entry.tags.len() != original_len {
+                    entry.touch();
                     modified_count += 1;
                 }
             }
@@ -735,7 +1887,7 @@ impl FileManagerApp {
 
         if modified_count > 0 {
             self.rebuild_tag_set();
-            let _ = self.save_user_data();
+            let _ = self.commit_entries("批量移除标签");
             self.force_update_filter();
         }
     }
@@ -760,18 +1912,22 @@ impl FileManagerApp {
         }
 
         let input_lower = input_text.to_lowercase();
-        let matching_tags: Vec<String> = self
-            .all_tags
-            .iter()
-            .filter(|tag| tag.to_lowercase().contains(&input_lower) && !input_text.contains(*tag))
-            .cloned()
-            .collect();
+        let matching_tags = Self::visible_tags(
+            &self.user_data.tag_registry,
+            self.all_tags
+                .iter()
+                .filter(|tag| {
+                    tag.to_lowercase().contains(&input_lower) && !input_text.contains(*tag)
+                })
+                .cloned()
+                .collect(),
+        );
 
         if !matching_tags.is_empty() {
             ui.small("建议:");
             ui.horizontal_wrapped(|ui| {
-                for tag in matching_tags.iter().take(6) {
-                    if ui.small_button(tag).clicked() {
+                for (tag, info) in matching_tags.iter().take(6) {
+                    if Self::tag_chip(ui, tag, info).clicked() {
                         if !self.add_tags_input.contains(tag) {
                             if self.add_tags_input.is_empty() {
                                 self.add_tags_input = tag.clone();
@@ -797,6 +1953,13 @@ impl FileManagerApp {
         ui.separator();
 
         ui.label("导出数据:");
+        ui.checkbox(&mut self.export_encrypted, "加密导出");
+        if self.export_encrypted {
+            ui.horizontal(|ui| {
+                ui.label("导出密码:");
+                ui.add(egui::TextEdit::singleline(&mut self.export_passphrase).password(true));
+            });
+        }
         if ui.button("导出").clicked() {
             self.export_data();
         }
@@ -823,6 +1986,15 @@ impl FileManagerApp {
             "替换模式：清空现有数据"
         });
 
+        ui.horizontal(|ui| {
+            ui.label("导入密码:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.import_passphrase)
+                    .password(true)
+                    .hint_text("仅加密文件需要"),
+            );
+        });
+
         if ui.button("导入").clicked() {
             self.import_data();
         }
@@ -876,38 +2048,50 @@ impl FileManagerApp {
 
         ui.label("筛选:");
         ui.text_edit_singleline(&mut self.tag_cloud_filter);
+        ui.checkbox(&mut self.show_hidden_tags, "显示隐藏的标签");
 
+        // 层级标签按树显示，点击上级标签时包含所有下级
         ui.add_space(8.0);
+        let tree = tags::build_tree(&self.entries);
+        let filter = self.tag_cloud_filter.to_lowercase();
         egui::ScrollArea::vertical()
             .max_height(200.0)
             .show(ui, |ui| {
-                for (tag, count) in stats {
-                    if self.tag_cloud_filter.is_empty()
-                        || tag
-                            .to_lowercase()
-                            .contains(&self.tag_cloud_filter.to_lowercase())
-                    {
-                        ui.horizontal(|ui| {
-                            let is_selected = self.selected_tags.contains(&tag);
-                            let mut selected = is_selected;
-                            if ui.checkbox(&mut selected, "").changed() {
-                                if selected {
-                                    self.selected_tags.insert(tag.clone());
-                                } else {
-                                    self.selected_tags.remove(&tag);
-                                }
-                            }
+                for node in &tree {
+                    self.render_tag_node(ui, node, &filter);
+                }
+            });
 
-                            if ui.button(&tag).clicked() {
-                                self.search_query = format!("#{}", tag.trim_start_matches('#'));
-                                self.force_update_filter();
-                            }
+        if !self.selected_tags.is_empty()
+            && ui
+                .button(format!("合并选中的 {} 个标签…", self.selected_tags.len()))
+                .clicked()
+        {
+            let mut sources: Vec<String> = self.selected_tags.iter().cloned().collect();
+            sources.sort();
+            self.tag_operation_target = sources[0].clone();
+            self.tag_operation = Some(TagOperation::Merge { sources });
+            self.tag_status.clear();
+        }
 
-                            ui.label(format!("({})", count));
-                        });
-                    }
+        self.render_tag_operation(ui);
+        self.render_tag_style_editor(ui);
+        if !self.tag_status.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label(&self.tag_status);
+                if ui.small_button("×").clicked() {
+                    self.tag_status.clear();
                 }
             });
+        }
+
+        ui.add_space(12.0);
+        ui.collapsing("标签别名", |ui| {
+            self.render_tag_aliases(ui);
+        });
+        ui.collapsing("自动标签规则", |ui| {
+            self.render_auto_tag_rules(ui);
+        });
 
         ui.add_space(12.0);
 
@@ -932,49 +2116,524 @@ impl FileManagerApp {
         });
     }
 
-    fn render_collection_manager(&mut self, ui: &mut egui::Ui) {
-        ui.horizontal(|ui| {
-            ui.heading("集合管理器");
-            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                if ui.button("×").clicked() {
-                    self.show_collection_manager = false;
+    /// 标签树的一个节点；有筛选词时只显示匹配的标签及其上级，并自动展开
+    fn render_tag_node(&mut self, ui: &mut egui::Ui, node: &TagNode, filter: &str) {
+        fn matches(node: &TagNode, filter: &str) -> bool {
+            node.path.to_lowercase().contains(filter)
+                || node.children.iter().any(|child| matches(child, filter))
+        }
+        if !filter.is_empty() && !matches(node, filter) {
+            return;
+        }
+        // 隐藏的标签连同下级一起隐藏
+        let info = tags::info_for(&self.user_data.tag_registry, &node.path);
+        if info.hidden && !self.show_hidden_tags {
+            return;
+        }
+
+        let row = |this: &mut Self, ui: &mut egui::Ui| {
+            let mut selected = this.selected_tags.contains(&node.path);
+            if ui.checkbox(&mut selected, "").changed() {
+                if selected {
+                    this.selected_tags.insert(node.path.clone());
+                } else {
+                    this.selected_tags.remove(&node.path);
                 }
-            });
-        });
-        ui.separator();
+            }
 
-        // 选择要编辑的集合
-        ui.label("选择集合:");
-        let collections: Vec<(usize, &FileEntry)> = self
-            .entries
-            .iter()
-            .enumerate()
-            .filter(|(_, entry)| entry.entry_type == crate::file_entry::EntryType::Collection)
-            .collect();
+            let hover = match &info.description {
+                Some(description) => format!("{}\n{}", node.path, description),
+                None => node.path.clone(),
+            };
+            if ui
+                .button(Self::tag_text(&info, &node.name))
+                .on_hover_text(hover)
+                .clicked()
+            {
+                this.search_query = node.path.clone();
+                this.force_update_filter();
+            }
 
-        if collections.is_empty() {
-            ui.label("还没有创建任何集合");
-            ui.small("请先添加一个集合类型的条目");
+            if node.own_count == node.total_count {
+                ui.label(format!("({})", node.total_count));
+            } else {
+                ui.label(format!("({}/{})", node.own_count, node.total_count))
+                    .on_hover_text("直接使用该标签的条目数 / 包括下级标签的条目数");
+            }
+
+            if ui
+                .small_button("改名")
+                .on_hover_text("重命名或移动该标签及其所有下级")
+                .clicked()
+            {
+                this.tag_operation = Some(TagOperation::Rename {
+                    from: node.path.clone(),
+                });
+                this.tag_operation_target = node.path.clone();
+                this.tag_status.clear();
+            }
+            if ui
+                .small_button("样式")
+                .on_hover_text("设置颜色、图标、说明和是否隐藏")
+                .clicked()
+            {
+                let info = this
+                    .user_data
+                    .tag_registry
+                    .get(&node.path)
+                    .cloned()
+                    .unwrap_or_default();
+                this.tag_style_editing = Some((node.path.clone(), info));
+                this.tag_status.clear();
+            }
+            if ui
+                .small_button("删除")
+                .on_hover_text("从所有条目中删除该标签及其所有下级")
+                .clicked()
+            {
+                this.tag_operation = Some(TagOperation::Delete {
+                    tag: node.path.clone(),
+                });
+                this.tag_status.clear();
+            }
+        };
+
+        if node.children.is_empty() {
+            ui.horizontal(|ui| row(self, ui));
             return;
         }
+        let id = ui.make_persistent_id(("tag_node", &node.path));
+        let mut state =
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false);
+        if !filter.is_empty() {
+            state.set_open(true);
+        }
+        state
+            .show_header(ui, |ui| row(self, ui))
+            .body(|ui| {
+                for child in &node.children {
+                    self.render_tag_node(ui, child, filter);
+                }
+            });
+    }
 
-        let mut selected_collection_name = self
-            .editing_collection_index
-            .and_then(|idx| collections.iter().find(|(i, _)| *i == idx))
-            .map(|(_, entry)| entry.name.clone())
-            .unwrap_or_else(|| "选择集合...".to_string());
+    /// 等待确认的标签操作：预览受影响的条目，应用后作为一次操作记入撤销历史
+    fn render_tag_operation(&mut self, ui: &mut egui::Ui) {
+        let Some(operation) = &self.tag_operation else {
+            return;
+        };
+        let (title, sources) = match operation {
+            TagOperation::Rename { from } => (
+                format!("重命名或移动 {}（包括所有下级）", from),
+                vec![from.clone()],
+            ),
+            TagOperation::Merge { sources } => (
+                format!("合并 {}（包括所有下级）", sources.join("、")),
+                sources.clone(),
+            ),
+            TagOperation::Delete { tag } => (
+                format!("从所有条目中删除 {}（包括所有下级）", tag),
+                vec![tag.clone()],
+            ),
+        };
+        let is_delete = matches!(operation, TagOperation::Delete { .. });
 
-        egui::ComboBox::from_label("")
-            .selected_text(&selected_collection_name)
-            .show_ui(ui, |ui| {
-                for (index, entry) in &collections {
-                    let response = ui.selectable_value(
+        ui.add_space(8.0);
+        ui.separator();
+        ui.label(title);
+        if !is_delete {
+            ui.horizontal(|ui| {
+                ui.label(if sources.len() > 1 { "合并到:" } else { "新标签:" });
+                ui.text_edit_singleline(&mut self.tag_operation_target);
+            });
+        }
+
+        let affected = tags::affected_entries(&self.entries, &sources);
+        ui.small(format!("将修改 {} 个条目:", affected.len()));
+        for &i in affected.iter().take(TAG_PREVIEW_LIMIT) {
+            let entry = &self.entries[i];
+            ui.small(format!(
+                "  {}  {}",
+                entry.nickname.as_ref().unwrap_or(&entry.name),
+                entry.tags.join(" ")
+            ));
+        }
+        if affected.len() > TAG_PREVIEW_LIMIT {
+            ui.small(format!("  …等 {} 个", affected.len()));
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button(if is_delete { "删除" } else { "应用" }).clicked() {
+                self.apply_tag_operation();
+            }
+            if ui.button("取消").clicked() {
+                self.tag_operation = None;
+            }
+        });
+    }
+
+    fn apply_tag_operation(&mut self) {
+        let Some(operation) = self.tag_operation.take() else {
+            return;
+        };
+        let target = self.tag_operation_target.clone();
+        let (label, result) = match &operation {
+            TagOperation::Rename { from } => (
+                "重命名标签",
+                tags::rename_subtree(&mut self.entries, from, &target),
+            ),
+            TagOperation::Merge { sources } => (
+                "合并标签",
+                tags::merge_into(&mut self.entries, sources, &target),
+            ),
+            TagOperation::Delete { tag } => {
+                ("删除标签", tags::delete_subtree(&mut self.entries, tag))
+            }
+        };
+
+        match result {
+            Ok(0) => {
+                self.tag_status = "没有条目需要修改".to_string();
+            }
+            Ok(modified) => {
+                let registry = &mut self.user_data.tag_registry;
+                match &operation {
+                    TagOperation::Rename { from } => tags::move_info(registry, from, &target),
+                    TagOperation::Merge { sources } => {
+                        for source in sources {
+                            tags::move_info(registry, source, &target);
+                        }
+                    }
+                    TagOperation::Delete { tag } => tags::remove_info(registry, tag),
+                }
+                self.selected_tags.clear();
+                self.rebuild_tag_set();
+                self.tag_status = match self.commit_entries(label) {
+                    Ok(()) => format!("{}：已修改 {} 个条目，可撤销", label, modified),
+                    Err(e) => format!("保存失败: {}", e),
+                };
+                self.force_update_filter();
+            }
+            Err(e) => {
+                self.tag_status = e;
+                self.tag_operation = Some(operation);
+            }
+        }
+    }
+
+    /// 编辑标签的显示信息；颜色和图标不设置时沿用上级标签的
+    fn render_tag_style_editor(&mut self, ui: &mut egui::Ui) {
+        let Some((tag, info)) = &mut self.tag_style_editing else {
+            return;
+        };
+
+        ui.add_space(8.0);
+        ui.separator();
+        ui.label(format!("{} 的显示样式", tag));
+        ui.horizontal(|ui| {
+            let mut has_color = info.color.is_some();
+            ui.checkbox(&mut has_color, "颜色");
+            if has_color {
+                ui.color_edit_button_srgb(info.color.get_or_insert([90, 140, 220]));
+            } else {
+                info.color = None;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("图标:");
+            ui.add(
+                egui::TextEdit::singleline(info.icon.get_or_insert_with(String::new))
+                    .hint_text("例如 📁")
+                    .desired_width(60.0),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("说明:");
+            ui.text_edit_singleline(info.description.get_or_insert_with(String::new));
+        });
+        ui.checkbox(&mut info.hidden, "隐藏（不在条目列表和标签建议中显示，仍可搜索）");
+
+        let preview = tags::info_for(&self.user_data.tag_registry, tag);
+        ui.horizontal(|ui| {
+            ui.small("预览:");
+            let mut edited = info.clone();
+            edited.color = edited.color.or(preview.color);
+            if edited.icon.as_deref().is_none_or(|icon| icon.trim().is_empty()) {
+                edited.icon = preview.icon;
+            }
+            ui.label(Self::tag_text(&edited, tag));
+        });
+
+        let mut save = false;
+        ui.horizontal(|ui| {
+            save = ui.button("保存").clicked();
+            if ui.button("取消").clicked() {
+                self.tag_style_editing = None;
+            }
+        });
+        if save {
+            self.save_tag_style();
+        }
+    }
+
+    fn save_tag_style(&mut self) {
+        let Some((tag, mut info)) = self.tag_style_editing.take() else {
+            return;
+        };
+        let trimmed = |text: Option<String>| {
+            text.map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+        };
+        info.icon = trimmed(info.icon);
+        info.description = trimmed(info.description);
+
+        if info.is_empty() {
+            self.user_data.tag_registry.remove(&tag);
+        } else {
+            self.user_data.tag_registry.insert(tag.clone(), info);
+        }
+        self.tag_status = match self.persist_entries(None) {
+            Ok(()) => format!("已保存 {} 的显示样式", tag),
+            Err(e) => format!("保存失败: {}", e),
+        };
+    }
+
+    /// 标签别名：输入或搜索别名时按标准标签处理；添加别名时已有的别名标签一并改为标准标签
+    fn render_tag_aliases(&mut self, ui: &mut egui::Ui) {
+        let mut to_remove = None;
+        for (alias, canonical) in &self.user_data.tag_aliases {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} → {}", alias, canonical));
+                if ui.small_button("×").on_hover_text("删除别名").clicked() {
+                    to_remove = Some(alias.clone());
+                }
+            });
+        }
+        if let Some(alias) = to_remove {
+            self.user_data.tag_aliases.remove(&alias);
+            if let Err(e) = self.persist_entries(None) {
+                self.tag_status = format!("保存失败: {}", e);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.tag_alias_input)
+                    .hint_text("#别名")
+                    .desired_width(100.0),
+            );
+            ui.label("→");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.tag_alias_target)
+                    .hint_text("#标准标签")
+                    .desired_width(100.0),
+            );
+            if ui.button("添加").clicked() {
+                self.add_tag_alias();
+            }
+        });
+    }
+
+    fn add_tag_alias(&mut self) {
+        let (alias, canonical) = match tags::add_alias(
+            &mut self.user_data.tag_aliases,
+            &self.tag_alias_input,
+            &self.tag_alias_target,
+        ) {
+            Ok(pair) => pair,
+            Err(e) => {
+                self.tag_status = e;
+                return;
+            }
+        };
+
+        let modified = tags::rename_subtree(&mut self.entries, &alias, &canonical).unwrap_or(0);
+        tags::move_info(&mut self.user_data.tag_registry, &alias, &canonical);
+        self.rebuild_tag_set();
+        self.tag_status = match self.commit_entries("添加标签别名") {
+            Ok(()) => format!(
+                "已添加别名 {} → {}，修改了 {} 个条目",
+                alias, canonical, modified
+            ),
+            Err(e) => format!("保存失败: {}", e),
+        };
+        self.tag_alias_input.clear();
+        self.tag_alias_target.clear();
+        self.force_update_filter();
+    }
+
+    /// 自动标签规则：添加条目或拖入文件时自动加上匹配的标签，也可以试运行后应用到已有条目
+    fn render_auto_tag_rules(&mut self, ui: &mut egui::Ui) {
+        let mut to_remove = None;
+        let mut changed = false;
+        for (i, rule) in self.user_data.auto_tag_rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                ui.label(format!(
+                    "{} {} → {}",
+                    rule.kind.label(),
+                    rule.pattern,
+                    rule.tags.join(" ")
+                ));
+                if ui.small_button("×").on_hover_text("删除规则").clicked() {
+                    to_remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = to_remove {
+            self.user_data.auto_tag_rules.remove(i);
+            changed = true;
+        }
+        if changed {
+            self.auto_tag_preview = None;
+            if let Err(e) = self.persist_entries(None) {
+                self.tag_status = format!("保存失败: {}", e);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("auto_tag_kind")
+                .selected_text(self.auto_tag_kind.label())
+                .show_ui(ui, |ui| {
+                    for kind in RuleKind::ALL {
+                        ui.selectable_value(&mut self.auto_tag_kind, kind, kind.label());
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.auto_tag_pattern)
+                    .hint_text(self.auto_tag_kind.hint())
+                    .desired_width(120.0),
+            );
+            ui.label("→");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.auto_tag_tags)
+                    .hint_text("#标签")
+                    .desired_width(100.0),
+            );
+            if ui.button("添加").clicked() {
+                self.add_auto_tag_rule();
+            }
+        });
+
+        if ui
+            .button("预览应用到已有条目…")
+            .on_hover_text("试运行所有启用的规则，确认后才修改条目")
+            .clicked()
+        {
+            let rules = RuleSet::new(&self.user_data.auto_tag_rules);
+            self.auto_tag_preview = Some(rules.preview(&self.entries));
+        }
+        let Some(preview) = &self.auto_tag_preview else {
+            return;
+        };
+        ui.small(format!("将修改 {} 个条目:", preview.len()));
+        for (i, added) in preview.iter().take(TAG_PREVIEW_LIMIT) {
+            if let Some(entry) = self.entries.get(*i) {
+                ui.small(format!(
+                    "  {}  + {}",
+                    entry.nickname.as_ref().unwrap_or(&entry.name),
+                    added.join(" ")
+                ));
+            }
+        }
+        if preview.len() > TAG_PREVIEW_LIMIT {
+            ui.small(format!("  …等 {} 个", preview.len()));
+        }
+        let can_apply = !preview.is_empty();
+        ui.horizontal(|ui| {
+            if can_apply && ui.button("应用").clicked() {
+                self.apply_auto_tags_to_library();
+            }
+            if ui.button("取消").clicked() {
+                self.auto_tag_preview = None;
+            }
+        });
+    }
+
+    fn add_auto_tag_rule(&mut self) {
+        let tags = self.parse_tag_input(&self.auto_tag_tags);
+        match AutoTagRule::new(self.auto_tag_kind, &self.auto_tag_pattern, tags) {
+            Ok(rule) => {
+                self.user_data.auto_tag_rules.push(rule);
+                self.auto_tag_pattern.clear();
+                self.auto_tag_tags.clear();
+                self.auto_tag_preview = None;
+                self.tag_status = match self.persist_entries(None) {
+                    Ok(()) => "已添加自动标签规则".to_string(),
+                    Err(e) => format!("保存失败: {}", e),
+                };
+            }
+            Err(e) => self.tag_status = e,
+        }
+    }
+
+    /// 把自动标签规则应用到所有已有条目，作为一次操作记入撤销历史
+    fn apply_auto_tags_to_library(&mut self) {
+        self.auto_tag_preview = None;
+        let rules = RuleSet::new(&self.user_data.auto_tag_rules);
+        let modified = self
+            .entries
+            .iter_mut()
+            .map(|entry| rules.apply(entry))
+            .filter(|&changed| changed)
+            .count();
+        if modified == 0 {
+            self.tag_status = "没有条目需要修改".to_string();
+            return;
+        }
+        self.rebuild_tag_set();
+        self.tag_status = match self.commit_entries("应用自动标签") {
+            Ok(()) => format!("应用自动标签：已修改 {} 个条目，可撤销", modified),
+            Err(e) => format!("保存失败: {}", e),
+        };
+        self.force_update_filter();
+    }
+
+    fn render_collection_manager(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("集合管理器");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("×").clicked() {
+                    self.show_collection_manager = false;
+                }
+            });
+        });
+        ui.separator();
+
+        // 选择要编辑的集合
+        ui.label("选择集合:");
+        let collections: Vec<(usize, &FileEntry)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.entry_type == crate::file_entry::EntryType::Collection)
+            .collect();
+
+        if collections.is_empty() {
+            ui.label("还没有创建任何集合");
+            ui.small("请先添加一个集合类型的条目");
+            return;
+        }
+
+        let mut selected_collection_name = self
+            .editing_collection_index
+            .and_then(|idx| collections.iter().find(|(i, _)| *i == idx))
+            .map(|(_, entry)| entry.name.clone())
+            .unwrap_or_else(|| "选择集合...".to_string());
+
+        egui::ComboBox::from_label("")
+            .selected_text(&selected_collection_name)
+            .show_ui(ui, |ui| {
+                for (index, entry) in &collections {
+                    let response = ui.selectable_value(
                         &mut selected_collection_name,
                         entry.name.clone(),
                         &entry.name,
                     );
                     if response.clicked() {
                         self.editing_collection_index = Some(*index);
+                        self.collection_query_input = entry.smart_query.clone().unwrap_or_default();
+                        self.collection_status.clear();
                         // 初始化子项选择状态，现在使用ID而不是索引
                         self.collection_child_selection.clear();
                         for child_id in &entry.child_entries {
@@ -995,29 +2654,40 @@ impl FileManagerApp {
                 ui.label(format!("编辑集合: {}", collection_name));
                 ui.separator();
 
+                if self.entries[collection_idx].is_smart_collection() {
+                    self.render_smart_collection_editor(ui, collection_idx);
+                    return;
+                }
+
                 ui.label("选择要包含在集合中的项目:");
 
-                // 显示可选择的项目（排除集合类型本身）
+                // 显示可选择的项目；当前集合和包含它的上级集合不能选择，否则会形成循环
+                let ancestors =
+                    collections::ancestors(&self.entries, &self.entries[collection_idx].id);
                 egui::ScrollArea::vertical()
                     .max_height(300.0)
                     .show(ui, |ui| {
                         for (idx, entry) in self.entries.iter().enumerate() {
-                            if idx == collection_idx
-                                || entry.entry_type == crate::file_entry::EntryType::Collection
-                            {
-                                continue; // 跳过当前编辑的集合和其他集合
+                            if idx == collection_idx {
+                                continue;
                             }
 
+                            let allowed = !ancestors.contains(&entry.id);
                             let mut is_selected = self.collection_child_selection.contains(&idx);
                             let entry_icon = match entry.entry_type {
                                 crate::file_entry::EntryType::File => "[F]",
                                 crate::file_entry::EntryType::Directory => "[D]",
                                 crate::file_entry::EntryType::WebLink => "[L]",
-                                _ => "[?]",
+                                crate::file_entry::EntryType::Command => "[>]",
+                                crate::file_entry::EntryType::Collection => "[C]",
                             };
 
                             ui.horizontal(|ui| {
-                                if ui.checkbox(&mut is_selected, "").changed() {
+                                if ui
+                                    .add_enabled(allowed, egui::Checkbox::new(&mut is_selected, ""))
+                                    .on_disabled_hover_text("该集合包含当前集合，加入后会形成循环")
+                                    .changed()
+                                {
                                     if is_selected {
                                         self.collection_child_selection.insert(idx);
                                     } else {
@@ -1036,16 +2706,30 @@ impl FileManagerApp {
 
                 ui.horizontal(|ui| {
                     if ui.button("保存集合").clicked() {
-                        // 更新集合的子项目，现在使用ID而不是索引
-                        let mut child_ids = Vec::new();
-                        for &selected_idx in &self.collection_child_selection {
-                            if let Some(entry) = self.entries.get(selected_idx) {
+                        // 更新集合的子项目：保留原有成员的顺序，新选中的按列表顺序加在后面
+                        let selected: HashSet<String> = self
+                            .collection_child_selection
+                            .iter()
+                            .filter_map(|&idx| self.entries.get(idx))
+                            .filter(|entry| !ancestors.contains(&entry.id))
+                            .map(|entry| entry.id.clone())
+                            .collect();
+                        let mut child_ids: Vec<String> = self.entries[collection_idx]
+                            .child_entries
+                            .iter()
+                            .filter(|id| selected.contains(*id))
+                            .cloned()
+                            .collect();
+                        for entry in &self.entries {
+                            if selected.contains(&entry.id) && !child_ids.contains(&entry.id) {
                                 child_ids.push(entry.id.clone());
                             }
                         }
                         if let Some(collection) = self.entries.get_mut(collection_idx) {
                             collection.child_entries = child_ids;
-                            let _ = self.save_user_data();
+                            collection.prune_child_options();
+                            collection.touch();
+                            let _ = self.commit_entries("修改集合成员");
                         }
                     }
 
@@ -1055,30 +2739,236 @@ impl FileManagerApp {
                     }
                 });
 
-                // 显示当前集合信息
-                if let Some(collection) = self.entries.get(collection_idx) {
-                    ui.add_space(12.0);
-                    ui.label(format!(
-                        "当前集合包含 {} 个项目:",
-                        collection.child_entries.len()
-                    ));
-                    for child_id in &collection.child_entries {
-                        // 通过ID查找对应的条目
-                        if let Some(child_entry) = self.entries.iter().find(|e| &e.id == child_id) {
-                            let entry_icon = match child_entry.entry_type {
-                                crate::file_entry::EntryType::File => "[F]",
-                                crate::file_entry::EntryType::Directory => "[D]",
-                                crate::file_entry::EntryType::WebLink => "[L]",
-                                _ => "[?]",
-                            };
-                            ui.label(format!("  {} {}", entry_icon, child_entry.name));
-                        } else {
-                            // 如果找不到对应的条目，说明可能已被删除
-                            ui.label(format!("  [已删除] ID: {}", child_id));
-                        }
+                // 当前成员：调整顺序、打开方式和各项的打开选项
+                ui.add_space(12.0);
+                self.render_collection_children(ui, collection_idx);
+                if !self.collection_status.is_empty() {
+                    ui.small(&self.collection_status);
+                }
+            }
+        }
+    }
+
+    /// 集合的当前成员：拖动 ☰ 调整顺序，设置打开方式、各项是否启用和打开前的延迟
+    fn render_collection_children(&mut self, ui: &mut egui::Ui, collection_idx: usize) {
+        let collection = &self.entries[collection_idx];
+        let mut launch_mode = collection.launch_mode;
+        let mut changed_options = None;
+        let mut commit_options = false;
+        let mut has_nested = false;
+
+        ui.horizontal(|ui| {
+            ui.label("打开方式:");
+            for mode in LaunchMode::ALL {
+                ui.radio_value(&mut launch_mode, mode, mode.label());
+            }
+        });
+        ui.label(format!(
+            "当前集合包含 {} 个项目（拖动 ☰ 调整顺序）:",
+            collection.child_entries.len()
+        ));
+
+        let mut row_rects = Vec::new();
+        for (position, child_id) in collection.child_entries.iter().enumerate() {
+            let row = ui.horizontal(|ui| {
+                let handle = ui
+                    .add(egui::Label::new("☰").sense(egui::Sense::drag()))
+                    .on_hover_cursor(egui::CursorIcon::Grab);
+                if handle.drag_started() {
+                    self.collection_drag = Some(position);
+                }
+
+                // 通过ID查找对应的条目
+                let Some(child_entry) = self.entries.iter().find(|e| &e.id == child_id) else {
+                    // 如果找不到对应的条目，说明可能已被删除
+                    ui.label(format!("[已删除] ID: {}", child_id));
+                    return;
+                };
+                let mut options = collection.child_options(child_id);
+                let entry_icon = match child_entry.entry_type {
+                    crate::file_entry::EntryType::File => "[F]",
+                    crate::file_entry::EntryType::Directory => "[D]",
+                    crate::file_entry::EntryType::WebLink => "[L]",
+                    crate::file_entry::EntryType::Command => "[>]",
+                    crate::file_entry::EntryType::Collection => {
+                        has_nested = true;
+                        "[C]"
                     }
+                };
+                let toggled = ui
+                    .checkbox(&mut options.enabled, "")
+                    .on_hover_text("停用的项目打开集合时跳过")
+                    .changed();
+                ui.label(format!("{} {}", entry_icon, child_entry.name));
+                let delay = ui
+                    .add(
+                        egui::DragValue::new(&mut options.delay_ms)
+                            .clamp_range(0..=60_000)
+                            .speed(10)
+                            .suffix(" ms"),
+                    )
+                    .on_hover_text("打开前等待的时间");
+                if toggled || delay.changed() {
+                    changed_options = Some((child_id.clone(), options));
+                }
+                // 拖动延迟时松开后才保存，避免每一帧都记入撤销历史
+                commit_options |=
+                    toggled || (delay.changed() && !delay.dragged()) || delay.drag_released();
+            });
+            row_rects.push(row.response.rect);
+        }
+
+        // 拖动时在松开的位置画出插入线，松开后移动
+        let mut reorder = None;
+        if let Some(from) = self.collection_drag
+            && let Some(pointer) = ui.ctx().pointer_interact_pos()
+            && let (Some(first), Some(last)) = (row_rects.first(), row_rects.last())
+        {
+            let to = row_rects
+                .iter()
+                .position(|rect| pointer.y < rect.center().y)
+                .unwrap_or(row_rects.len());
+            let y = row_rects.get(to).map_or(last.bottom(), |rect| rect.top());
+            ui.painter().hline(
+                first.x_range(),
+                y,
+                egui::Stroke::new(2.0, ui.visuals().selection.bg_fill),
+            );
+            if ui.input(|i| i.pointer.any_released()) {
+                reorder = Some((from, to));
+            }
+        }
+        if ui.input(|i| !i.pointer.any_down()) {
+            self.collection_drag = None;
+        }
+
+        if has_nested
+            && ui
+                .button("展平")
+                .on_hover_text("把下级集合换成它们包含的所有条目")
+                .clicked()
+        {
+            self.flatten_collection(collection_idx);
+            return;
+        }
+
+        let collection = &mut self.entries[collection_idx];
+        let mut label = None;
+        if launch_mode != collection.launch_mode {
+            collection.launch_mode = launch_mode;
+            label = Some("修改集合打开方式");
+        }
+        if let Some((child_id, options)) = changed_options {
+            collection.set_child_options(&child_id, options);
+            if commit_options {
+                label = Some("修改集合项目选项");
+            }
+        }
+        if let Some((from, to)) = reorder
+            && collections::move_child(collection, from, to)
+        {
+            self.collection_drag = None;
+            label = Some("调整集合顺序");
+        }
+        if let Some(label) = label {
+            self.entries[collection_idx].touch();
+            self.collection_status = match self.commit_entries(label) {
+                Ok(()) => String::new(),
+                Err(e) => format!("保存失败: {}", e),
+            };
+        }
+    }
+
+    /// 展平集合，并按新的成员更新集合管理器中的选择
+    fn flatten_collection(&mut self, index: usize) {
+        let Some(collection) = self.entries.get(index) else {
+            self.collection_status = "集合不存在".to_string();
+            return;
+        };
+        let flattened = collections::flattened(&self.members(), collection);
+        self.collection_status = match flattened {
+            Ok(items) => {
+                let count = collections::apply_flattened(&mut self.entries[index], items);
+                match self.commit_entries("展平集合") {
+                    Ok(()) => format!("已展平，现在包含 {} 个项目", count),
+                    Err(e) => format!("保存失败: {}", e),
                 }
             }
+            Err(e) => e,
+        };
+        if self.editing_collection_index == Some(index) {
+            self.collection_child_selection = self.entries[index]
+                .child_entries
+                .iter()
+                .filter_map(|id| self.entries.iter().position(|e| &e.id == id))
+                .collect();
+        }
+    }
+
+    /// 编辑智能集合的搜索条件
+    fn render_smart_collection_editor(&mut self, ui: &mut egui::Ui, collection_idx: usize) {
+        ui.label("搜索条件:");
+        ui.text_edit_singleline(&mut self.collection_query_input)
+            .on_hover_text(SEARCH_SYNTAX_HELP);
+        self.render_smart_query_preview(ui, &self.collection_query_input);
+
+        ui.add_space(12.0);
+        ui.horizontal(|ui| {
+            let valid = !self.collection_query_input.trim().is_empty()
+                && query::parse(&self.collection_query_input).is_ok();
+            if ui.add_enabled(valid, egui::Button::new("保存集合")).clicked()
+                && let Some(collection) = self.entries.get_mut(collection_idx)
+            {
+                collection.smart_query = Some(self.collection_query_input.trim().to_string());
+                collection.touch();
+                let _ = self.commit_entries("修改智能集合条件");
+            }
+
+            if ui.button("取消").clicked() {
+                self.editing_collection_index = None;
+                self.collection_query_input.clear();
+            }
+        });
+
+        // 显示当前集合信息
+        if let Some(collection) = self.entries.get(collection_idx)
+            && let Ok(members) = self.members().of(collection)
+        {
+            ui.add_space(12.0);
+            ui.label(format!("当前集合包含 {} 个项目:", members.len()));
+            for member in members {
+                let entry_icon = match member.entry_type {
+                    crate::file_entry::EntryType::File => "[F]",
+                    crate::file_entry::EntryType::Directory => "[D]",
+                    crate::file_entry::EntryType::WebLink => "[L]",
+                    crate::file_entry::EntryType::Command => "[>]",
+                    _ => "[?]",
+                };
+                ui.label(format!("  {} {}", entry_icon, member.name));
+            }
+        }
+    }
+
+    /// 检查智能集合的搜索条件：显示语法错误或当前匹配的条目数
+    fn render_smart_query_preview(&self, ui: &mut egui::Ui, query: &str) {
+        if query.trim().is_empty() {
+            ui.small("例如: #工作 -#归档 或 type:link opened:<30d");
+            return;
+        }
+        let preview = FileEntry::new_smart_collection(
+            String::new(),
+            None,
+            None,
+            Vec::new(),
+            query.to_string(),
+        );
+        match self.members().of(&preview) {
+            Ok(members) => {
+                ui.small(format!("当前匹配 {} 个条目", members.len()));
+            }
+            Err(e) => {
+                ui.colored_label(egui::Color32::from_rgb(200, 50, 50), e);
+            }
         }
     }
 
@@ -1105,6 +2995,7 @@ impl FileManagerApp {
                             crate::file_entry::EntryType::File => "[F]",
                             crate::file_entry::EntryType::Directory => "[D]",
                             crate::file_entry::EntryType::WebLink => "[L]",
+                            crate::file_entry::EntryType::Command => "[>]",
                             crate::file_entry::EntryType::Collection => "[C]",
                         };
                         ui.horizontal(|ui| {
@@ -1140,7 +3031,7 @@ impl FileManagerApp {
                 );
                 
                 self.entries.push(collection);
-                let _ = self.save_user_data();
+                let _ = self.commit_entries("创建集合");
                 
                 // 清理状态
                 self.batch_collection_name.clear();
@@ -1193,6 +3084,11 @@ impl FileManagerApp {
                 crate::file_entry::EntryType::Collection,
                 "集合",
             );
+            ui.radio_value(
+                &mut self.add_entry_type,
+                crate::file_entry::EntryType::Command,
+                "命令",
+            );
         });
 
         ui.add_space(8.0);
@@ -1222,56 +3118,66 @@ impl FileManagerApp {
             }
             crate::file_entry::EntryType::Collection => {
                 ui.label("集合信息:");
-                ui.small("选择要包含在集合中的项目:");
-                
+
                 // 不需要路径输入，集合使用虚拟路径
                 self.add_path_input.clear();
+
+                ui.checkbox(&mut self.add_smart_collection, "智能集合（按搜索条件自动包含）");
+                if self.add_smart_collection {
+                    ui.label("搜索条件:");
+                    ui.text_edit_singleline(&mut self.add_smart_query_input)
+                        .on_hover_text(SEARCH_SYNTAX_HELP);
+                    self.render_smart_query_preview(ui, &self.add_smart_query_input);
+                } else {
+                    ui.small("选择要包含在集合中的项目:");
+                    ui.add_space(8.0);
                 
-                ui.add_space(8.0);
-                
-                // 显示可选择的项目（排除集合类型）
-                egui::ScrollArea::vertical()
-                    .max_height(200.0)
-                    .show(ui, |ui| {
-                        for (idx, entry) in self.entries.iter().enumerate() {
-                            if entry.entry_type == crate::file_entry::EntryType::Collection {
-                                continue; // 跳过其他集合
-                            }
+                    // 显示可选择的项目（排除集合类型）
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for (idx, entry) in self.entries.iter().enumerate() {
+                                if entry.entry_type == crate::file_entry::EntryType::Collection {
+                                    continue; // 跳过其他集合
+                                }
 
-                            let mut is_selected = self.collection_child_selection.contains(&idx);
-                            let entry_icon = match entry.entry_type {
-                                crate::file_entry::EntryType::File => "📄",
-                                crate::file_entry::EntryType::Directory => "📁",
-                                crate::file_entry::EntryType::WebLink => "🌐",
-                                _ => "📋",
-                            };
+                                let mut is_selected = self.collection_child_selection.contains(&idx);
+                                let entry_icon = match entry.entry_type {
+                                    crate::file_entry::EntryType::File => "📄",
+                                    crate::file_entry::EntryType::Directory => "📁",
+                                    crate::file_entry::EntryType::WebLink => "🌐",
+                                    crate::file_entry::EntryType::Command => "⚙",
+                                    _ => "📋",
+                                };
 
-                            ui.horizontal(|ui| {
-                                if ui.checkbox(&mut is_selected, "").changed() {
-                                    if is_selected {
-                                        self.collection_child_selection.insert(idx);
-                                    } else {
-                                        self.collection_child_selection.remove(&idx);
+                                ui.horizontal(|ui| {
+                                    if ui.checkbox(&mut is_selected, "").changed() {
+                                        if is_selected {
+                                            self.collection_child_selection.insert(idx);
+                                        } else {
+                                            self.collection_child_selection.remove(&idx);
+                                        }
                                     }
-                                }
-                                ui.label(format!("{} {}", entry_icon, entry.name));
-                                if let Some(nickname) = &entry.nickname {
-                                    ui.small(format!("({})", nickname));
-                                }
-                            });
-                        }
+                                    ui.label(format!("{} {}", entry_icon, entry.name));
+                                    if let Some(nickname) = &entry.nickname {
+                                        ui.small(format!("({})", nickname));
+                                    }
+                                });
+                            }
                         
-                        if self.entries.iter().all(|e| e.entry_type == crate::file_entry::EntryType::Collection) {
-                            ui.label("没有可选择的项目");
-                            ui.small("请先添加一些文件、文件夹或网页链接");
-                        }
-                    });
+                            if self.entries.iter().all(|e| e.entry_type == crate::file_entry::EntryType::Collection) {
+                                ui.label("没有可选择的项目");
+                                ui.small("请先添加一些文件、文件夹或网页链接");
+                            }
+                        });
                 
-                if !self.collection_child_selection.is_empty() {
-                    ui.add_space(4.0);
-                    ui.label(format!("已选择 {} 个项目", self.collection_child_selection.len()));
+                    if !self.collection_child_selection.is_empty() {
+                        ui.add_space(4.0);
+                        ui.label(format!("已选择 {} 个项目", self.collection_child_selection.len()));
+                    }
                 }
             }
+            crate::file_entry::EntryType::Command => self.render_command_fields(ui),
             _ => {
                 ui.label("路径:");
                 ui.text_edit_singleline(&mut self.add_path_input);
@@ -1325,7 +3231,11 @@ impl FileManagerApp {
                 }
                 crate::file_entry::EntryType::Collection => {
                     !self.add_name_input.is_empty()
+                        && (!self.add_smart_collection
+                            || (!self.add_smart_query_input.trim().is_empty()
+                                && query::parse(&self.add_smart_query_input).is_ok()))
                 }
+                crate::file_entry::EntryType::Command => self.command_from_inputs().is_ok(),
                 _ => !self.add_path_input.is_empty(),
             };
 
@@ -1343,6 +3253,9 @@ impl FileManagerApp {
                 self.add_description_input.clear();
                 self.add_entry_type = crate::file_entry::EntryType::File;
                 self.collection_child_selection.clear();
+                self.add_smart_collection = false;
+                self.add_smart_query_input.clear();
+                self.clear_command_inputs();
             }
         });
     }
@@ -1379,9 +3292,20 @@ impl FileManagerApp {
         ui.label("描述:");
         ui.text_edit_multiline(&mut self.add_description_input);
 
+        let is_command = self
+            .editing_entry_index
+            .and_then(|index| self.entries.get(index))
+            .is_some_and(|entry| entry.command.is_some());
+        if is_command {
+            ui.add_space(8.0);
+            ui.separator();
+            self.render_command_fields(ui);
+        }
+
         ui.add_space(16.0);
         ui.horizontal(|ui| {
-            if ui.button("保存").clicked() {
+            let can_save = !is_command || self.command_from_inputs().is_ok();
+            if ui.add_enabled(can_save, egui::Button::new("保存")).clicked() {
                 self.save_entry_edit();
             }
             if ui.button("取消").clicked() {
@@ -1390,10 +3314,198 @@ impl FileManagerApp {
                 self.add_tags_input.clear();
                 self.add_nickname_input.clear();
                 self.add_description_input.clear();
+                self.add_path_input.clear();
+                self.clear_command_inputs();
             }
         });
     }
 
+    /// 生成高亮了搜索匹配字符的文本
+    fn highlighted(
+        ui: &egui::Ui,
+        text: &str,
+        ranges: &[Range<usize>],
+        text_style: egui::TextStyle,
+    ) -> egui::text::LayoutJob {
+        let plain = egui::TextFormat {
+            font_id: text_style.resolve(ui.style()),
+            color: ui.visuals().text_color(),
+            ..Default::default()
+        };
+        // 链接会覆盖文字颜色，所以用背景色标出匹配的字符
+        let marked = egui::TextFormat {
+            background: if ui.visuals().dark_mode {
+                egui::Color32::from_rgb(110, 90, 20)
+            } else {
+                egui::Color32::from_rgb(255, 225, 120)
+            },
+            ..plain.clone()
+        };
+
+        let mut job = egui::text::LayoutJob::default();
+        let mut position = 0;
+        for range in ranges {
+            if range.start < position || range.end > text.len() {
+                continue;
+            }
+            job.append(&text[position..range.start], 0.0, plain.clone());
+            job.append(&text[range.clone()], 0.0, marked.clone());
+            position = range.end;
+        }
+        job.append(&text[position..], 0.0, plain);
+        job
+    }
+
+    /// 内容搜索命中的片段：文件名后面跟着高亮了匹配部分的上下文
+    fn snippet_job(ui: &egui::Ui, snippet: &ContentSnippet) -> egui::text::LayoutJob {
+        let prefix = format!(
+            "{}: ",
+            snippet.path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let highlight =
+            prefix.len() + snippet.highlight.start..prefix.len() + snippet.highlight.end;
+        let text = prefix + &snippet.text;
+        Self::highlighted(ui, &text, &[highlight], egui::TextStyle::Small)
+    }
+
+    /// 按标签的显示信息加上图标和颜色
+    fn tag_text(info: &TagInfo, tag: &str) -> egui::RichText {
+        let text = egui::RichText::new(info.label(tag));
+        match info.color {
+            Some([r, g, b]) => text.color(egui::Color32::from_rgb(r, g, b)),
+            None => text,
+        }
+    }
+
+    /// 标签按钮，悬停时显示标签说明
+    fn tag_chip(ui: &mut egui::Ui, tag: &str, info: &TagInfo) -> egui::Response {
+        let response = ui.add(egui::Button::new(Self::tag_text(info, tag).small()));
+        match &info.description {
+            Some(description) => response.on_hover_text(description),
+            None => response,
+        }
+    }
+
+    /// 条目上要显示的标签及其显示信息，隐藏的标签不显示
+    fn visible_tags(registry: &TagRegistry, tags: Vec<String>) -> Vec<(String, TagInfo)> {
+        tags.into_iter()
+            .map(|tag| {
+                let info = tags::info_for(registry, &tag);
+                (tag, info)
+            })
+            .filter(|(_, info)| !info.hidden)
+            .collect()
+    }
+
+    /// 集合成员的树：下级集合可以展开，最多显示 `collections::MAX_DEPTH` 层。
+    /// `(root, parent)` 为列表中的顶层集合和成员所在集合的位置
+    fn render_collection_tree(
+        ui: &mut egui::Ui,
+        members: &collections::Members,
+        (root, parent): (usize, usize),
+        children: &[String],
+        removable: bool,
+        depth: usize,
+    ) -> Option<CollectionAction> {
+        let mut action = None;
+        for (i, child_id) in children.iter().enumerate() {
+            let Some(child_idx) = members.entries.iter().position(|e| &e.id == child_id) else {
+                continue;
+            };
+            let child_entry = &members.entries[child_idx];
+            let is_collection =
+                child_entry.entry_type == crate::file_entry::EntryType::Collection;
+
+            let row = |ui: &mut egui::Ui| {
+                let mut action = None;
+                // 连接线
+                ui.label(if i == children.len() - 1 { "└─" } else { "├─" });
+
+                let child_icon = match child_entry.entry_type {
+                    crate::file_entry::EntryType::File => "[F]",
+                    crate::file_entry::EntryType::Directory => "[D]",
+                    crate::file_entry::EntryType::WebLink => "[L]",
+                    crate::file_entry::EntryType::Command => "[>]",
+                    crate::file_entry::EntryType::Collection => "[C]",
+                };
+
+                // 可点击的子项目链接
+                let child_response = ui.add(
+                    egui::Label::new(
+                        egui::RichText::new(format!("{} {}", child_icon, child_entry.name))
+                            .size(11.0)
+                            .color(egui::Color32::from_rgb(100, 150, 200)),
+                    )
+                    .sense(egui::Sense::click()),
+                );
+                if child_response.clicked() {
+                    action = Some(CollectionAction::Open(child_idx));
+                }
+
+                if let Some(nickname) = &child_entry.nickname {
+                    ui.label(
+                        egui::RichText::new(format!("({})", nickname))
+                            .size(10.0)
+                            .color(egui::Color32::from_gray(120)),
+                    );
+                }
+
+                // 使用固定宽度的空间来避免与集合编辑按钮重合
+                ui.allocate_ui_with_layout(
+                    [50.0, 20.0].into(),
+                    egui::Layout::right_to_left(egui::Align::Center),
+                    |ui| {
+                        // 智能集合的成员由搜索条件决定，不能单独移除
+                        if removable
+                            && ui.small_button("－").on_hover_text("从集合中移除").clicked()
+                        {
+                            action = Some(CollectionAction::Remove(parent, child_idx));
+                        }
+                        if removable
+                            && is_collection
+                            && parent != root
+                            && ui.small_button("⇡").on_hover_text("移到顶层集合").clicked()
+                        {
+                            action =
+                                Some(CollectionAction::Extract(root, parent, child_id.clone()));
+                        }
+                    },
+                );
+                action
+            };
+
+            let row_action = if is_collection && depth + 1 < collections::MAX_DEPTH {
+                let id = ui.make_persistent_id(("collection_tree", root, parent, child_id));
+                let state = egui::collapsing_header::CollapsingState::load_with_default_open(
+                    ui.ctx(),
+                    id,
+                    false,
+                );
+                let (_, header, body) = state.show_header(ui, row).body(|ui| {
+                    let grandchildren: Vec<String> = members
+                        .of(child_entry)
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|e| e.id.clone())
+                        .collect();
+                    Self::render_collection_tree(
+                        ui,
+                        members,
+                        (root, child_idx),
+                        &grandchildren,
+                        !child_entry.is_smart_collection(),
+                        depth + 1,
+                    )
+                });
+                header.inner.or(body.and_then(|body| body.inner))
+            } else {
+                ui.horizontal(row).inner
+            };
+            action = action.or(row_action);
+        }
+        action
+    }
+
     fn render_list(&mut self, ui: &mut egui::Ui) {
         let mut to_edit: Option<usize> = None;
         let mut to_expand: Option<usize> = None;
@@ -1401,7 +3513,23 @@ impl FileManagerApp {
         let mut to_open: Option<usize> = None;
         let mut search_update: Option<String> = None;
         let mut remove_from_collection: Option<(usize, usize)> = None;
+        let mut to_extract: Option<(usize, usize, String)> = None;
         let mut edit_collection: Option<usize> = None;
+        let mut to_transfer: Option<(Option<String>, bool)> = None;
+        let mut to_move: Option<(usize, bool)> = None;
+        let mut to_show_history: Option<usize> = None;
+        let mut to_toggle_pin: Option<usize> = None;
+        let transfer_targets = self.library_transfer_targets();
+        // 只有默认顺序下才能手动调整位置
+        let manual_order = self.config.sort_order == SortOrder::Manual;
+        // 列表里的闭包会修改其他字段，这里只借用求成员需要的字段
+        let members = collections::Members {
+            entries: &self.entries,
+            search_index: &self.search_index,
+            aliases: &self.user_data.tag_aliases,
+            content: Some(self.content_index.as_ref()),
+            cache: &self.smart_members,
+        };
 
         egui::ScrollArea::vertical()
             .max_height(ui.available_height() - 50.0)
@@ -1417,12 +3545,39 @@ impl FileManagerApp {
                     let entry_name = entry.name.clone();
                     let entry_nickname = entry.nickname.clone();
                     let (hash_tags, _path_tags) = entry.get_tag_categories();
+                    let hash_tags =
+                        Self::visible_tags(&self.user_data.tag_registry, hash_tags);
                     let entry_description = entry.description.clone();
                     let entry_type = entry.entry_type.clone();
                     let entry_path = entry.path.clone();
-                    let child_entries = entry.child_entries.clone();
+                    let entry_smart = entry.is_smart_collection();
+                    let entry_pinned = entry.pinned;
+                    let entry_match =
+                        self.search_matches.get(&index).cloned().unwrap_or_default();
+                    let entry_times = format!(
+                        "添加: {}  修改: {}  打开: {}（{} 次）",
+                        listing::format_time(entry.created_at),
+                        listing::format_time(entry.updated_at),
+                        if entry.open_count == 0 {
+                            "从未".to_string()
+                        } else {
+                            listing::format_time(entry.last_opened_at)
+                        },
+                        entry.open_count
+                    );
 
                     let is_expanded = self.expanded_entries.contains(&index);
+                    // 展开时才需要成员；智能集合的成员按搜索条件实时计算
+                    let child_entries: Vec<String> = if is_expanded {
+                        members
+                            .of(entry)
+                            .unwrap_or_default()
+                            .iter()
+                            .map(|e| e.id.clone())
+                            .collect()
+                    } else {
+                        Vec::new()
+                    };
 
                     if self.compact_mode && !is_expanded {
                         // 紧凑模式：单行显示
@@ -1478,30 +3633,58 @@ impl FileManagerApp {
                                 let icon = match entry_type {
                                     crate::file_entry::EntryType::Directory => "[D]",
                                     crate::file_entry::EntryType::WebLink => "[L]",
+                                    crate::file_entry::EntryType::Command => "[>]",
+                                    crate::file_entry::EntryType::Collection if entry_smart => "[S]",
                                     crate::file_entry::EntryType::Collection => "[C]",
                                     _ => "[F]",
                                 };
                                 ui.label(icon);
+                                if entry_pinned {
+                                    ui.label(egui::RichText::new("置顶").small().strong())
+                                        .on_hover_text("置顶的条目总是排在最前");
+                                }
 
                                 // 文件名/昵称
                                 if let Some(nickname) = &entry_nickname {
-                                    if ui.link(nickname).clicked() {
+                                    let text = Self::highlighted(
+                                        ui,
+                                        nickname,
+                                        &entry_match.nickname,
+                                        egui::TextStyle::Body,
+                                    );
+                                    if ui.link(text).clicked() {
                                         to_open = Some(index);
                                     }
                                 } else {
-                                    if ui.link(&entry_name).clicked() {
+                                    let text = Self::highlighted(
+                                        ui,
+                                        &entry_name,
+                                        &entry_match.name,
+                                        egui::TextStyle::Body,
+                                    );
+                                    if ui.link(text).clicked() {
                                         to_open = Some(index);
                                     }
                                 }
 
                                 // 标签（只显示第一个）
-                                if !hash_tags.is_empty() {
-                                    ui.small(&hash_tags[0]);
+                                if let Some((tag, info)) = hash_tags.first() {
+                                    let label = ui.label(Self::tag_text(info, tag).small());
+                                    if let Some(description) = &info.description {
+                                        label.on_hover_text(description);
+                                    }
                                     if hash_tags.len() > 1 {
                                         ui.small(format!("+{}", hash_tags.len() - 1));
                                     }
                                 }
 
+                                // 文件内容搜索命中时，悬停查看片段
+                                if let Some(snippet) = &entry_match.snippet {
+                                    ui.small("[内容]").on_hover_ui(|ui| {
+                                        ui.label(Self::snippet_job(ui, snippet));
+                                    });
+                                }
+
                                 ui.with_layout(
                                     egui::Layout::right_to_left(egui::Align::Center),
                                     |ui| {
@@ -1540,6 +3723,12 @@ impl FileManagerApp {
                                     self.show_batch_collection_dialog = true;
                                     ui.close_menu();
                                 }
+
+                                if let Some(transfer) =
+                                    Self::render_library_transfer_menu(ui, &transfer_targets)
+                                {
+                                    to_transfer = Some(transfer);
+                                }
                                 
                                 if ui.button("删除选中项目").clicked() {
                                     // 这里可以实现批量删除逻辑
@@ -1562,7 +3751,30 @@ impl FileManagerApp {
                                     to_edit = Some(index);
                                     ui.close_menu();
                                 }
+                                if manual_order {
+                                    ui.horizontal(|ui| {
+                                        if ui.button("上移").clicked() {
+                                            to_move = Some((index, true));
+                                            ui.close_menu();
+                                        }
+                                        if ui.button("下移").clicked() {
+                                            to_move = Some((index, false));
+                                            ui.close_menu();
+                                        }
+                                    });
+                                }
+                                if ui
+                                    .button(if entry_pinned { "取消置顶" } else { "置顶" })
+                                    .clicked()
+                                {
+                                    to_toggle_pin = Some(index);
+                                    ui.close_menu();
+                                }
                                 ui.separator();
+                                if ui.button("历史记录").clicked() {
+                                    to_show_history = Some(index);
+                                    ui.close_menu();
+                                }
                                 if ui.button("多选").clicked() {
                                     self.multi_select_mode = true;
                                     self.selected_entries.insert(index);
@@ -1636,10 +3848,16 @@ impl FileManagerApp {
                                 let icon = match entry_type {
                                     crate::file_entry::EntryType::Directory => "[D]",
                                     crate::file_entry::EntryType::WebLink => "[L]",
+                                    crate::file_entry::EntryType::Command => "[>]",
+                                    crate::file_entry::EntryType::Collection if entry_smart => "[S]",
                                     crate::file_entry::EntryType::Collection => "[C]",
                                     _ => "[F]",
                                 };
                                 ui.label(icon);
+                                if entry_pinned {
+                                    ui.label(egui::RichText::new("置顶").small().strong())
+                                        .on_hover_text("置顶的条目总是排在最前");
+                                }
 
                                 // 主要信息
                                 ui.vertical(|ui| {
@@ -1647,27 +3865,62 @@ impl FileManagerApp {
 
                                     // 文件名/昵称
                                     if let Some(nickname) = &entry_nickname {
-                                        if ui.link(nickname).clicked() {
+                                        let text = Self::highlighted(
+                                            ui,
+                                            nickname,
+                                            &entry_match.nickname,
+                                            egui::TextStyle::Body,
+                                        );
+                                        if ui.link(text).clicked() {
                                             to_open = Some(index);
                                         }
-                                        ui.small(&entry_name);
+                                        ui.label(Self::highlighted(
+                                            ui,
+                                            &entry_name,
+                                            &entry_match.name,
+                                            egui::TextStyle::Small,
+                                        ));
                                     } else {
-                                        if ui.link(&entry_name).clicked() {
+                                        let text = Self::highlighted(
+                                            ui,
+                                            &entry_name,
+                                            &entry_match.name,
+                                            egui::TextStyle::Body,
+                                        );
+                                        if ui.link(text).clicked() {
                                             to_open = Some(index);
                                         }
                                     }
 
                                     // 描述（如果有）
                                     if let Some(desc) = &entry_description {
-                                        ui.small(desc);
+                                        ui.label(Self::highlighted(
+                                            ui,
+                                            desc,
+                                            &entry_match.description,
+                                            egui::TextStyle::Small,
+                                        ));
                                     }
 
+                                    // 文件内容搜索命中的片段
+                                    if let Some(snippet) = &entry_match.snippet {
+                                        ui.label(Self::snippet_job(ui, snippet))
+                                            .on_hover_text(snippet.path.display().to_string());
+                                    }
+
+                                    // 时间信息
+                                    ui.label(
+                                        egui::RichText::new(&entry_times)
+                                            .small()
+                                            .color(egui::Color32::from_gray(140)),
+                                    );
+
                                     // 标签（完整显示）
                                     if !hash_tags.is_empty() {
                                         ui.horizontal(|ui| {
                                             ui.small("Tags:");
-                                            for tag in &hash_tags {
-                                                if ui.small_button(tag).clicked() {
+                                            for (tag, info) in &hash_tags {
+                                                if Self::tag_chip(ui, tag, info).clicked() {
                                                     let tag_query = format!("#{}", tag.trim_start_matches('#'));
                                                     if !self.search_query.contains(&tag_query) {
                                                         let new_query = if self.search_query.is_empty() {
@@ -1702,7 +3955,7 @@ impl FileManagerApp {
                                         collection_frame.show(ui, |ui| {
                                             if !child_entries.is_empty() {
                                                 ui.horizontal(|ui| {
-                                                    ui.label("集合:");
+                                                    ui.label(if entry_smart { "智能集合:" } else { "集合:" });
                                                     ui.label(egui::RichText::new(format!("包含 {} 个项目", child_entries.len()))
                                                         .size(12.0)
                                                         .color(egui::Color32::from_gray(150)));
@@ -1710,65 +3963,32 @@ impl FileManagerApp {
                                                 
                                                 ui.add_space(4.0);
                                                 
-                                                for (i, child_id) in child_entries.iter().enumerate() {
-                                                    if let Some(child_entry) = self.entries.iter().find(|e| &e.id == child_id) {
-                                                        ui.horizontal(|ui| {
-                                                            // 连接线
-                                                            if i == child_entries.len() - 1 {
-                                                                ui.label("└─");
-                                                            } else {
-                                                                ui.label("├─");
-                                                            }
-                                                
-                                                            let child_icon = match child_entry.entry_type {
-                                                                crate::file_entry::EntryType::File => "[F]",
-                                                                crate::file_entry::EntryType::Directory => "[D]",
-                                                                crate::file_entry::EntryType::WebLink => "[L]",
-                                                                _ => "[?]",
-                                                            };
-                                                
-                                                            // 可点击的子项目链接
-                                                            let child_response = ui.add(
-                                                                egui::Label::new(
-                                                                    egui::RichText::new(format!("{} {}", child_icon, child_entry.name))
-                                                                        .size(11.0)
-                                                                        .color(egui::Color32::from_rgb(100, 150, 200))
-                                                                ).sense(egui::Sense::click())
-                                                            );
-                                                
-                                                            if child_response.clicked() {
-                                                                if let Some(child_idx) = self.entries.iter().position(|e| &e.id == child_id) {
-                                                                    to_open = Some(child_idx);
-                                                                }
-                                                            }
-                                                
-                                                            if let Some(nickname) = &child_entry.nickname {
-                                                                ui.label(egui::RichText::new(format!("({})", nickname))
-                                                                    .size(10.0)
-                                                                    .color(egui::Color32::from_gray(120)));
-                                                            }
-                                                
-                                                            // 使用固定宽度的空间来避免与集合编辑按钮重合
-                                                            ui.allocate_ui_with_layout(
-                                                                [30.0, 20.0].into(),
-                                                                egui::Layout::right_to_left(egui::Align::Center),
-                                                                |ui| {
-                                                                    if ui.small_button("－").on_hover_text("从集合中移除").clicked() {
-                                                                        if let Some(child_idx) = self.entries.iter().position(|e| &e.id == child_id) {
-                                                                            remove_from_collection = Some((index, child_idx));
-                                                                        }
-                                                                    }
-                                                                }
-                                                            );
-                                                        });
+                                                let tree_action = Self::render_collection_tree(
+                                                    ui,
+                                                    &members,
+                                                    (index, index),
+                                                    &child_entries,
+                                                    !entry_smart,
+                                                    0,
+                                                );
+                                                match tree_action {
+                                                    Some(CollectionAction::Open(child_idx)) => {
+                                                        to_open = Some(child_idx);
+                                                    }
+                                                    Some(CollectionAction::Remove(collection_idx, child_idx)) => {
+                                                        remove_from_collection = Some((collection_idx, child_idx));
                                                     }
+                                                    Some(CollectionAction::Extract(root, parent, child_id)) => {
+                                                        to_extract = Some((root, parent, child_id));
+                                                    }
+                                                    None => {}
                                                 }
                                                 
                                                 ui.add_space(4.0);
                                                 ui.separator();
                                                 
                                                 ui.horizontal(|ui| {
-                                                    if ui.button("+ 添加更多").clicked() {
+                                                    if ui.button(if entry_smart { "编辑条件" } else { "+ 添加更多" }).clicked() {
                                                         edit_collection = Some(index);
                                                     }
                                                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -1796,6 +4016,8 @@ impl FileManagerApp {
                                         // 非集合类型显示路径
                                         let display_path = if entry_type == crate::file_entry::EntryType::WebLink {
                                             entry.url.clone().unwrap_or_else(|| entry_path.to_string_lossy().to_string())
+                                        } else if let Some(command) = &entry.command {
+                                            launcher::command_line(command)
                                         } else {
                                             entry_path.to_string_lossy().to_string()
                                         };
@@ -1843,6 +4065,12 @@ impl FileManagerApp {
                                     self.show_batch_collection_dialog = true;
                                     ui.close_menu();
                                 }
+
+                                if let Some(transfer) =
+                                    Self::render_library_transfer_menu(ui, &transfer_targets)
+                                {
+                                    to_transfer = Some(transfer);
+                                }
                                 
                                 if ui.button("删除选中项目").clicked() {
                                     // 这里可以实现批量删除逻辑
@@ -1865,7 +4093,30 @@ impl FileManagerApp {
                                     to_edit = Some(index);
                                     ui.close_menu();
                                 }
+                                if manual_order {
+                                    ui.horizontal(|ui| {
+                                        if ui.button("上移").clicked() {
+                                            to_move = Some((index, true));
+                                            ui.close_menu();
+                                        }
+                                        if ui.button("下移").clicked() {
+                                            to_move = Some((index, false));
+                                            ui.close_menu();
+                                        }
+                                    });
+                                }
+                                if ui
+                                    .button(if entry_pinned { "取消置顶" } else { "置顶" })
+                                    .clicked()
+                                {
+                                    to_toggle_pin = Some(index);
+                                    ui.close_menu();
+                                }
                                 ui.separator();
+                                if ui.button("历史记录").clicked() {
+                                    to_show_history = Some(index);
+                                    ui.close_menu();
+                                }
                                 if ui.button("多选").clicked() {
                                     self.multi_select_mode = true;
                                     self.selected_entries.insert(index);
@@ -1911,13 +4162,23 @@ impl FileManagerApp {
             self.expanded_entries.remove(&index);
         }
         if let Some(index) = to_open {
-            if let Some(entry) = self.entries.get(index) {
-                self.open_entry(entry);
-            }
+            self.open_entry(index);
         }
         if let Some(index) = to_edit {
             self.edit_entry_tags(index);
         }
+        if let Some((index, up)) = to_move {
+            self.move_entry(index, up);
+        }
+        if let Some(index) = to_toggle_pin {
+            self.toggle_pin(index);
+        }
+        if let Some(index) = to_show_history {
+            self.show_entry_history(index);
+        }
+        if let Some((target, move_entries)) = to_transfer {
+            self.transfer_selected_to_library(target, move_entries);
+        }
         if let Some(query) = search_update {
             self.search_query = query;
             self.force_update_filter();
@@ -1928,13 +4189,21 @@ impl FileManagerApp {
                 let child_id = child_entry.id.clone();
                 if let Some(collection) = self.entries.get_mut(collection_idx) {
                     collection.child_entries.retain(|x| x != &child_id);
-                    let _ = self.save_user_data();
+                    collection.touch();
+                    let _ = self.commit_entries("从集合移除");
                 }
             }
         }
+        if let Some((root, parent, child_id)) = to_extract
+            && collections::extract_to_top(&mut self.entries, root, parent, &child_id).is_ok()
+        {
+            let _ = self.commit_entries("移到顶层集合");
+        }
         if let Some(collection_idx) = edit_collection {
             if let Some(collection_entry) = self.entries.get(collection_idx) {
                 self.editing_collection_index = Some(collection_idx);
+                self.collection_query_input =
+                    collection_entry.smart_query.clone().unwrap_or_default();
                 self.collection_child_selection.clear();
                 for child_id in &collection_entry.child_entries {
                     if let Some(child_idx) = self.entries.iter().position(|e| &e.id == child_id) {
@@ -1947,6 +4216,10 @@ impl FileManagerApp {
     }
 
     fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        // 文本框有自己的撤销，输入时不触发条目撤销
+        let text_editing = ctx.wants_keyboard_input();
+        let mut history_step = None;
+
         ctx.input(|i| {
             let cmd = if cfg!(target_os = "macos") {
                 i.modifiers.mac_cmd
@@ -1959,6 +4232,11 @@ impl FileManagerApp {
                 self.toggle_panel("add_dialog");
             }
 
+            // Cmd/Ctrl+Z: 撤销，Cmd/Ctrl+Shift+Z: 重做
+            if cmd && i.key_pressed(egui::Key::Z) && !text_editing {
+                history_step = Some(!i.modifiers.shift);
+            }
+
             // Cmd/Ctrl+F: 聚焦搜索框
             if cmd && i.key_pressed(egui::Key::F) {
                 self.search_has_focus = true;
@@ -1967,9 +4245,7 @@ impl FileManagerApp {
             // Enter: 打开选中的条目
             if i.key_pressed(egui::Key::Enter) && !self.search_currently_focused {
                 if let Some(focused_idx) = self.focused_entry {
-                    if let Some(entry) = self.entries.get(focused_idx) {
-                        self.open_entry(entry);
-                    }
+                    self.open_entry(focused_idx);
                 }
             }
 
@@ -2062,6 +4338,12 @@ impl FileManagerApp {
                 }
             }
         });
+
+        match history_step {
+            Some(true) => self.undo(),
+            Some(false) => self.redo(),
+            None => {}
+        }
     }
 
     /// 验证URL格式
@@ -2154,6 +4436,81 @@ impl FileManagerApp {
             let _ = self.save_config();
         }
 
+        ui.add_space(16.0);
+        ui.horizontal(|ui| {
+            ui.label("热度半衰期:");
+            let response = ui
+                .add(
+                    egui::DragValue::new(&mut self.config.frecency_half_life_days)
+                        .clamp_range(1.0..=365.0)
+                        .speed(0.5)
+                        .suffix(" 天"),
+                )
+                .on_hover_text("一次打开对热度的贡献每过这么多天减半；越小越偏向最近打开的条目");
+            if response.changed() {
+                self.force_update_filter();
+            }
+            if response.drag_released() || response.lost_focus() {
+                let _ = self.save_config();
+            }
+        });
+
+        ui.add_space(16.0);
+        if ui
+            .checkbox(&mut self.config.persist_history, "保存撤销历史")
+            .on_hover_text("把撤销历史保存在数据文件旁，重启后仍可撤销")
+            .changed()
+        {
+            let journal = History::journal_path(self.data_store.location());
+            if self.config.persist_history {
+                self.save_history_journal();
+            } else {
+                let _ = std::fs::remove_file(journal);
+            }
+            let _ = self.save_config();
+        }
+
+        ui.add_space(16.0);
+        if ui
+            .checkbox(&mut self.config.content_indexing, "索引文件内容")
+            .on_hover_text(format!(
+                "在后台读取文件条目和文件夹中的文本文件（单个文件不超过 {} KB），\
+                 之后可用 content:\"短语\" 搜索文件内容",
+                content_index::MAX_FILE_SIZE / 1024
+            ))
+            .changed()
+        {
+            self.reset_content_index();
+            if !self.config.content_indexing {
+                let _ = std::fs::remove_file(ContentIndex::index_path(self.data_store.location()));
+            }
+            self.force_update_filter();
+            let _ = self.save_config();
+        }
+        if self.config.content_indexing {
+            let status = if self.content_indexer.is_running() {
+                format!("正在索引…（已索引 {} 个文件）", self.content_index.len())
+            } else {
+                format!("已索引 {} 个文件", self.content_index.len())
+            };
+            ui.label(egui::RichText::new(status).small());
+        }
+
+        ui.add_space(16.0);
+        ui.collapsing("资料库", |ui| {
+            self.render_library_settings(ui);
+        });
+
+        ui.add_space(16.0);
+        ui.collapsing("变更日志", |ui| {
+            self.render_journal_settings(ui);
+        });
+
+        ui.add_space(16.0);
+        ui.collapsing("数据加密", |ui| {
+            self.render_encryption_settings(ui);
+        });
+
         ui.add_space(16.0);
         ui.collapsing("数据备份", |ui| {
             ui.label("快速备份当前数据");
@@ -2174,24 +4531,12 @@ impl FileManagerApp {
                         .set_file_name(&backup_name)
                         .save_file()
                     {
-                        let backup_data = UserData {
-                            entries: self.entries.clone(),
-                            version: env!("CARGO_PKG_VERSION").to_string(),
-                        };
+                        let backup_data = self.export_snapshot();
 
-                        match serde_json::to_string_pretty(&backup_data) {
-                            Ok(json) => match std::fs::write(&path, json) {
-                                Ok(_) => {
-                                    self.export_status = format!("备份成功: {}", path.display());
-                                }
-                                Err(e) => {
-                                    self.export_status = format!("备份失败: {}", e);
-                                }
-                            },
-                            Err(e) => {
-                                self.export_status = format!("备份序列化失败: {}", e);
-                            }
-                        }
+                        self.export_status = match self.write_export_file(&path, &backup_data) {
+                            Ok(()) => format!("备份成功: {}", path.display()),
+                            Err(e) => format!("备份失败: {}", e),
+                        };
                     }
                 }
 
@@ -2219,6 +4564,9 @@ impl FileManagerApp {
                 "当前位置: {}",
                 self.config_manager.get_config_path().display()
             ));
+            if paths::is_portable() {
+                ui.label("便携模式: 配置和数据默认保存在程序目录");
+            }
 
             ui.add_space(8.0);
             ui.horizontal(|ui| {
@@ -2258,10 +4606,13 @@ impl FileManagerApp {
 
         ui.add_space(8.0);
         ui.collapsing("用户数据文件", |ui| {
-            ui.label("数据文件格式: JSON");
+            ui.label(format!(
+                "数据文件格式: {}",
+                self.config.active_data_location().1.label()
+            ));
             ui.label(format!(
                 "当前位置: {}",
-                self.data_manager.get_data_path().display()
+                self.data_store.location().display()
             ));
 
             ui.add_space(8.0);
@@ -2285,33 +4636,71 @@ impl FileManagerApp {
                     let new_path = PathBuf::from(&self.custom_data_path);
 
                     // 先保存当前数据到新位置
-                    let old_manager = std::mem::replace(
-                        &mut self.data_manager,
-                        DataManager::new_with_path(new_path),
-                    );
-                    self.config.data_file_path = Some(self.custom_data_path.clone());
-
-                    if let Err(e) = self.save_user_data() {
-                        // 如果保存失败，恢复原来的数据管理器
-                        self.data_manager = old_manager;
-                        self.config.data_file_path = None;
-                        ui.label(format!("保存数据失败: {}", e));
-                    } else {
-                        // 保存配置中的数据路径
-                        let _ = self.save_config();
-                        ui.label("数据路径已更新");
+                    let (old_path, backend) = self.config.active_data_location();
+                    let old_path = old_path.cloned();
+                    match storage::open_store(backend, new_path) {
+                        Ok(new_store) => {
+                            let old_store = std::mem::replace(&mut self.data_store, new_store);
+                            self.config.set_active_data_location(
+                                Some(self.custom_data_path.clone()),
+                                backend,
+                            );
+                            let was_blocked =
+                                std::mem::replace(&mut self.data_save_blocked, false);
+                            self.reset_sync_state();
+
+                            if let Err(e) = self.save_user_data() {
+                                // 如果保存失败，恢复原来的数据存储
+                                self.data_store = old_store;
+                                self.config.set_active_data_location(old_path, backend);
+                                self.data_save_blocked = was_blocked;
+                                self.reset_sync_state();
+                                ui.label(format!("保存数据失败: {}", e));
+                            } else {
+                                // 保存配置中的数据路径
+                                let _ = self.save_config();
+                                ui.label("数据路径已更新");
+                            }
+                        }
+                        Err(e) => {
+                            ui.label(format!("打开数据文件失败: {}", e));
+                        }
                     }
                 }
 
                 if ui.button("重置数据路径").clicked() {
-                    self.data_manager = DataManager::new();
                     self.custom_data_path.clear();
-                    self.config.data_file_path = None;
-                    let _ = self.save_config();
-                    let _ = self.save_user_data();
+                    let backend = self.config.active_data_location().1;
+                    self.config.set_active_data_location(None, backend);
+                    match Self::open_data_store(&self.config) {
+                        Ok(store) => {
+                            self.data_store = store;
+                            self.reset_sync_state();
+                            let _ = self.save_config();
+                            let _ = self.save_user_data();
+                        }
+                        Err(e) => {
+                            self.storage_status = format!("打开数据文件失败: {}", e);
+                        }
+                    }
                 }
             });
 
+            ui.add_space(8.0);
+            let backend = self.config.active_data_location().1;
+            ui.label(format!("存储后端: {}", backend.label()));
+            if backend == StorageBackend::Json
+                && ui
+                    .button("迁移到 SQLite")
+                    .on_hover_text("条目较多时使用 SQLite 可按行增量保存")
+                    .clicked()
+            {
+                self.migrate_to_sqlite();
+            }
+            if !self.storage_status.is_empty() {
+                ui.label(&self.storage_status);
+            }
+
             ui.add_space(8.0);
             ui.label("提示:");
             ui.label("• 用户数据(文件列表)与应用配置分开保存");
@@ -2331,8 +4720,9 @@ impl FileManagerApp {
                     .max_height(200.0)
                     .show(ui, |ui| {
                         for (tag, count) in tag_stats.iter().take(20) {
+                            let info = tags::info_for(&self.user_data.tag_registry, tag);
                             ui.horizontal(|ui| {
-                                if ui.small_button(tag).clicked() {
+                                if Self::tag_chip(ui, tag, &info).clicked() {
                                     let tag_query = format!("#{}", tag.trim_start_matches('#'));
                                     self.search_query = tag_query;
                                     self.force_update_filter();
@@ -2356,7 +4746,7 @@ impl FileManagerApp {
         if ui.button("清空所有用户数据").clicked() {
             self.entries.clear();
             self.all_tags.clear();
-            let _ = self.save_user_data();
+            let _ = self.commit_entries("清空所有数据");
             self.force_update_filter();
         }
     }
@@ -2398,6 +4788,775 @@ impl FileManagerApp {
                 });
         }
     }
+
+    /// 把当前 JSON 数据迁移到同目录下的 SQLite 数据库并切换后端
+    fn migrate_to_sqlite(&mut self) {
+        if let Err(e) = self.save_user_data() {
+            self.storage_status = format!("迁移前保存数据失败: {}", e);
+            return;
+        }
+
+        let json_path = self.data_store.location().to_path_buf();
+        let db_path = json_path.with_extension("db");
+        match storage::migrate_json_to_sqlite(&json_path, &db_path) {
+            Ok(count) => match storage::open_store(StorageBackend::Sqlite, db_path.clone()) {
+                Ok(mut store) => {
+                    // 加载一次以建立增量保存所需的快照
+                    let _ = store.load();
+                    self.data_store = store;
+                    self.reset_sync_state();
+                    self.config.set_active_data_location(
+                        Some(db_path.to_string_lossy().to_string()),
+                        StorageBackend::Sqlite,
+                    );
+                    self.custom_data_path = db_path.to_string_lossy().to_string();
+                    let _ = self.save_config();
+                    self.storage_status = format!("已迁移 {} 个条目到 {}", count, db_path.display());
+                }
+                Err(e) => {
+                    self.storage_status = format!("打开数据库失败: {}", e);
+                }
+            },
+            Err(e) => {
+                self.storage_status = format!("迁移失败: {}", e);
+            }
+        }
+    }
+
+    /// 切换到指定资料库，None 表示默认资料库
+    fn switch_library(&mut self, name: Option<String>) {
+        if name == self.config.active_library {
+            return;
+        }
+
+        // 先保存当前资料库（暂停保存或未解锁时跳过，避免覆盖无法加载的原文件）
+        if !self.data_save_blocked
+            && !self.locked
+            && let Err(e) = self.save_user_data()
+        {
+            self.library_status = format!("保存当前资料库失败: {}", e);
+            return;
+        }
+
+        let previous = std::mem::replace(&mut self.config.active_library, name);
+        let opened = Self::open_data_store(&self.config).and_then(|mut store| {
+            // 加密的资料库切换后再解锁加载
+            let loaded = if store.is_locked() {
+                None
+            } else {
+                Some(store.load()?)
+            };
+            Ok((store, loaded))
+        });
+        let (store, loaded) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.config.active_library = previous;
+                self.library_status = format!("打开资料库失败: {}", e);
+                return;
+            }
+        };
+
+        self.data_store = store;
+        self.data_save_blocked = false;
+        match loaded {
+            Some(loaded) => {
+                self.locked = false;
+                self.apply_loaded_data(loaded);
+            }
+            None => self.clear_unlocked_data(),
+        }
+
+        // 应用资料库自己的主题和显示模式
+        self.theme_mode = Self::theme_mode_from_config(self.config.active_theme_mode());
+        if let Some(compact_mode) = self.config.active_compact_mode() {
+            self.compact_mode = compact_mode;
+        }
+        self.custom_data_path = self
+            .config
+            .active_data_location()
+            .0
+            .cloned()
+            .unwrap_or_default();
+
+        let _ = self.config_manager.save_config(&self.config);
+        self.library_status = format!("已切换到资料库“{}”", self.config.active_library_name());
+    }
+
+    /// 用新加载的数据替换当前条目（切换资料库或解锁后调用）
+    fn apply_loaded_data(&mut self, loaded: LoadedData) {
+        self.data_notice = loaded
+            .recovered_from
+            .as_deref()
+            .map(|backup| Self::recovered_notice("数据文件", backup));
+        self.user_data = loaded.data;
+        self.entries = std::mem::take(&mut self.user_data.entries);
+        if let Some(notice) = Self::cycle_notice(collections::break_cycles(&mut self.entries)) {
+            self.data_notice = Some(match self.data_notice.take() {
+                Some(recovered) => format!("{}\n\n{}", recovered, notice),
+                None => notice,
+            });
+        }
+        self.reset_sync_state();
+        self.reset_entry_view_state();
+        self.history = Self::load_history(&self.config, self.data_store.as_ref());
+        self.reset_content_index();
+        self.rebuild_tag_set();
+        self.force_update_filter();
+    }
+
+    /// 保存后清除内存中的密钥和条目，需要重新输入密码才能继续使用
+    fn lock_data(&mut self) {
+        if self.entries != self.base_entries
+            && let Err(e) = self.save_user_data()
+        {
+            self.encryption_status = format!("锁定前保存失败: {}", e);
+            return;
+        }
+        self.data_store.lock();
+        self.clear_unlocked_data();
+    }
+
+    /// 清除所有解密后的数据，进入锁定状态
+    fn clear_unlocked_data(&mut self) {
+        self.locked = true;
+        self.entries.clear();
+        self.base_entries.clear();
+        self.user_data = UserData::default();
+        self.base_user_data = UserData::default();
+        self.history = History::default();
+        self.content_indexer = ContentIndexer::default();
+        self.content_index = Arc::default();
+        self.pending_merge = None;
+        self.entry_history = None;
+        self.restore_preview = None;
+        self.unlock_input.clear();
+        self.unlock_error.clear();
+        self.reset_entry_view_state();
+        self.rebuild_tag_set();
+        self.force_update_filter();
+    }
+
+    fn unlock_data(&mut self) {
+        let result = self
+            .data_store
+            .unlock(&self.unlock_input)
+            .and_then(|_| self.data_store.load());
+        match result {
+            Ok(loaded) => {
+                self.locked = false;
+                self.unlock_input.clear();
+                self.unlock_error.clear();
+                self.last_activity = Instant::now();
+                self.apply_loaded_data(loaded);
+            }
+            Err(e) => {
+                self.data_store.lock();
+                self.unlock_error = e;
+            }
+        }
+    }
+
+    /// 有输入时刷新活动时间，空闲超过设定时间后自动锁定
+    fn check_auto_lock(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| !i.events.is_empty() || i.pointer.is_moving()) {
+            self.last_activity = Instant::now();
+        }
+
+        let minutes = self.config.auto_lock_minutes;
+        if !self.locked
+            && minutes > 0
+            && self.data_store.is_encrypted()
+            && self.last_activity.elapsed() >= Duration::from_secs(u64::from(minutes) * 60)
+        {
+            self.lock_data();
+            if self.locked {
+                self.unlock_error = format!("空闲超过 {} 分钟，已自动锁定", minutes);
+            } else {
+                // 保存失败时不锁定，等下一个空闲周期再试
+                self.last_activity = Instant::now();
+            }
+        }
+    }
+
+    /// 设置、修改（Some）或取消（None）数据文件的密码，并立即重新保存
+    fn set_data_passphrase(&mut self, passphrase: Option<String>) {
+        let was_encrypted = self.data_store.is_encrypted();
+        if let Err(e) = self.data_store.set_passphrase(passphrase.as_deref()) {
+            self.encryption_status = e;
+            return;
+        }
+
+        self.encryption_status = match self.persist_entries(None) {
+            Ok(()) => {
+                self.new_passphrase_input.clear();
+                self.confirm_passphrase_input.clear();
+                match (was_encrypted, passphrase.is_some()) {
+                    (false, true) => {
+                        self.remove_plaintext_files();
+                        "已启用加密".to_string()
+                    }
+                    (true, true) => {
+                        // 备份仍由旧密码加密，无法用新密码恢复
+                        atomic_file::remove_backups(self.data_store.location(), BACKUP_GENERATIONS);
+                        "密码已修改，旧密码加密的备份已删除".to_string()
+                    }
+                    _ => "已取消加密，数据文件恢复为明文".to_string(),
+                }
+            }
+            Err(e) => format!("保存失败: {}（新设置将在下次成功保存时生效）", e),
+        };
+    }
+
+    /// 启用加密后删除仍为明文的备份、损坏文件、变更日志和撤销历史
+    fn remove_plaintext_files(&mut self) {
+        let location = self.data_store.location().to_path_buf();
+        atomic_file::remove_backups(&location, BACKUP_GENERATIONS);
+        let _ = std::fs::remove_file(journal::journal_path(&location));
+        let _ = std::fs::remove_file(History::journal_path(&location));
+        self.history = History::default();
+        // 先停止后台扫描，避免它在删除之后再写入明文索引
+        self.content_indexer = ContentIndexer::default();
+        self.content_index = Arc::default();
+        self.last_content_scan = None;
+        let _ = std::fs::remove_file(ContentIndex::index_path(&location));
+    }
+
+    fn render_encryption_settings(&mut self, ui: &mut egui::Ui) {
+        let encrypted = self.data_store.is_encrypted();
+        if self.config.active_data_location().1 != StorageBackend::Json {
+            ui.label("只有 JSON 存储后端支持加密");
+            return;
+        }
+
+        ui.label(if encrypted {
+            "数据文件已加密（Argon2id + XChaCha20-Poly1305）"
+        } else {
+            "数据文件未加密"
+        });
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            ui.label(if encrypted { "新密码:" } else { "密码:" });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_passphrase_input)
+                    .password(true)
+                    .desired_width(160.0),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("确认密码:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.confirm_passphrase_input)
+                    .password(true)
+                    .desired_width(160.0),
+            );
+        });
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            let label = if encrypted { "修改密码" } else { "启用加密" };
+            let button = ui.button(label);
+            let button = if encrypted {
+                button
+            } else {
+                button.on_hover_text("启用后会删除明文的备份、变更日志和撤销历史文件")
+            };
+            if button.clicked() {
+                if self.new_passphrase_input.is_empty() {
+                    self.encryption_status = "密码不能为空".to_string();
+                } else if self.new_passphrase_input != self.confirm_passphrase_input {
+                    self.encryption_status = "两次输入的密码不一致".to_string();
+                } else {
+                    action = Some(Some(self.new_passphrase_input.clone()));
+                }
+            }
+
+            if encrypted {
+                if ui.button("取消加密").clicked() {
+                    action = Some(None);
+                }
+                if ui.button("立即锁定").clicked() {
+                    self.lock_data();
+                }
+            }
+        });
+        if let Some(passphrase) = action {
+            self.set_data_passphrase(passphrase);
+        }
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            ui.label("空闲自动锁定:");
+            if ui
+                .add(
+                    egui::DragValue::new(&mut self.config.auto_lock_minutes)
+                        .clamp_range(0..=240)
+                        .suffix(" 分钟"),
+                )
+                .on_hover_text("0 表示不自动锁定")
+                .changed()
+            {
+                let _ = self.save_config();
+            }
+        });
+        if encrypted {
+            ui.small("加密的数据不会写入变更日志和撤销历史");
+        }
+        ui.small("忘记密码将无法恢复数据，请妥善保管");
+
+        if !self.encryption_status.is_empty() {
+            ui.label(&self.encryption_status);
+        }
+    }
+
+    /// 锁定时代替主界面显示的解锁页面
+    fn render_unlock_screen(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(80.0);
+                ui.heading("数据已加密");
+                ui.add_space(8.0);
+                ui.label(format!(
+                    "资料库“{}”: {}",
+                    self.config.active_library_name(),
+                    self.data_store.location().display()
+                ));
+                ui.add_space(16.0);
+
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.unlock_input)
+                        .password(true)
+                        .hint_text("输入密码")
+                        .desired_width(220.0),
+                );
+                response.request_focus();
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                ui.add_space(8.0);
+                if (ui.button("解锁").clicked() || submitted) && !self.unlock_input.is_empty() {
+                    self.unlock_data();
+                }
+
+                if !self.unlock_error.is_empty() {
+                    ui.add_space(8.0);
+                    ui.colored_label(egui::Color32::RED, &self.unlock_error);
+                }
+
+                // 忘记密码时仍可切换到其他资料库
+                if !self.config.libraries.is_empty() {
+                    ui.add_space(24.0);
+                    let mut switch_to = None;
+                    egui::ComboBox::from_id_source("locked_library_switcher")
+                        .selected_text(self.config.active_library_name())
+                        .show_ui(ui, |ui| {
+                            for (label, name) in self.library_choices() {
+                                let selected = name == self.config.active_library;
+                                if ui.selectable_label(selected, label).clicked() && !selected {
+                                    switch_to = Some(name);
+                                }
+                            }
+                        });
+                    if let Some(name) = switch_to {
+                        self.switch_library(name);
+                    }
+                }
+            });
+        });
+    }
+
+    /// 把选中的条目复制或移动到另一个资料库
+    fn transfer_selected_to_library(&mut self, target: Option<String>, move_entries: bool) {
+        if self.selected_entries.is_empty() || target == self.config.active_library {
+            return;
+        }
+
+        let mut target_config = self.config.clone();
+        target_config.active_library = target;
+        let target_name = target_config.active_library_name().to_string();
+
+        let mut indices: Vec<usize> = self
+            .selected_entries
+            .iter()
+            .copied()
+            .filter(|&index| index < self.entries.len())
+            .collect();
+        indices.sort_unstable();
+        let selected: Vec<FileEntry> = indices.iter().map(|&i| self.entries[i].clone()).collect();
+
+        // 移动时保留目标资料库原来的数据，本地保存失败时用来撤销追加
+        let result = Self::open_data_store(&target_config).and_then(|mut store| {
+            let lock = DataLock::acquire(store.location())?;
+            let original = if move_entries {
+                Some(store.load()?.data)
+            } else {
+                None
+            };
+            let existing = storage::count_existing(store.as_ref(), &selected)?;
+            let count = storage::append_entries(store.as_mut(), &selected, move_entries)?;
+            Ok((count, existing, store, lock, original))
+        });
+        let (count, existing, mut store, _lock, original) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.library_status = format!("写入资料库“{}”失败: {}", target_name, e);
+                return;
+            }
+        };
+
+        if let Some(original) = original {
+            // 从当前资料库移除，并清理集合中对这些条目的引用
+            for &index in indices.iter().rev() {
+                self.entries.remove(index);
+            }
+            let moved_ids: HashSet<&String> = selected.iter().map(|e| &e.id).collect();
+            let mut children_before = HashMap::new();
+            for entry in &mut self.entries {
+                if entry.child_entries.iter().any(|id| moved_ids.contains(id)) {
+                    children_before.insert(entry.id.clone(), entry.child_entries.clone());
+                    entry.child_entries.retain(|id| !moved_ids.contains(id));
+                }
+            }
+            self.expanded_entries.clear();
+            self.focused_entry = None;
+
+            if let Err(e) = self.commit_entries("移动到资料库") {
+                // 本地保存失败：放回条目并撤销追加，避免条目同时出现在两个资料库中
+                for (&index, entry) in indices.iter().zip(&selected) {
+                    self.entries.insert(index.min(self.entries.len()), entry.clone());
+                }
+                for entry in &mut self.entries {
+                    if let Some(children) = children_before.remove(&entry.id) {
+                        entry.child_entries = children;
+                    }
+                }
+                self.library_status = match store.save(&original, &original.entries) {
+                    Ok(()) => format!("保存当前资料库失败，已取消移动: {}", e),
+                    Err(rollback) => format!(
+                        "保存当前资料库失败: {}；撤销资料库“{}”中的追加也失败: {}",
+                        e, target_name, rollback
+                    ),
+                };
+                self.force_update_filter();
+                return;
+            }
+            self.rebuild_tag_set();
+            self.force_update_filter();
+        }
+
+        self.selected_entries.clear();
+        self.multi_select_mode = false;
+        self.library_status = format!(
+            "已{} {} 个项目到资料库“{}”",
+            if move_entries { "移动" } else { "复制" },
+            count,
+            target_name
+        );
+        if existing > 0 {
+            self.library_status += &format!("，其中 {} 个在该资料库中已有相同的条目", existing);
+        }
+    }
+
+    /// 资料库列表（包含默认资料库），返回 (显示名称, 配置中的名称)
+    fn library_choices(&self) -> Vec<(String, Option<String>)> {
+        std::iter::once((AppConfig::DEFAULT_LIBRARY_NAME.to_string(), None))
+            .chain(
+                self.config
+                    .libraries
+                    .iter()
+                    .map(|lib| (lib.name.clone(), Some(lib.name.clone()))),
+            )
+            .collect()
+    }
+
+    /// 可作为复制/移动目标的其他资料库
+    fn library_transfer_targets(&self) -> Vec<(String, Option<String>)> {
+        self.library_choices()
+            .into_iter()
+            .filter(|(_, name)| *name != self.config.active_library)
+            .collect()
+    }
+
+    /// 多选菜单中的“移动/复制到资料库”子菜单，返回 (目标资料库, 是否移动)
+    fn render_library_transfer_menu(
+        ui: &mut egui::Ui,
+        targets: &[(String, Option<String>)],
+    ) -> Option<(Option<String>, bool)> {
+        if targets.is_empty() {
+            return None;
+        }
+
+        let mut chosen = None;
+        for (menu_label, move_entries) in [("移动到资料库", true), ("复制到资料库", false)] {
+            ui.menu_button(menu_label, |ui| {
+                for (label, name) in targets {
+                    if ui.button(label).clicked() {
+                        chosen = Some((name.clone(), move_entries));
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+        chosen
+    }
+
+    fn render_library_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("当前资料库: {}", self.config.active_library_name()));
+        ui.add_space(4.0);
+
+        let libraries_before = self.config.libraries.clone();
+        let mut to_remove = None;
+        let active = self.config.active_library.clone();
+        for (index, lib) in self.config.libraries.iter_mut().enumerate() {
+            ui.group(|ui| {
+                ui.horizontal(|ui| {
+                    ui.strong(&lib.name);
+                    if active.as_deref() != Some(lib.name.as_str())
+                        && ui.small_button("移除").on_hover_text("只从列表中移除，不删除数据文件").clicked()
+                    {
+                        to_remove = Some(index);
+                    }
+                });
+                ui.small(
+                    lib.data_file_path
+                        .clone()
+                        .unwrap_or_else(|| DataManager::library_data_path(&lib.name).display().to_string()),
+                );
+
+                let mut own_theme = lib.theme_mode.is_some();
+                if ui.checkbox(&mut own_theme, "使用独立主题").changed() {
+                    lib.theme_mode = own_theme.then(|| "Light".to_string());
+                }
+                if let Some(theme_mode) = &mut lib.theme_mode {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(theme_mode, "Light".to_string(), "浅色");
+                        ui.selectable_value(theme_mode, "Dark".to_string(), "深色");
+                        ui.selectable_value(theme_mode, "System".to_string(), "跟随系统");
+                    });
+                }
+
+                let mut own_compact = lib.compact_mode.is_some();
+                if ui.checkbox(&mut own_compact, "使用独立显示模式").changed() {
+                    lib.compact_mode = own_compact.then_some(true);
+                }
+                if let Some(compact_mode) = &mut lib.compact_mode {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(compact_mode, false, "普通");
+                        ui.selectable_value(compact_mode, true, "紧凑");
+                    });
+                }
+            });
+        }
+
+        if let Some(index) = to_remove {
+            self.config.libraries.remove(index);
+        }
+
+        ui.add_space(8.0);
+        ui.label("新建资料库:");
+        ui.horizontal(|ui| {
+            ui.label("名称:");
+            ui.text_edit_singleline(&mut self.new_library_name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("数据文件:");
+            ui.text_edit_singleline(&mut self.new_library_path)
+                .on_hover_text("留空则在默认数据目录下创建");
+            if ui.small_button("选择").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON文件", &["json"])
+                    .set_file_name("file_manager_data.json")
+                    .save_file()
+            {
+                self.new_library_path = path.to_string_lossy().to_string();
+            }
+        });
+
+        if ui.button("创建资料库").clicked() {
+            let name = self.new_library_name.trim().to_string();
+            if name.is_empty() {
+                self.library_status = "资料库名称不能为空".to_string();
+            } else if name == AppConfig::DEFAULT_LIBRARY_NAME || self.config.find_library(&name).is_some() {
+                self.library_status = format!("资料库“{}”已存在", name);
+            } else {
+                let path = self.new_library_path.trim();
+                let path = if path.is_empty() {
+                    DataManager::library_data_path(&name)
+                } else {
+                    PathBuf::from(path)
+                };
+                if let Some(other) = self.config.library_using_path(&path) {
+                    self.library_status = format!(
+                        "资料库“{}”已使用数据文件 {}，请换一个名称或指定其他数据文件",
+                        other,
+                        path.display()
+                    );
+                } else {
+                    self.config.libraries.push(config::LibraryProfile::new(
+                        name.clone(),
+                        Some(path.to_string_lossy().to_string()),
+                    ));
+                    self.new_library_name.clear();
+                    self.new_library_path.clear();
+                    self.library_status = format!("已创建资料库“{}”", name);
+                }
+            }
+        }
+
+        if !self.library_status.is_empty() {
+            ui.label(&self.library_status);
+        }
+
+        // 资料库列表或覆盖设置有变化时立即保存，并让当前资料库的覆盖生效
+        if self.config.libraries != libraries_before {
+            self.theme_mode = Self::theme_mode_from_config(self.config.active_theme_mode());
+            if let Some(compact_mode) = self.config.active_compact_mode() {
+                self.compact_mode = compact_mode;
+            }
+            let _ = self.config_manager.save_config(&self.config);
+        }
+
+        ui.add_space(4.0);
+        ui.label("提示: 启动时可使用 --library 名称 打开指定资料库");
+    }
+
+    /// 外部修改与本地修改冲突时的处理窗口
+    fn render_merge_conflict_dialog(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &mut self.pending_merge else {
+            return;
+        };
+
+        let describe = |entry: &Option<FileEntry>| match entry {
+            Some(entry) => {
+                let mut text = entry.path.display().to_string();
+                if !entry.tags.is_empty() {
+                    text.push_str(&format!("  {}", entry.tags.join(" ")));
+                }
+                text
+            }
+            None => "（已删除）".to_string(),
+        };
+
+        let mut finished = false;
+        let mut keep_all = None;
+        egui::Window::new("数据文件冲突")
+            .collapsible(false)
+            .resizable(true)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("数据文件已被其他实例或程序修改，以下条目在两边都有改动:");
+                ui.add_space(8.0);
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (conflict, choice) in
+                        pending.result.conflicts.iter().zip(pending.choices.iter_mut())
+                    {
+                        ui.group(|ui| {
+                            ui.strong(conflict.display_name());
+                            ui.radio_value(
+                                choice,
+                                Resolution::Ours,
+                                format!("保留本地: {}", describe(&conflict.ours)),
+                            );
+                            ui.radio_value(
+                                choice,
+                                Resolution::Theirs,
+                                format!("使用外部: {}", describe(&conflict.theirs)),
+                            );
+                        });
+                    }
+                });
+
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("全部保留本地").clicked() {
+                        keep_all = Some(Resolution::Ours);
+                    }
+                    if ui.button("全部使用外部").clicked() {
+                        keep_all = Some(Resolution::Theirs);
+                    }
+                    ui.separator();
+                    if ui.button("应用").clicked() {
+                        finished = true;
+                    }
+                });
+            });
+
+        if let Some(resolution) = keep_all {
+            pending.choices.fill(resolution);
+        }
+        if !finished {
+            return;
+        }
+
+        if let Some(pending) = self.pending_merge.take() {
+            // 对话框打开期间本地可能又有修改：以当前条目重新合并，本地版本没变的冲突沿用已做的
+            // 选择，其余保留本地
+            let result =
+                sync::merge_entries(&self.base_entries, &self.entries, &pending.their_entries);
+            let choices: Vec<Resolution> = result
+                .conflicts
+                .iter()
+                .map(|conflict| {
+                    pending
+                        .result
+                        .conflicts
+                        .iter()
+                        .zip(&pending.choices)
+                        .find(|(old, _)| old.id == conflict.id && old.ours == conflict.ours)
+                        .map_or(Resolution::Ours, |(_, choice)| *choice)
+                })
+                .collect();
+            let merged = result.resolve(&choices);
+            self.apply_merged_entries(merged, pending.their_entries, pending.disk_version);
+            if let Err(e) = self.save_user_data() {
+                self.library_status = format!("保存合并结果失败: {}", e);
+            }
+        }
+    }
+
+    fn render_data_notice_dialog(&mut self, ctx: &egui::Context) {
+        let Some(notice) = self.data_notice.clone() else {
+            return;
+        };
+
+        egui::Window::new("数据文件提示")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(10.0);
+                    ui.label(&notice);
+                    ui.add_space(10.0);
+                    ui.label(format!(
+                        "数据文件: {}",
+                        self.data_store.location().display()
+                    ));
+                    ui.add_space(20.0);
+
+                    ui.horizontal(|ui| {
+                        if ui.button("知道了").clicked() {
+                            self.data_notice = None;
+                        }
+
+                        if self.data_save_blocked {
+                            ui.add_space(20.0);
+                            if ui
+                                .button("仍然允许保存")
+                                .on_hover_text("之后的修改会覆盖当前数据文件")
+                                .clicked()
+                            {
+                                self.data_save_blocked = false;
+                                self.data_notice = None;
+                            }
+                        }
+                    });
+                    ui.add_space(10.0);
+                });
+            });
+    }
 }
 
 impl eframe::App for FileManagerApp {
@@ -2408,11 +5567,23 @@ impl eframe::App for FileManagerApp {
         // 应用主题
         self.apply_theme(ctx);
 
+        // 加密数据：空闲自动锁定，锁定时只显示解锁页面
+        self.check_auto_lock(ctx);
+        if self.locked {
+            self.render_unlock_screen(ctx);
+            ctx.request_repaint_after(SYNC_CHECK_INTERVAL);
+            return;
+        }
+
         // 处理快捷键
         self.handle_shortcuts(ctx);
 
         // 处理拖拽文件
         ctx.input(|i| {
+            if i.raw.dropped_files.is_empty() {
+                return;
+            }
+            let rules = RuleSet::new(&self.user_data.auto_tag_rules);
             for file in &i.raw.dropped_files {
                 if let Some(path) = &file.path {
                     // 直接添加拖拽的文件，而不是只设置到输入框
@@ -2424,9 +5595,12 @@ impl eframe::App for FileManagerApp {
                         .to_string();
                     let is_directory = path_buf.is_dir();
 
-                    let entry = FileEntry::new(path_buf, name, None, Vec::new(), is_directory);
+                    let mut entry =
+                        FileEntry::new(path_buf, name, None, Vec::new(), is_directory);
+                    rules.apply(&mut entry);
+                    self.all_tags.extend(entry.tags.iter().cloned());
                     self.entries.push(entry);
-                    let _ = self.save_user_data();
+                    let _ = self.commit_entries("拖入文件");
 
                     // 强制重新过滤并更新索引
                     self.force_update_filter();
@@ -2438,6 +5612,36 @@ impl eframe::App for FileManagerApp {
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.label(egui::RichText::new("文件管理器").strong());
+
+                // 资料库切换
+                let mut switch_to = None;
+                egui::ComboBox::from_id_source("library_switcher")
+                    .selected_text(self.config.active_library_name())
+                    .show_ui(ui, |ui| {
+                        for (label, name) in self.library_choices() {
+                            let selected = name == self.config.active_library;
+                            if ui.selectable_label(selected, label).clicked() && !selected {
+                                switch_to = Some(name);
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("切换资料库，可在设置中新建");
+                if let Some(name) = switch_to {
+                    self.switch_library(name);
+                }
+                if !self.library_status.is_empty()
+                    && !self.show_settings
+                    && ui
+                        .add(
+                            egui::Label::new(egui::RichText::new(&self.library_status).small())
+                                .sense(egui::Sense::click()),
+                        )
+                        .on_hover_text("点击清除")
+                        .clicked()
+                {
+                    self.library_status.clear();
+                }
                 ui.separator();
 
                 // 统一搜索框（支持文件名、标签和描述）
@@ -2446,7 +5650,19 @@ impl eframe::App for FileManagerApp {
                     [200.0, 20.0],
                     egui::TextEdit::singleline(&mut self.search_query)
                         .hint_text("搜索文件、标签...")
-                );
+                ).on_hover_text(SEARCH_SYNTAX_HELP);
+
+                // 查询语法错误显示在搜索框下方
+                if let Some(error) = &self.query_error {
+                    egui::Area::new("query_error")
+                        .order(egui::Order::Foreground)
+                        .fixed_pos(search_response.rect.left_bottom() + egui::vec2(0.0, 2.0))
+                        .show(ui.ctx(), |ui| {
+                            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                                ui.colored_label(egui::Color32::from_rgb(200, 80, 80), error);
+                            });
+                        });
+                }
                 
                 // 处理搜索框焦点
                 if self.search_has_focus {
@@ -2456,16 +5672,53 @@ impl eframe::App for FileManagerApp {
                 
                 // 检测搜索框当前是否有焦点，用于确定是否启用快捷键
                 self.search_currently_focused = search_response.has_focus();
-                if ui.ctx().input(|i| i.key_pressed(egui::Key::Enter) && self.search_currently_focused)
-                    || self.search_query != self.last_search_query
-                {
+                if ui.ctx().input(|i| i.key_pressed(egui::Key::Enter) && self.search_currently_focused) {
                     self.force_update_filter();
+                } else {
+                    // 输入时条目没有变化，不需要同步搜索索引
+                    self.update_filter();
                 }
 
                 if !self.search_query.is_empty() && ui.small_button("清除").clicked() {
                     self.search_query.clear();
                     self.force_update_filter();
                 }
+                if !self.search_query.trim().is_empty()
+                    && self.query_error.is_none()
+                    && ui
+                        .small_button("保存搜索")
+                        .on_hover_text("保存为智能集合，成员随资料库变化自动更新")
+                        .clicked()
+                {
+                    self.toggle_panel("add_dialog");
+                    self.add_entry_type = crate::file_entry::EntryType::Collection;
+                    self.add_smart_collection = true;
+                    self.add_smart_query_input = self.search_query.trim().to_string();
+                }
+
+                // 排序和时间筛选
+                let old_sort = self.config.sort_order;
+                let old_time_filter = self.time_filter;
+                egui::ComboBox::from_id_source("sort_order")
+                    .selected_text(self.config.sort_order.label())
+                    .show_ui(ui, |ui| {
+                        for order in SortOrder::ALL {
+                            ui.selectable_value(&mut self.config.sort_order, order, order.label());
+                        }
+                    });
+                egui::ComboBox::from_id_source("time_filter")
+                    .selected_text(self.time_filter.label())
+                    .show_ui(ui, |ui| {
+                        for filter in TimeFilter::ALL {
+                            ui.selectable_value(&mut self.time_filter, filter, filter.label());
+                        }
+                    });
+                if self.config.sort_order != old_sort {
+                    let _ = self.save_config();
+                }
+                if self.config.sort_order != old_sort || self.time_filter != old_time_filter {
+                    self.force_update_filter();
+                }
 
                 ui.separator();
 
@@ -2487,9 +5740,46 @@ impl eframe::App for FileManagerApp {
                         self.toggle_panel("import_export");
                     }
 
+                    if ui.button("运行日志").clicked() {
+                        self.show_run_log = !self.show_run_log;
+                    }
+
                     if ui.button("设置").clicked() {
                         self.toggle_panel("settings");
                     }
+
+                    ui.separator();
+                    let cmd_key = if cfg!(target_os = "macos") { "Cmd" } else { "Ctrl" };
+                    let undo_label = self.history.undo_label().map(str::to_string);
+                    if ui
+                        .add_enabled(undo_label.is_some(), egui::Button::new("撤销"))
+                        .on_hover_text(format!(
+                            "撤销: {} ({}+Z)",
+                            undo_label.unwrap_or_default(),
+                            cmd_key
+                        ))
+                        .clicked()
+                    {
+                        self.undo();
+                    }
+                    let redo_label = self.history.redo_label().map(str::to_string);
+                    if ui
+                        .add_enabled(redo_label.is_some(), egui::Button::new("重做"))
+                        .on_hover_text(format!(
+                            "重做: {} ({}+Shift+Z)",
+                            redo_label.unwrap_or_default(),
+                            cmd_key
+                        ))
+                        .clicked()
+                    {
+                        self.redo();
+                    }
+
+                    if self.data_store.is_encrypted()
+                        && ui.button("锁定").on_hover_text("清除内存中的数据和密钥").clicked()
+                    {
+                        self.lock_data();
+                    }
                 });
 
                 // 显示多选状态和批量操作
@@ -2506,6 +5796,12 @@ impl eframe::App for FileManagerApp {
                                 self.batch_collection_name.clear();
                                 self.toggle_panel("batch_collection_dialog");
                             }
+                            let targets = self.library_transfer_targets();
+                            if let Some((target, move_entries)) =
+                                Self::render_library_transfer_menu(ui, &targets)
+                            {
+                                self.transfer_selected_to_library(target, move_entries);
+                            }
                         }
                         
                         if ui.button("退出多选").clicked() {
@@ -2587,5 +5883,23 @@ if self.show_add_dialog
 
         // 删除确认对话框
         self.render_delete_confirm_dialog(ctx);
+        self.render_command_confirm_dialog(ctx);
+
+        // 数据恢复/加载失败提示
+        self.render_data_notice_dialog(ctx);
+
+        // 多实例同步：检测外部修改并处理冲突
+        self.check_external_changes();
+        self.render_merge_conflict_dialog(ctx);
+
+        // 文件内容索引
+        self.check_content_index();
+
+        // 条目变更历史
+        self.render_entry_history_window(ctx);
+
+        // 命令运行日志
+        self.render_run_log_window(ctx);
+        ctx.request_repaint_after(SYNC_CHECK_INTERVAL);
     }
 }
@@ -0,0 +1,212 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 默认保留的备份代数
+pub const BACKUP_GENERATIONS: usize = 3;
+
+/// 带恢复信息的加载结果
+pub struct Recovered<T> {
+    pub value: T,
+    /// 若主文件损坏并从备份恢复，记录所使用的备份文件
+    pub recovered_from: Option<PathBuf>,
+}
+
+/// 获取第 n 代备份文件路径，例如 `data.json.bak1`
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak{}", generation));
+    path.with_file_name(name)
+}
+
+/// 无法解析时另存的损坏文件路径，例如 `data.json.corrupt`
+pub fn corrupt_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".corrupt");
+    path.with_file_name(name)
+}
+
+/// 删除所有备份和另存的损坏文件
+pub fn remove_backups(path: &Path, generations: usize) {
+    for generation in 1..=generations {
+        let _ = fs::remove_file(backup_path(path, generation));
+    }
+    let _ = fs::remove_file(corrupt_path(path));
+}
+
+/// 原子写入：先写入同目录下的临时文件并 fsync，再重命名覆盖目标文件
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+
+    let mut temp_file =
+        tempfile::NamedTempFile::new_in(&dir).map_err(|e| format!("创建临时文件失败: {}", e))?;
+    temp_file
+        .write_all(contents)
+        .map_err(|e| format!("写入临时文件失败: {}", e))?;
+    temp_file
+        .as_file()
+        .sync_all()
+        .map_err(|e| format!("同步临时文件失败: {}", e))?;
+    temp_file
+        .persist(path)
+        .map_err(|e| format!("替换文件失败: {}", e.error))?;
+
+    // 同步目录项，确保重命名本身也已落盘
+    #[cfg(unix)]
+    {
+        if let Ok(dir_handle) = fs::File::open(&dir) {
+            let _ = dir_handle.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+/// 滚动备份：bak(n-1) → bak(n)，…，当前文件 → bak1
+pub fn rotate_backups(path: &Path, generations: usize) -> Result<(), String> {
+    if generations == 0 || !path.exists() {
+        return Ok(());
+    }
+
+    for generation in (1..generations).rev() {
+        let from = backup_path(path, generation);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, generation + 1))
+                .map_err(|e| format!("轮换备份失败: {}", e))?;
+        }
+    }
+
+    // 使用复制而不是重命名，保证任意时刻主文件都存在
+    fs::copy(path, backup_path(path, 1)).map_err(|e| format!("创建备份失败: {}", e))?;
+    Ok(())
+}
+
+/// 保存文件：先轮换备份，再原子写入
+pub fn save_with_backups(path: &Path, contents: &[u8], generations: usize) -> Result<(), String> {
+    rotate_backups(path, generations)?;
+    write_atomic(path, contents)
+}
+
+/// 读取并解析文件，主文件损坏时依次尝试各代备份
+///
+/// 主文件不存在时返回 `Ok(None)`；主文件和所有备份都无法解析时返回错误，
+/// 此时损坏的主文件会被另存为 `.corrupt` 文件以便手动排查。
+pub fn load_with_recovery<T>(
+    path: &Path,
+    generations: usize,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<Recovered<T>>, String> {
+    let primary_error = match fs::read_to_string(path) {
+        Ok(content) => match parse(&content) {
+            Ok(value) => {
+                return Ok(Some(Recovered {
+                    value,
+                    recovered_from: None,
+                }));
+            }
+            Err(e) => e,
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => format!("读取文件失败: {}", e),
+    };
+
+    // 保留损坏的主文件，避免后续保存将其覆盖
+    let _ = fs::copy(path, corrupt_path(path));
+
+    for generation in 1..=generations {
+        let candidate = backup_path(path, generation);
+        if let Ok(content) = fs::read_to_string(&candidate)
+            && let Ok(value) = parse(&content)
+        {
+            return Ok(Some(Recovered {
+                value,
+                recovered_from: Some(candidate),
+            }));
+        }
+    }
+
+    Err(format!("{}，且没有可用的备份", primary_error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_number(content: &str) -> Result<u32, String> {
+        content.trim().parse::<u32>().map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+
+        write_atomic(&path, b"1").unwrap();
+        write_atomic(&path, b"2").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2");
+        // 不应残留临时文件
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_rotating_backups_keeps_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+
+        for i in 1..=5 {
+            save_with_backups(&path, i.to_string().as_bytes(), 3).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "5");
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "4");
+        assert_eq!(fs::read_to_string(backup_path(&path, 2)).unwrap(), "3");
+        assert_eq!(fs::read_to_string(backup_path(&path, 3)).unwrap(), "2");
+        assert!(!backup_path(&path, 4).exists());
+    }
+
+    #[test]
+    fn test_recovery_from_newest_valid_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+
+        save_with_backups(&path, b"1", 3).unwrap();
+        save_with_backups(&path, b"2", 3).unwrap();
+        save_with_backups(&path, b"3", 3).unwrap();
+
+        // 模拟写入中途崩溃：主文件和最新备份都被截断
+        fs::write(&path, b"").unwrap();
+        fs::write(backup_path(&path, 1), b"").unwrap();
+
+        let loaded = load_with_recovery(&path, 3, parse_number).unwrap().unwrap();
+        assert_eq!(loaded.value, 1);
+        assert_eq!(loaded.recovered_from, Some(backup_path(&path, 2)));
+    }
+
+    #[test]
+    fn test_missing_file_and_unrecoverable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+
+        assert!(
+            load_with_recovery(&path, 3, parse_number)
+                .unwrap()
+                .is_none()
+        );
+
+        fs::write(&path, b"not a number").unwrap();
+        assert!(load_with_recovery(&path, 3, parse_number).is_err());
+        // 损坏的文件应被另存
+        assert!(dir.path().join("data.json.corrupt").exists());
+
+        save_with_backups(&path, b"1", 3).unwrap();
+        remove_backups(&path, 3);
+        assert!(!corrupt_path(&path).exists());
+        assert!(!backup_path(&path, 1).exists());
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,327 @@
+//! 自动标签规则：按路径通配符、扩展名、网址域名或名称正则，为新添加的条目加上标签
+
+use crate::file_entry::{EntryType, FileEntry};
+use crate::tags;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// 规则的匹配方式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    /// 路径通配符：`*` 和 `?` 不跨越目录，`**` 匹配任意层目录
+    PathGlob,
+    /// 文件扩展名，多个用逗号或空格分隔，不区分大小写
+    Extension,
+    /// 网址的域名，同时匹配其子域名
+    UrlDomain,
+    /// 名称或昵称的正则表达式
+    NameRegex,
+}
+
+impl RuleKind {
+    pub const ALL: [RuleKind; 4] = [
+        RuleKind::PathGlob,
+        RuleKind::Extension,
+        RuleKind::UrlDomain,
+        RuleKind::NameRegex,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RuleKind::PathGlob => "路径通配符",
+            RuleKind::Extension => "扩展名",
+            RuleKind::UrlDomain => "网址域名",
+            RuleKind::NameRegex => "名称正则",
+        }
+    }
+
+    pub fn hint(self) -> &'static str {
+        match self {
+            RuleKind::PathGlob => "例如 ~/work/**/*.md",
+            RuleKind::Extension => "例如 pdf, docx",
+            RuleKind::UrlDomain => "例如 github.com",
+            RuleKind::NameRegex => "例如 (?i)^report",
+        }
+    }
+}
+
+/// 一条自动标签规则
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AutoTagRule {
+    pub kind: RuleKind,
+    pub pattern: String,
+    /// 匹配时添加的标签（已规范化）
+    pub tags: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl AutoTagRule {
+    /// 创建规则并检查写法，标签会被规范化
+    pub fn new(kind: RuleKind, pattern: &str, tags: Vec<String>) -> Result<Self, String> {
+        let rule = Self {
+            kind,
+            pattern: pattern.trim().to_string(),
+            tags: tags.iter().filter_map(|t| tags::normalize(t)).collect(),
+            enabled: true,
+        };
+        if rule.pattern.is_empty() {
+            return Err("请填写匹配条件".to_string());
+        }
+        if rule.tags.is_empty() {
+            return Err("请填写要添加的标签".to_string());
+        }
+        Matcher::compile(&rule)?;
+        Ok(rule)
+    }
+}
+
+/// 编译后的匹配条件
+enum Matcher {
+    Path(Regex),
+    Extensions(Vec<String>),
+    Domain(String),
+    Name(Regex),
+}
+
+impl Matcher {
+    fn compile(rule: &AutoTagRule) -> Result<Self, String> {
+        Ok(match rule.kind {
+            RuleKind::PathGlob => Matcher::Path(glob_to_regex(&rule.pattern)?),
+            RuleKind::Extension => Matcher::Extensions(
+                rule.pattern
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|ext| !ext.is_empty())
+                    .collect(),
+            ),
+            RuleKind::UrlDomain => Matcher::Domain(
+                rule.pattern
+                    .trim_start_matches("*.")
+                    .trim_matches('.')
+                    .to_lowercase(),
+            ),
+            RuleKind::NameRegex => Matcher::Name(
+                Regex::new(&rule.pattern).map_err(|e| format!("正则表达式无效: {}", e))?,
+            ),
+        })
+    }
+
+    fn matches(&self, entry: &FileEntry) -> bool {
+        let has_path = matches!(entry.entry_type, EntryType::File | EntryType::Directory);
+        match self {
+            Matcher::Path(regex) => has_path && regex.is_match(&normalize_path(entry)),
+            Matcher::Extensions(extensions) => {
+                entry.entry_type == EntryType::File
+                    && entry
+                        .path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| extensions.contains(&ext.to_lowercase()))
+            }
+            Matcher::Domain(domain) => {
+                entry.url.as_deref().and_then(url_host).is_some_and(|host| {
+                    host == *domain
+                        || host
+                            .strip_suffix(domain.as_str())
+                            .is_some_and(|sub| sub.ends_with('.'))
+                })
+            }
+            Matcher::Name(regex) => {
+                regex.is_match(&entry.name)
+                    || entry.nickname.as_deref().is_some_and(|n| regex.is_match(n))
+            }
+        }
+    }
+}
+
+/// 条目路径，统一用 `/` 分隔
+fn normalize_path(entry: &FileEntry) -> String {
+    entry.path.to_string_lossy().replace('\\', "/")
+}
+
+/// 把通配符转换为匹配整个路径的正则表达式，`~/` 开头时按主目录展开
+fn glob_to_regex(glob: &str) -> Result<Regex, String> {
+    let mut glob = glob.replace('\\', "/");
+    if let Some(rest) = glob.strip_prefix("~/")
+        && let Some(home) = dirs::home_dir()
+    {
+        glob = format!("{}/{}", home.to_string_lossy().replace('\\', "/"), rest);
+    }
+
+    // Windows 的路径不区分大小写
+    let mut pattern = String::from(if cfg!(windows) { "(?i)^" } else { "^" });
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).map_err(|e| format!("通配符无效: {}", e))
+}
+
+/// 网址中的主机名（小写），没有协议时整个地址按主机名开头处理
+fn url_host(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = host.split(':').next()?.trim_end_matches('.');
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// 一组编译好的规则；写法有误或已停用的规则被忽略
+pub struct RuleSet {
+    rules: Vec<(Matcher, Vec<String>)>,
+}
+
+impl RuleSet {
+    pub fn new(rules: &[AutoTagRule]) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .filter(|rule| rule.enabled)
+                .filter_map(|rule| Some((Matcher::compile(rule).ok()?, rule.tags.clone())))
+                .collect(),
+        }
+    }
+
+    /// 条目按规则应添加、但还没有的标签（去重、排序）
+    pub fn tags_for(&self, entry: &FileEntry) -> Vec<String> {
+        let mut added: Vec<String> = self
+            .rules
+            .iter()
+            .filter(|(matcher, _)| matcher.matches(entry))
+            .flat_map(|(_, tags)| tags.iter())
+            .filter(|tag| !entry.tags.contains(tag))
+            .cloned()
+            .collect();
+        added.sort();
+        added.dedup();
+        added
+    }
+
+    /// 为条目添加匹配的标签，返回是否有修改
+    pub fn apply(&self, entry: &mut FileEntry) -> bool {
+        let added = self.tags_for(entry);
+        if added.is_empty() {
+            return false;
+        }
+        entry.tags.extend(added);
+        tags::dedup(&mut entry.tags);
+        entry.touch();
+        true
+    }
+
+    /// 对已有条目试运行：返回会被修改的条目位置及其将添加的标签，不修改条目
+    pub fn preview(&self, entries: &[FileEntry]) -> Vec<(usize, Vec<String>)> {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (i, self.tags_for(entry)))
+            .filter(|(_, added)| !added.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::file_at;
+
+    fn rule(kind: RuleKind, pattern: &str, tag: &str) -> AutoTagRule {
+        AutoTagRule::new(kind, pattern, vec![tag.to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_rule_kinds() {
+        let rules = RuleSet::new(&[
+            rule(RuleKind::PathGlob, "/work/**/*.md", "工作/笔记"),
+            rule(RuleKind::Extension, ".PDF, docx", "#文档"),
+            rule(RuleKind::UrlDomain, "github.com", "#代码"),
+            rule(RuleKind::NameRegex, "(?i)^report", "#报告"),
+        ]);
+
+        assert_eq!(rules.tags_for(&file_at("/work/a.md")), vec!["#工作/笔记"]);
+        assert_eq!(
+            rules.tags_for(&file_at("/work/x/y/a.md")),
+            vec!["#工作/笔记"]
+        );
+        assert!(rules.tags_for(&file_at("/work/a.mdx")).is_empty());
+        assert_eq!(
+            rules.tags_for(&file_at("/home/Report 2024.pdf")),
+            vec!["#报告", "#文档"]
+        );
+
+        let link = |url: &str| {
+            FileEntry::new_web_link("站点".to_string(), url.to_string(), None, None, vec![])
+        };
+        assert_eq!(
+            rules.tags_for(&link("https://gist.github.com/a")),
+            vec!["#代码"]
+        );
+        assert_eq!(
+            rules.tags_for(&link("GitHub.com:443/rust-lang")),
+            vec!["#代码"]
+        );
+        assert!(rules.tags_for(&link("https://notgithub.com")).is_empty());
+    }
+
+    #[test]
+    fn test_glob_does_not_cross_directories() {
+        let rules = RuleSet::new(&[rule(RuleKind::PathGlob, "/work/*.md", "#笔记")]);
+        assert!(rules.apply(&mut file_at("/work/a.md")));
+        assert!(!rules.apply(&mut file_at("/work/x/a.md")));
+    }
+
+    #[test]
+    fn test_apply_and_preview() {
+        let mut disabled = rule(RuleKind::Extension, "txt", "#文本");
+        disabled.enabled = false;
+        let rules = RuleSet::new(&[rule(RuleKind::Extension, "pdf", "#文档"), disabled]);
+
+        let mut entries = vec![file_at("/a.pdf"), file_at("/b.txt"), file_at("/c.pdf")];
+        entries[2].tags.push("#文档".to_string());
+        // 试运行不修改条目，已有标签的条目不列出
+        assert_eq!(
+            rules.preview(&entries),
+            vec![(0, vec!["#文档".to_string()])]
+        );
+        assert!(entries[0].tags.is_empty());
+
+        assert!(rules.apply(&mut entries[0]));
+        assert_eq!(entries[0].tags, vec!["#文档"]);
+        assert!(!rules.apply(&mut entries[0]));
+
+        // 新标签加在后面，不打乱已有标签的顺序
+        let mut tagged = file_at("/d.pdf");
+        tagged.tags.push("#项目".to_string());
+        assert!(rules.apply(&mut tagged));
+        assert_eq!(tagged.tags, vec!["#项目", "#文档"]);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(AutoTagRule::new(RuleKind::NameRegex, "(", vec!["#a".to_string()]).is_err());
+        assert!(AutoTagRule::new(RuleKind::Extension, " ", vec!["#a".to_string()]).is_err());
+        assert!(AutoTagRule::new(RuleKind::Extension, "pdf", vec!["#".to_string()]).is_err());
+    }
+}
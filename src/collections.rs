@@ -0,0 +1,612 @@
+//! 嵌套集合：集合可以包含其他集合，打开和展开时按深度限制递归，并防止循环引用
+
+use crate::content_index::ContentIndex;
+use crate::file_entry::{EntryType, FileEntry, LaunchMode};
+use crate::query;
+use crate::search_index::{EntryIndex, SearchIndex};
+use chrono::Utc;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+/// 递归打开或展开集合的最大层数
+pub const MAX_DEPTH: usize = 8;
+
+fn find<'a>(entries: &'a [FileEntry], id: &str) -> Option<&'a FileEntry> {
+    entries.iter().find(|e| e.id == id)
+}
+
+/// 缓存的搜索条件数超过这个值时清空，避免编辑条件时逐字累积
+const SMART_CACHE_LIMIT: usize = 64;
+
+/// 满足搜索条件的条目位置和ID，或条件的错误
+type Found = Result<Vec<(usize, String)>, String>;
+
+/// 智能集合成员的缓存：搜索条件 → 满足条件的条目位置和ID，或条件的错误。
+/// 条目、标签别名或内容索引变化后调用 `clear`；位置上的条目对不上时重新计算
+#[derive(Default)]
+pub struct SmartCache {
+    members: RefCell<HashMap<String, Found>>,
+}
+
+impl SmartCache {
+    pub fn clear(&self) {
+        self.members.borrow_mut().clear();
+    }
+}
+
+/// 求集合成员所需的数据：智能集合与搜索框一样使用搜索索引、标签别名和内容索引
+pub struct Members<'a> {
+    pub entries: &'a [FileEntry],
+    pub search_index: &'a SearchIndex,
+    pub aliases: &'a BTreeMap<String, String>,
+    pub content: Option<&'a ContentIndex>,
+    pub cache: &'a SmartCache,
+}
+
+impl<'a> Members<'a> {
+    /// 集合的成员：普通集合按子项目ID查找；智能集合为满足搜索条件的条目（不含集合），
+    /// 搜索条件有误时返回错误
+    pub fn of(&self, collection: &FileEntry) -> Result<Vec<&'a FileEntry>, String> {
+        let Some(text) = &collection.smart_query else {
+            return Ok(collection
+                .child_entries
+                .iter()
+                .filter_map(|id| find(self.entries, id))
+                .collect());
+        };
+
+        let entries = self.entries;
+        let resolve = |found: &[(usize, String)]| -> Option<Vec<&'a FileEntry>> {
+            found
+                .iter()
+                .map(|(i, id)| entries.get(*i).filter(|e| &e.id == id))
+                .collect()
+        };
+        if let Some(cached) = self.cache.members.borrow().get(text) {
+            match cached {
+                Ok(found) => {
+                    if let Some(members) = resolve(found) {
+                        return Ok(members);
+                    }
+                }
+                Err(e) => return Err(e.clone()),
+            }
+        }
+
+        let found = self.evaluate(text);
+        let result = match &found {
+            Ok(found) => Ok(resolve(found).unwrap_or_default()),
+            Err(e) => Err(e.clone()),
+        };
+        let mut cache = self.cache.members.borrow_mut();
+        if cache.len() >= SMART_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(text.clone(), found);
+        result
+    }
+
+    /// 按搜索框的规则求满足搜索条件的条目位置和ID
+    fn evaluate(&self, text: &str) -> Found {
+        let mut query = query::parse(text)?;
+        query.resolve_tag_aliases(self.aliases);
+        let now = Utc::now();
+        let candidates = query
+            .candidates(self.search_index)
+            .unwrap_or_else(|| (0..self.entries.len()).collect());
+        Ok(candidates
+            .into_iter()
+            .filter_map(|i| {
+                let entry = self.entries.get(i)?;
+                if entry.entry_type == EntryType::Collection {
+                    return None;
+                }
+                // 索引过期时（条目刚被修改、尚未同步）临时建立
+                let fresh;
+                let index = match self.search_index.get(i, entry) {
+                    Some(index) => index,
+                    None => {
+                        fresh = EntryIndex::new(entry);
+                        &fresh
+                    }
+                };
+                query.evaluate(entry, index, self.content, now)?;
+                Some((i, entry.id.clone()))
+            })
+            .collect())
+    }
+}
+
+/// 普通集合的子集合ID；智能集合的成员不含集合，不会形成循环
+fn child_collections<'a>(
+    entries: &'a [FileEntry],
+    collection: &'a FileEntry,
+) -> impl Iterator<Item = &'a FileEntry> + 'a {
+    collection
+        .child_entries
+        .iter()
+        .filter(|_| !collection.is_smart_collection())
+        .filter_map(|id| find(entries, id))
+        .filter(|e| e.entry_type == EntryType::Collection)
+}
+
+/// 从 `from` 出发沿子集合能否到达 `target`（包括 `from` 本身）
+pub fn reaches(entries: &[FileEntry], from: &str, target: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(id) = stack.pop() {
+        if id == target {
+            return true;
+        }
+        if !visited.insert(id) {
+            continue;
+        }
+        if let Some(collection) = find(entries, id) {
+            stack.extend(child_collections(entries, collection).map(|c| c.id.as_str()));
+        }
+    }
+    false
+}
+
+/// 把 `child` 加入集合 `collection` 后是否仍然没有循环
+pub fn can_contain(entries: &[FileEntry], collection: &str, child: &str) -> bool {
+    !reaches(entries, child, collection)
+}
+
+/// 直接或间接包含 `id` 的所有集合，连同 `id` 本身；这些集合都不能成为 `id` 的成员
+pub fn ancestors(entries: &[FileEntry], id: &str) -> HashSet<String> {
+    let mut parents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for entry in entries
+        .iter()
+        .filter(|e| e.entry_type == EntryType::Collection && !e.is_smart_collection())
+    {
+        for child in &entry.child_entries {
+            parents.entry(child).or_default().push(&entry.id);
+        }
+    }
+
+    let mut result = HashSet::from([id.to_string()]);
+    let mut stack = vec![id];
+    while let Some(current) = stack.pop() {
+        for &parent in parents.get(current).into_iter().flatten() {
+            if result.insert(parent.to_string()) {
+                stack.push(parent);
+            }
+        }
+    }
+    result
+}
+
+/// 移除形成循环的子集合引用（例如旧数据或同步合并带来的），返回移除的引用数
+pub fn break_cycles(entries: &mut [FileEntry]) -> usize {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        entries: &[FileEntry],
+        positions: &HashMap<&str, usize>,
+        index: usize,
+        states: &mut HashMap<usize, State>,
+        back_edges: &mut Vec<(usize, String)>,
+    ) {
+        states.insert(index, State::Visiting);
+        for child in child_collections(entries, &entries[index]) {
+            let child_index = positions[child.id.as_str()];
+            match states.get(&child_index) {
+                Some(State::Visiting) => back_edges.push((index, child.id.clone())),
+                Some(State::Done) => {}
+                None => visit(entries, positions, child_index, states, back_edges),
+            }
+        }
+        states.insert(index, State::Done);
+    }
+
+    let back_edges = {
+        let positions: HashMap<&str, usize> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.id.as_str(), i))
+            .collect();
+        let mut states = HashMap::new();
+        let mut back_edges = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if entry.entry_type == EntryType::Collection && !states.contains_key(&index) {
+                visit(entries, &positions, index, &mut states, &mut back_edges);
+            }
+        }
+        back_edges
+    };
+
+    for (index, child_id) in &back_edges {
+        entries[*index].child_entries.retain(|id| id != child_id);
+    }
+    back_edges.len()
+}
+
+/// 递归展开后的集合内容
+#[derive(Default)]
+pub struct Expanded<'a> {
+    /// 按顺序排列、不重复的非集合成员
+    pub items: Vec<&'a FileEntry>,
+    /// 途经的下级集合
+    pub collections: Vec<&'a FileEntry>,
+    /// 是否因超过层数限制而省略了更深的集合
+    pub truncated: bool,
+}
+
+/// 递归展开集合，最多进入 `max_depth` 层下级集合；同一条目只出现一次，循环引用被忽略
+pub fn expand<'a>(members: &Members<'a>, collection: &FileEntry, max_depth: usize) -> Expanded<'a> {
+    fn walk<'a>(
+        members: &Members<'a>,
+        collection: &FileEntry,
+        depth: usize,
+        max_depth: usize,
+        seen: &mut HashSet<String>,
+        result: &mut Expanded<'a>,
+    ) {
+        for member in members.of(collection).unwrap_or_default() {
+            if !seen.insert(member.id.clone()) {
+                continue;
+            }
+            if member.entry_type != EntryType::Collection {
+                result.items.push(member);
+            } else if depth < max_depth {
+                result.collections.push(member);
+                walk(members, member, depth + 1, max_depth, seen, result);
+            } else {
+                result.truncated = true;
+            }
+        }
+    }
+
+    let mut result = Expanded::default();
+    let mut seen = HashSet::from([collection.id.clone()]);
+    walk(members, collection, 0, max_depth, &mut seen, &mut result);
+    result
+}
+
+/// 打开集合时要启动的一个条目
+pub struct LaunchItem<'a> {
+    pub entry: &'a FileEntry,
+    /// 启动前等待的时间
+    pub delay: Duration,
+}
+
+/// 打开集合时要启动的条目及途经的下级集合。停用的子项目被跳过，下级集合按各自的
+/// 子项目选项展开（最多 `MAX_DEPTH` 层，“只打开第一个”的下级集合只取第一项），
+/// 同一条目只启动一次；整体按顶层集合的打开方式启动
+pub fn launch_items<'a>(
+    members: &Members<'a>,
+    collection: &FileEntry,
+) -> (Vec<LaunchItem<'a>>, Vec<&'a FileEntry>) {
+    fn walk<'a>(
+        members: &Members<'a>,
+        collection: &FileEntry,
+        depth: usize,
+        seen: &mut HashSet<String>,
+        items: &mut Vec<LaunchItem<'a>>,
+        nested: &mut Vec<&'a FileEntry>,
+    ) {
+        let start = items.len();
+        for member in members.of(collection).unwrap_or_default() {
+            let options = collection.child_options(&member.id);
+            if !options.enabled || !seen.insert(member.id.clone()) {
+                continue;
+            }
+            let delay = Duration::from_millis(options.delay_ms);
+            if member.entry_type != EntryType::Collection {
+                items.push(LaunchItem {
+                    entry: member,
+                    delay,
+                });
+            } else if depth < MAX_DEPTH {
+                nested.push(member);
+                let first = items.len();
+                walk(members, member, depth + 1, seen, items, nested);
+                // 下级集合的延迟加在它的第一项上
+                if let Some(item) = items.get_mut(first) {
+                    item.delay = item.delay.saturating_add(delay);
+                }
+            }
+            if collection.launch_mode == LaunchMode::FirstOnly && items.len() > start {
+                break;
+            }
+        }
+    }
+
+    let mut items = Vec::new();
+    let mut nested = Vec::new();
+    let mut seen = HashSet::from([collection.id.clone()]);
+    walk(members, collection, 0, &mut seen, &mut items, &mut nested);
+    (items, nested)
+}
+
+/// 调整集合中子项目的顺序：把第 `from` 项移到第 `to` 项之前（`to` 为长度时移到末尾）
+pub fn move_child(collection: &mut FileEntry, from: usize, to: usize) -> bool {
+    let len = collection.child_entries.len();
+    if from >= len || to > len || to == from || to == from + 1 {
+        return false;
+    }
+    let child = collection.child_entries.remove(from);
+    let to = if to > from { to - 1 } else { to };
+    collection.child_entries.insert(to, child);
+    collection.touch();
+    true
+}
+
+/// 展平集合后的成员ID：下级集合换成它们递归包含的条目
+pub fn flattened(members: &Members, collection: &FileEntry) -> Result<Vec<String>, String> {
+    if collection.is_smart_collection() {
+        return Err("智能集合的成员由搜索条件决定，不能展平".to_string());
+    }
+    let expanded = expand(members, collection, MAX_DEPTH);
+    if expanded.collections.is_empty() {
+        return Err("集合中没有下级集合".to_string());
+    }
+    Ok(expanded.items.iter().map(|e| e.id.clone()).collect())
+}
+
+/// 把集合的成员换成 `flattened` 的结果，返回展平后的成员数
+pub fn apply_flattened(collection: &mut FileEntry, items: Vec<String>) -> usize {
+    collection.child_entries = items;
+    collection.prune_child_options();
+    collection.touch();
+    collection.child_entries.len()
+}
+
+/// 把 `parent` 中的下级集合 `child_id` 移到顶层集合 `root` 的直接成员中
+pub fn extract_to_top(
+    entries: &mut [FileEntry],
+    root: usize,
+    parent: usize,
+    child_id: &str,
+) -> Result<(), String> {
+    if root == parent {
+        return Err("已经在顶层".to_string());
+    }
+    let root_id = entries.get(root).ok_or("集合不存在")?.id.clone();
+    if !entries
+        .get(parent)
+        .is_some_and(|p| p.child_entries.iter().any(|id| id == child_id))
+    {
+        return Err("集合中没有该项目".to_string());
+    }
+    if !can_contain(entries, &root_id, child_id) {
+        return Err("移动后会形成循环引用".to_string());
+    }
+
+    let options = entries[parent].child_options(child_id);
+    entries[parent].child_entries.retain(|id| id != child_id);
+    entries[parent].prune_child_options();
+    entries[parent].touch();
+    let root = &mut entries[root];
+    if !root.child_entries.iter().any(|id| id == child_id) {
+        root.child_entries.push(child_id.to_string());
+        root.set_child_options(child_id, options);
+    }
+    root.touch();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::file;
+
+    fn collection(name: &str, children: &[&FileEntry]) -> FileEntry {
+        FileEntry::new_collection(
+            name.to_string(),
+            None,
+            None,
+            Vec::new(),
+            children.iter().map(|e| e.id.clone()).collect(),
+        )
+    }
+
+    fn names(items: &[&FileEntry]) -> Vec<String> {
+        items.iter().map(|e| e.name.clone()).collect()
+    }
+
+    /// 用新建的搜索索引求成员，没有标签别名和内容索引
+    fn with_members<R>(entries: &[FileEntry], f: impl FnOnce(&Members) -> R) -> R {
+        let search_index = SearchIndex::new(entries);
+        let cache = SmartCache::default();
+        f(&Members {
+            entries,
+            search_index: &search_index,
+            aliases: &BTreeMap::new(),
+            content: None,
+            cache: &cache,
+        })
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let a = file("a");
+        let inner = collection("inner", &[&a]);
+        let outer = collection("outer", &[&inner]);
+        let entries = vec![a, inner, outer];
+
+        assert!(can_contain(&entries, &entries[2].id, &entries[0].id));
+        // 集合不能包含自己，也不能包含自己的上级
+        assert!(!can_contain(&entries, &entries[2].id, &entries[2].id));
+        assert!(!can_contain(&entries, &entries[1].id, &entries[2].id));
+
+        let ancestors = ancestors(&entries, &entries[0].id);
+        assert_eq!(ancestors.len(), 3);
+    }
+
+    #[test]
+    fn test_break_cycles() {
+        let a = file("a");
+        let mut first = collection("first", &[&a]);
+        let second = collection("second", &[&first]);
+        first.child_entries.push(second.id.clone());
+        let mut entries = vec![a, first, second];
+
+        // 先遍历到的集合保留引用，指回它的引用被移除
+        assert_eq!(break_cycles(&mut entries), 1);
+        assert_eq!(
+            entries[1].child_entries,
+            vec![entries[0].id.clone(), entries[2].id.clone()]
+        );
+        assert!(entries[2].child_entries.is_empty());
+        assert_eq!(break_cycles(&mut entries), 0);
+    }
+
+    #[test]
+    fn test_expand_with_depth_limit() {
+        let (a, b, c) = (file("a"), file("b"), file("c"));
+        let deepest = collection("deepest", &[&c]);
+        let inner = collection("inner", &[&b, &a, &deepest]);
+        let outer = collection("outer", &[&a, &inner]);
+        let entries = vec![a, b, c, deepest, inner, outer];
+
+        with_members(&entries, |members| {
+            let expanded = expand(members, &entries[5], MAX_DEPTH);
+            assert_eq!(names(&expanded.items), vec!["a", "b", "c"]);
+            assert_eq!(names(&expanded.collections), vec!["inner", "deepest"]);
+            assert!(!expanded.truncated);
+
+            let shallow = expand(members, &entries[5], 1);
+            assert_eq!(names(&shallow.items), vec!["a", "b"]);
+            assert!(shallow.truncated);
+        });
+    }
+
+    #[test]
+    fn test_flatten_and_extract() {
+        let (a, b) = (file("a"), file("b"));
+        let deepest = collection("deepest", &[&b]);
+        let inner = collection("inner", &[&a, &deepest]);
+        let outer = collection("outer", &[&inner]);
+        let mut entries = vec![a, b, deepest, inner, outer];
+
+        let (deepest_id, inner_id) = (entries[2].id.clone(), entries[3].id.clone());
+        extract_to_top(&mut entries, 4, 3, &deepest_id).unwrap();
+        assert_eq!(entries[3].child_entries, vec![entries[0].id.clone()]);
+        assert_eq!(entries[4].child_entries, vec![inner_id.clone(), deepest_id]);
+        assert!(extract_to_top(&mut entries, 4, 4, &inner_id).is_err());
+
+        let items = with_members(&entries, |members| flattened(members, &entries[4])).unwrap();
+        assert_eq!(apply_flattened(&mut entries[4], items), 2);
+        assert_eq!(
+            entries[4].child_entries,
+            vec![entries[0].id.clone(), entries[1].id.clone()]
+        );
+        assert!(with_members(&entries, |members| flattened(members, &entries[4])).is_err());
+    }
+
+    #[test]
+    fn test_launch_items() {
+        use crate::file_entry::ChildOptions;
+
+        let (a, b, c, d) = (file("a"), file("b"), file("c"), file("d"));
+        let mut inner = collection("inner", &[&c, &d]);
+        inner.launch_mode = LaunchMode::FirstOnly;
+        let mut outer = collection("outer", &[&a, &b, &inner, &a]);
+        let disabled = ChildOptions {
+            enabled: false,
+            ..ChildOptions::default()
+        };
+        outer.set_child_options(&b.id, disabled);
+        outer.set_child_options(
+            &inner.id,
+            ChildOptions {
+                delay_ms: 1000,
+                ..ChildOptions::default()
+            },
+        );
+        let entries = vec![a, b, c, d, inner, outer];
+
+        with_members(&entries, |members| {
+            let (items, nested) = launch_items(members, &entries[5]);
+            let launched: Vec<&FileEntry> = items.iter().map(|item| item.entry).collect();
+            // b 已停用，inner 只打开第一项，重复的 a 只打开一次
+            assert_eq!(names(&launched), vec!["a", "c"]);
+            assert_eq!(items[1].delay, Duration::from_millis(1050));
+            assert_eq!(names(&nested), vec!["inner"]);
+
+            let mut outer = entries[5].clone();
+            outer.launch_mode = LaunchMode::FirstOnly;
+            assert_eq!(launch_items(members, &outer).0.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_smart_collection_members() {
+        let mut entries = vec![file("a"), file("b")];
+        entries[0].tags = vec!["#work".to_string()];
+        entries[1].entry_type = EntryType::Directory;
+        let smart = FileEntry::new_smart_collection(
+            "Work".to_string(),
+            None,
+            None,
+            vec!["#work".to_string()],
+            "#job OR type:dir".to_string(),
+        );
+        entries.push(smart.clone());
+
+        let mut search_index = SearchIndex::new(&entries);
+        let aliases = BTreeMap::from([("#job".to_string(), "#work".to_string())]);
+        let cache = SmartCache::default();
+        let members_of = |entries: &[FileEntry], search_index: &SearchIndex, smart: &FileEntry| {
+            let members = Members {
+                entries,
+                search_index,
+                aliases: &aliases,
+                content: None,
+                cache: &cache,
+            };
+            members.of(smart).map(|found| names(&found))
+        };
+
+        // 与搜索框一样使用标签别名；集合本身不算在内
+        assert_eq!(
+            members_of(&entries, &search_index, &smart).unwrap(),
+            vec!["a", "b"]
+        );
+
+        // 条目变化后清空缓存即按新的条目计算
+        entries[1].entry_type = EntryType::File;
+        search_index.sync(&entries);
+        cache.clear();
+        assert_eq!(
+            members_of(&entries, &search_index, &smart).unwrap(),
+            vec!["a"]
+        );
+
+        // 位置上的条目对不上时不使用缓存
+        entries.swap(0, 1);
+        search_index.sync(&entries);
+        assert_eq!(
+            members_of(&entries, &search_index, &smart).unwrap(),
+            vec!["a"]
+        );
+
+        let mut broken = smart.clone();
+        broken.smart_query = Some("(#work".to_string());
+        assert!(members_of(&entries, &search_index, &broken).is_err());
+    }
+
+    #[test]
+    fn test_move_child() {
+        let (a, b, c) = (file("a"), file("b"), file("c"));
+        let mut outer = collection("outer", &[&a, &b, &c]);
+
+        assert!(move_child(&mut outer, 0, 3));
+        assert_eq!(
+            outer.child_entries,
+            vec![b.id.clone(), c.id.clone(), a.id.clone()]
+        );
+        assert!(move_child(&mut outer, 2, 0));
+        assert_eq!(outer.child_entries, vec![a.id, b.id, c.id]);
+        // 移到原位置不算修改
+        assert!(!move_child(&mut outer, 1, 2));
+        assert!(!move_child(&mut outer, 3, 0));
+    }
+}
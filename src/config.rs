@@ -1,13 +1,82 @@
-use crate::file_entry::FileEntry;
+use crate::atomic_file::{self, BACKUP_GENERATIONS};
+use crate::auto_tag::AutoTagRule;
+use crate::crypto::{self, EncryptionKey};
+use crate::file_entry::{CommandSpec, FileEntry};
+use crate::frecency;
+use crate::listing::SortOrder;
+use crate::migration::{self, CURRENT_SCHEMA_VERSION};
+use crate::paths;
+use crate::storage::StorageBackend;
+use crate::tags::TagRegistry;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct AppConfig {
     pub theme_mode: String,
     pub data_file_path: Option<String>, // 用户数据文件路径
     pub compact_mode: bool, // 紧凑模式
+    #[serde(default)]
+    pub storage_backend: StorageBackend, // 数据存储后端
+    #[serde(default)]
+    pub libraries: Vec<LibraryProfile>, // 命名资料库
+    #[serde(default)]
+    pub active_library: Option<String>, // 当前资料库名称，None 表示默认资料库
+    #[serde(default)]
+    pub persist_history: bool, // 把撤销历史保存到数据文件旁，重启后仍可撤销
+    #[serde(default)]
+    pub sort_order: SortOrder, // 条目列表的排序方式
+    #[serde(default = "default_half_life_days")]
+    pub frecency_half_life_days: f64, // 热度排序中打开记录的半衰期（天）
+    #[serde(default = "default_auto_lock_minutes")]
+    pub auto_lock_minutes: u32, // 加密数据空闲多少分钟后自动锁定，0 表示不自动锁定
+    #[serde(default)]
+    pub content_indexing: bool, // 在后台索引文件内容，可用 content: 搜索
+    /// 本机确认过的命令：条目ID → 确认时的命令。导入或同步得到的命令条目
+    /// 不在这里或已被改动时，运行前需要确认
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub trusted_commands: BTreeMap<String, CommandSpec>,
+    /// 当前版本不认识的配置项，保存时原样写回
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 命名资料库：拥有独立的数据文件，并可覆盖主题和显示模式
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LibraryProfile {
+    pub name: String,
+    pub data_file_path: Option<String>,
+    #[serde(default)]
+    pub storage_backend: StorageBackend,
+    #[serde(default)]
+    pub theme_mode: Option<String>, // 为空时使用全局主题
+    #[serde(default)]
+    pub compact_mode: Option<bool>, // 为空时使用全局显示模式
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl LibraryProfile {
+    pub fn new(name: String, data_file_path: Option<String>) -> Self {
+        Self {
+            name,
+            data_file_path,
+            storage_backend: StorageBackend::Json,
+            theme_mode: None,
+            compact_mode: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+fn default_half_life_days() -> f64 {
+    frecency::DEFAULT_HALF_LIFE_DAYS
+}
+
+fn default_auto_lock_minutes() -> u32 {
+    10
 }
 
 impl Default for AppConfig {
@@ -16,6 +85,138 @@ impl Default for AppConfig {
             theme_mode: "Light".to_string(),
             data_file_path: None,
             compact_mode: false,
+            storage_backend: StorageBackend::Json,
+            libraries: Vec::new(),
+            active_library: None,
+            persist_history: false,
+            sort_order: SortOrder::default(),
+            frecency_half_life_days: default_half_life_days(),
+            auto_lock_minutes: default_auto_lock_minutes(),
+            content_indexing: false,
+            trusted_commands: BTreeMap::new(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// 默认资料库在界面上显示的名称
+    pub const DEFAULT_LIBRARY_NAME: &'static str = "默认";
+
+    /// 条目是否可以直接打开：不是命令，或命令在本机确认过且之后没有改动
+    pub fn is_trusted_command(&self, entry: &FileEntry) -> bool {
+        entry
+            .command
+            .as_ref()
+            .is_none_or(|command| self.trusted_commands.get(&entry.id) == Some(command))
+    }
+
+    /// 记录本机添加、编辑或确认运行过的命令
+    pub fn trust_command(&mut self, entry: &FileEntry) {
+        if let Some(command) = &entry.command {
+            self.trusted_commands.insert(entry.id.clone(), command.clone());
+        }
+    }
+
+    pub fn find_library(&self, name: &str) -> Option<&LibraryProfile> {
+        self.libraries.iter().find(|lib| lib.name == name)
+    }
+
+    /// 已使用 `path` 作为数据文件的资料库名称（含默认资料库）。默认的数据文件名由资料库
+    /// 名称转换而来，不同的名称可能得到同一个文件，创建资料库前需要检查
+    pub fn library_using_path(&self, path: &Path) -> Option<&str> {
+        let data_path = |custom: Option<&String>, default: PathBuf, backend| {
+            match custom.filter(|p| !p.is_empty()) {
+                Some(custom) => PathBuf::from(custom),
+                None if backend == StorageBackend::Sqlite => default.with_extension("db"),
+                None => default,
+            }
+        };
+        if data_path(
+            self.data_file_path.as_ref(),
+            paths::default_data_path(),
+            self.storage_backend,
+        ) == path
+        {
+            return Some(Self::DEFAULT_LIBRARY_NAME);
+        }
+        self.libraries
+            .iter()
+            .find(|lib| {
+                data_path(
+                    lib.data_file_path.as_ref(),
+                    DataManager::library_data_path(&lib.name),
+                    lib.storage_backend,
+                ) == path
+            })
+            .map(|lib| lib.name.as_str())
+    }
+
+    /// 当前激活的命名资料库，未选择或已被删除时返回 None（即默认资料库）
+    pub fn active_library(&self) -> Option<&LibraryProfile> {
+        self.active_library
+            .as_deref()
+            .and_then(|name| self.find_library(name))
+    }
+
+    fn active_library_mut(&mut self) -> Option<&mut LibraryProfile> {
+        let name = self.active_library.clone()?;
+        self.libraries.iter_mut().find(|lib| lib.name == name)
+    }
+
+    pub fn active_library_name(&self) -> &str {
+        self.active_library()
+            .map(|lib| lib.name.as_str())
+            .unwrap_or(Self::DEFAULT_LIBRARY_NAME)
+    }
+
+    /// 当前资料库的数据文件路径和存储后端
+    pub fn active_data_location(&self) -> (Option<&String>, StorageBackend) {
+        match self.active_library() {
+            Some(lib) => (lib.data_file_path.as_ref(), lib.storage_backend),
+            None => (self.data_file_path.as_ref(), self.storage_backend),
+        }
+    }
+
+    /// 修改当前资料库的数据文件路径和存储后端
+    pub fn set_active_data_location(&mut self, path: Option<String>, backend: StorageBackend) {
+        match self.active_library_mut() {
+            Some(lib) => {
+                lib.data_file_path = path;
+                lib.storage_backend = backend;
+            }
+            None => {
+                self.data_file_path = path;
+                self.storage_backend = backend;
+            }
+        }
+    }
+
+    /// 当前资料库生效的主题
+    pub fn active_theme_mode(&self) -> &str {
+        self.active_library()
+            .and_then(|lib| lib.theme_mode.as_deref())
+            .unwrap_or(&self.theme_mode)
+    }
+
+    /// 当前资料库生效的显示模式
+    pub fn active_compact_mode(&self) -> Option<bool> {
+        self.active_library().and_then(|lib| lib.compact_mode)
+    }
+
+    /// 保存主题设置：当前资料库有自己的主题时写入资料库，否则写入全局配置
+    pub fn set_active_theme_mode(&mut self, theme_mode: String) {
+        match self.active_library_mut() {
+            Some(lib) if lib.theme_mode.is_some() => lib.theme_mode = Some(theme_mode),
+            _ => self.theme_mode = theme_mode,
+        }
+    }
+
+    /// 保存显示模式：规则同 `set_active_theme_mode`
+    pub fn set_active_compact_mode(&mut self, compact_mode: bool) {
+        match self.active_library_mut() {
+            Some(lib) if lib.compact_mode.is_some() => lib.compact_mode = Some(compact_mode),
+            _ => self.compact_mode = compact_mode,
         }
     }
 }
@@ -23,44 +224,85 @@ impl Default for AppConfig {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct UserData {
     pub entries: Vec<FileEntry>,
-    pub version: String,
+    pub version: String, // 写入该文件的程序版本
+    #[serde(default)]
+    pub schema_version: u32, // 数据结构版本，用于迁移
+    /// 标签别名 → 标准标签，输入或搜索别名时按标准标签处理
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tag_aliases: BTreeMap<String, String>,
+    /// 标签的颜色、图标、说明等显示信息
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tag_registry: TagRegistry,
+    /// 自动标签规则，按顺序应用到新添加的条目
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auto_tag_rules: Vec<AutoTagRule>,
+    /// 当前版本不认识的字段，保存时原样写回，避免混用不同版本时互相覆盖数据
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Default for UserData {
     fn default() -> Self {
         Self {
             entries: Vec::new(),
-            version: "0.2.3".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: CURRENT_SCHEMA_VERSION,
+            tag_aliases: BTreeMap::new(),
+            tag_registry: TagRegistry::new(),
+            auto_tag_rules: Vec::new(),
+            extra: Map::new(),
         }
     }
 }
 
+impl UserData {
+    /// 创建带有当前版本标记的数据
+    pub fn with_entries(entries: Vec<FileEntry>) -> Self {
+        Self {
+            entries,
+            ..Self::default()
+        }
+    }
+}
+
+/// 用于序列化的借用视图，字段与 `UserData` 保持一致
+#[derive(Serialize)]
+struct UserDataRef<'a> {
+    entries: &'a [FileEntry],
+    version: &'a str,
+    schema_version: u32,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    tag_aliases: &'a BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    tag_registry: &'a TagRegistry,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    auto_tag_rules: &'a [AutoTagRule],
+    #[serde(flatten)]
+    extra: &'a Map<String, Value>,
+}
+
+/// 解析用户数据，旧版本数据会经过迁移管道升级到当前结构版本
+pub fn parse_user_data(content: &str) -> Result<UserData, String> {
+    let value = serde_json::from_str::<Value>(content)
+        .map_err(|e| format!("解析数据失败: {}", e))?;
+    let value = migration::migrate_to_current(value)?;
+    serde_json::from_value::<UserData>(value).map_err(|e| format!("解析数据失败: {}", e))
+}
+
 pub struct ConfigManager {
     config_path: PathBuf,
 }
 
 impl Default for ConfigManager {
     fn default() -> Self {
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or_else(|| PathBuf::from("."));
-        
-        Self {
-            config_path: exe_dir.join("file_manager_config.json"),
-        }
+        Self::new()
     }
 }
 
 impl ConfigManager {
     pub fn new() -> Self {
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or_else(|| PathBuf::from("."));
-        
         Self {
-            config_path: exe_dir.join("file_manager_config.json"),
+            config_path: paths::default_config_path(),
         }
     }
 
@@ -75,121 +317,431 @@ impl ConfigManager {
     }
 
     pub fn save_config(&self, config: &AppConfig) -> Result<(), String> {
-        // 确保目录存在
-        if let Some(parent) = self.config_path.parent() {
-            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
-        }
-
         match serde_json::to_string_pretty(config) {
-            Ok(json) => {
-                fs::write(&self.config_path, json).map_err(|e| format!("保存配置失败: {}", e))
-            }
+            Ok(json) => atomic_file::save_with_backups(
+                &self.config_path,
+                json.as_bytes(),
+                BACKUP_GENERATIONS,
+            )
+            .map_err(|e| format!("保存配置失败: {}", e)),
             Err(e) => Err(format!("序列化失败: {}", e)),
         }
     }
 
-    pub fn load_config(&self) -> Result<AppConfig, String> {
-        match fs::read_to_string(&self.config_path) {
-            Ok(content) => {
-                serde_json::from_str::<AppConfig>(&content)
+    /// 读取配置，主文件损坏时从备份恢复；文件不存在时返回默认配置
+    pub fn load_config(&self) -> Result<LoadedConfig, String> {
+        let loaded =
+            atomic_file::load_with_recovery(&self.config_path, BACKUP_GENERATIONS, |content| {
+                serde_json::from_str::<AppConfig>(content)
                     .map_err(|e: serde_json::Error| format!("解析配置失败: {}", e))
-            }
-            Err(_) => Ok(AppConfig::default()),
-        }
+            })?;
+
+        Ok(match loaded {
+            Some(recovered) => LoadedConfig {
+                config: recovered.value,
+                recovered_from: recovered.recovered_from,
+            },
+            None => LoadedConfig {
+                config: AppConfig::default(),
+                recovered_from: None,
+            },
+        })
     }
 }
 
 pub struct DataManager {
     data_path: PathBuf,
+    /// 解锁后的密钥，用于读取加密文件
+    encryption: Option<EncryptionKey>,
+    /// 保存时是否加密；取消加密后仍保留密钥以便读取尚未改写的加密文件
+    encrypt_on_save: bool,
 }
 
 impl Default for DataManager {
     fn default() -> Self {
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or_else(|| PathBuf::from("."));
-        
-        Self {
-            data_path: exe_dir.join("file_manager_data.json"),
-        }
+        Self::new()
     }
 }
 
 impl DataManager {
     pub fn new() -> Self {
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or_else(|| PathBuf::from("."));
-        
         Self {
-            data_path: exe_dir.join("file_manager_data.json"),
+            data_path: paths::default_data_path(),
+            encryption: None,
+            encrypt_on_save: false,
         }
     }
 
     pub fn new_with_path(path: PathBuf) -> Self {
         Self {
             data_path: path,
+            encryption: None,
+            encrypt_on_save: false,
         }
     }
 
+    /// 命名资料库默认的数据文件路径，与默认数据文件放在同一目录
+    pub fn library_data_path(name: &str) -> PathBuf {
+        let file_stem: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        paths::data_dir().join(format!("file_manager_data_{}.json", file_stem))
+    }
+
     pub fn get_data_path(&self) -> &PathBuf {
         &self.data_path
     }
 
-    pub fn save_data(&self, data: &UserData) -> Result<(), String> {
-        // 确保目录存在
-        if let Some(parent) = self.data_path.parent() {
-            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+    /// 保存用户数据，`meta.entries` 会被忽略，条目以 `entries` 为准（避免整体克隆条目列表）
+    pub fn save_data(&self, meta: &UserData, entries: &[FileEntry]) -> Result<(), String> {
+        let data = UserDataRef {
+            entries,
+            version: &meta.version,
+            schema_version: meta.schema_version,
+            tag_aliases: &meta.tag_aliases,
+            tag_registry: &meta.tag_registry,
+            auto_tag_rules: &meta.auto_tag_rules,
+            extra: &meta.extra,
+        };
+
+        if self.is_locked() {
+            return Err("数据文件已锁定，请先解锁".to_string());
         }
 
-        match serde_json::to_string_pretty(data) {
+        match serde_json::to_string_pretty(&data) {
             Ok(json) => {
-                fs::write(&self.data_path, json).map_err(|e| format!("保存数据失败: {}", e))
+                let content = match &self.encryption {
+                    Some(key) if self.encrypt_on_save => crypto::encrypt(&json, key)?,
+                    _ => json,
+                };
+                atomic_file::save_with_backups(&self.data_path, content.as_bytes(), BACKUP_GENERATIONS)
+                    .map_err(|e| format!("保存数据失败: {}", e))
             }
             Err(e) => Err(format!("序列化失败: {}", e)),
         }
     }
 
-    pub fn load_data(&self) -> Result<UserData, String> {
-        match fs::read_to_string(&self.data_path) {
-            Ok(content) => {
-                // 尝试新格式
-                serde_json::from_str::<UserData>(&content)
-                    .or_else(|_| {
-                        // 兼容旧格式：直接是entries数组或者包含entries的Config
-                        if let Ok(mut entries) = serde_json::from_str::<Vec<FileEntry>>(&content) {
-                            // 迁移旧数据格式
-                            for entry in &mut entries {
-                                *entry = entry.clone().migrate_from_old();
-                            }
-                            Ok(UserData {
-                                entries,
-                                version: "0.2.3".to_string(),
-                            })
-                        } else if let Ok(old_config) = serde_json::from_str::<serde_json::Value>(&content) {
-                            if let Some(entries_value) = old_config.get("entries") {
-                                let mut entries: Vec<FileEntry> = serde_json::from_value(entries_value.clone())
-                                    .unwrap_or_default();
-                                // 迁移旧数据格式
-                                for entry in &mut entries {
-                                    *entry = entry.clone().migrate_from_old();
-                                }
-                                Ok(UserData {
-                                    entries,
-                                    version: "0.2.3".to_string(),
-                                })
-                            } else {
-                                Ok(UserData::default())
-                            }
-                        } else {
-                            Ok(UserData::default())
-                        }
-                    })
-                    .map_err(|e: serde_json::Error| format!("解析数据失败: {}", e))
+    /// 磁盘上的数据文件是否为加密格式
+    fn file_is_encrypted(&self) -> bool {
+        std::fs::read_to_string(&self.data_path).is_ok_and(|content| crypto::is_encrypted(&content))
+    }
+
+    /// 保存时是否加密（已设置密码或已解锁）
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypt_on_save
+    }
+
+    /// 数据文件已加密但尚未解锁
+    pub fn is_locked(&self) -> bool {
+        self.encryption.is_none() && self.file_is_encrypted()
+    }
+
+    /// 验证密码并把密钥保存在内存中
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        let content = std::fs::read_to_string(&self.data_path)
+            .map_err(|e| format!("读取数据文件失败: {}", e))?;
+        let (_, key) = crypto::decrypt(&content, passphrase)?;
+        self.encryption = Some(key);
+        self.encrypt_on_save = true;
+        Ok(())
+    }
+
+    /// 从内存中清除密钥
+    pub fn lock(&mut self) {
+        self.encryption = None;
+        self.encrypt_on_save = false;
+    }
+
+    /// 设置新密码（None 表示取消加密），下次保存时生效
+    ///
+    /// 取消加密前必须已经解锁，否则无法读取原有数据。
+    pub fn set_passphrase(&mut self, passphrase: Option<&str>) -> Result<(), String> {
+        if self.is_locked() {
+            return Err("数据文件已锁定，请先解锁".to_string());
+        }
+        if let Some(passphrase) = passphrase {
+            self.encryption = Some(EncryptionKey::derive(passphrase)?);
+        }
+        self.encrypt_on_save = passphrase.is_some();
+        Ok(())
+    }
+
+    /// 解密（如果需要）并解析数据文件内容
+    fn parse_content(&self, content: &str) -> Result<UserData, String> {
+        if !crypto::is_encrypted(content) {
+            return parse_user_data(content);
+        }
+        let key = self
+            .encryption
+            .as_ref()
+            .ok_or_else(|| "数据文件已加密，请先解锁".to_string())?;
+        parse_user_data(&crypto::decrypt_with_key(content, key)?)
+    }
+
+    /// 加载用户数据，主文件损坏时自动从最新的有效备份恢复
+    pub fn load_data(&self) -> Result<LoadedData, String> {
+        // 由更新版本程序写入的文件不是损坏文件，直接拒绝而不是尝试从备份恢复
+        if let Ok(content) = std::fs::read_to_string(&self.data_path) {
+            let plaintext = if crypto::is_encrypted(&content) {
+                // 未解锁或密码已在其他地方修改时不能当作损坏文件处理
+                let key = self
+                    .encryption
+                    .as_ref()
+                    .ok_or_else(|| "数据文件已加密，请先解锁".to_string())?;
+                if !crypto::matches_key(&content, key) {
+                    return Err("数据文件的密码已在其他地方修改，请重新解锁".to_string());
+                }
+                crypto::decrypt_with_key(&content, key).ok()
+            } else {
+                Some(content)
+            };
+            if let Some(value) = plaintext.and_then(|p| serde_json::from_str::<Value>(&p).ok()) {
+                migration::check_supported(&value)?;
             }
-            Err(_) => Ok(UserData::default()),
         }
+
+        let loaded = atomic_file::load_with_recovery(&self.data_path, BACKUP_GENERATIONS, |content| {
+            self.parse_content(content)
+        })
+        .map_err(|e| format!("加载数据失败: {}", e))?;
+
+        Ok(match loaded {
+            Some(recovered) => LoadedData {
+                data: recovered.value,
+                recovered_from: recovered.recovered_from,
+            },
+            None => LoadedData {
+                data: UserData::default(),
+                recovered_from: None,
+            },
+        })
     }
-}
\ No newline at end of file
+}
+
+/// 配置加载结果
+pub struct LoadedConfig {
+    pub config: AppConfig,
+    /// 配置文件损坏时实际使用的备份文件
+    pub recovered_from: Option<PathBuf>,
+}
+
+/// 数据加载结果
+pub struct LoadedData {
+    pub data: UserData,
+    /// 主数据文件损坏时实际使用的备份文件
+    pub recovered_from: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auto_tag::RuleKind;
+    use crate::tags::TagInfo;
+
+    #[test]
+    fn test_user_data_preserves_unknown_fields() {
+        let json = format!(
+            r##"{{
+                "entries": [{{"path": "/a", "name": "A", "nickname": null, "description": null,
+                              "tags": [], "url": null, "id": "a", "rating": 5}}],
+                "version": "9.0.0",
+                "schema_version": {},
+                "tag_colors": {{"#work": "red"}}
+            }}"##,
+            CURRENT_SCHEMA_VERSION
+        );
+
+        let data = parse_user_data(&json).unwrap();
+        assert_eq!(data.extra["tag_colors"]["#work"], "red");
+        assert_eq!(data.entries[0].extra["rating"], 5);
+
+        let saved = serde_json::to_value(&data).unwrap();
+        assert_eq!(saved["tag_colors"]["#work"], "red");
+        assert_eq!(saved["entries"][0]["rating"], 5);
+    }
+
+    #[test]
+    fn test_tag_aliases_are_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = DataManager::new_with_path(dir.path().join("data.json"));
+        let mut meta = UserData::default();
+        meta.tag_aliases.insert("#wrok".to_string(), "#work".to_string());
+        meta.tag_registry.insert(
+            "#work".to_string(),
+            TagInfo {
+                color: Some([200, 40, 40]),
+                icon: Some("💼".to_string()),
+                ..TagInfo::default()
+            },
+        );
+        manager.save_data(&meta, &[]).unwrap();
+
+        let loaded = manager.load_data().unwrap().data;
+        assert_eq!(loaded.tag_aliases, meta.tag_aliases);
+        assert_eq!(loaded.tag_registry, meta.tag_registry);
+    }
+
+    #[test]
+    fn test_auto_tag_rules_are_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = DataManager::new_with_path(dir.path().join("data.json"));
+        let mut meta = UserData::default();
+        meta.auto_tag_rules.push(
+            AutoTagRule::new(RuleKind::Extension, "pdf", vec!["#文档".to_string()]).unwrap(),
+        );
+        manager.save_data(&meta, &[]).unwrap();
+
+        let loaded = manager.load_data().unwrap().data;
+        assert_eq!(loaded.auto_tag_rules, meta.auto_tag_rules);
+    }
+
+    #[test]
+    fn test_trusted_commands() {
+        let mut entry = FileEntry::new_command(
+            "build".to_string(),
+            CommandSpec {
+                program: "cargo".to_string(),
+                args: vec!["build".to_string()],
+                ..CommandSpec::default()
+            },
+            None,
+            None,
+            vec![],
+        );
+        let mut config = AppConfig::default();
+        assert!(!config.is_trusted_command(&entry));
+
+        config.trust_command(&entry);
+        assert!(config.is_trusted_command(&entry));
+
+        // 同步带来的改动需要重新确认
+        entry.command.as_mut().unwrap().program = "rm".to_string();
+        assert!(!config.is_trusted_command(&entry));
+
+        let file = FileEntry::new(PathBuf::from("/a"), "a".to_string(), None, vec![], false);
+        assert!(config.is_trusted_command(&file));
+    }
+
+    #[test]
+    fn test_config_recovered_from_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let manager = ConfigManager::new_with_path(path.clone());
+        assert!(manager.load_config().unwrap().recovered_from.is_none());
+
+        let config = AppConfig {
+            theme_mode: "Dark".to_string(),
+            ..AppConfig::default()
+        };
+        manager.save_config(&config).unwrap();
+        manager.save_config(&config).unwrap();
+        std::fs::write(&path, "{").unwrap();
+
+        // 恢复时把使用的备份告诉调用方，以便提示用户
+        let loaded = manager.load_config().unwrap();
+        assert_eq!(loaded.config.theme_mode, "Dark");
+        assert_eq!(
+            loaded.recovered_from,
+            Some(atomic_file::backup_path(&path, 1))
+        );
+    }
+
+    #[test]
+    fn test_app_config_preserves_unknown_fields() {
+        let json = r#"{"theme_mode": "Dark", "data_file_path": null, "compact_mode": true,
+                       "language": "en"}"#;
+
+        let config: AppConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.extra["language"], "en");
+
+        let saved = serde_json::to_value(&config).unwrap();
+        assert_eq!(saved["language"], "en");
+        assert_eq!(saved["theme_mode"], "Dark");
+    }
+
+    #[test]
+    fn test_active_library_routes_settings() {
+        let mut config = AppConfig::default();
+        let mut work = LibraryProfile::new("Work".to_string(), Some("/tmp/work.json".to_string()));
+        work.theme_mode = Some("Dark".to_string());
+        config.libraries.push(work);
+
+        // 默认资料库使用全局设置
+        assert_eq!(config.active_library_name(), AppConfig::DEFAULT_LIBRARY_NAME);
+        assert_eq!(config.active_data_location().0, None);
+
+        config.active_library = Some("Work".to_string());
+        assert_eq!(
+            config.active_data_location().0.map(String::as_str),
+            Some("/tmp/work.json")
+        );
+        assert_eq!(config.active_theme_mode(), "Dark");
+
+        // 资料库覆盖了主题，修改写入资料库；没有覆盖显示模式，修改写入全局
+        config.set_active_theme_mode("Light".to_string());
+        config.set_active_compact_mode(true);
+        assert_eq!(config.libraries[0].theme_mode.as_deref(), Some("Light"));
+        assert_eq!(config.theme_mode, "Light");
+        assert!(config.compact_mode);
+        assert_eq!(config.libraries[0].compact_mode, None);
+
+        // 资料库被删除后退回默认资料库
+        config.libraries.clear();
+        assert!(config.active_library().is_none());
+    }
+
+    #[test]
+    fn test_library_data_file_collision() {
+        let mut config = AppConfig::default();
+        config
+            .libraries
+            .push(LibraryProfile::new("Project X".to_string(), None));
+
+        // "Project X" 和 "Project_X" 的默认数据文件相同
+        let path = DataManager::library_data_path("Project_X");
+        assert_eq!(config.library_using_path(&path), Some("Project X"));
+        assert_eq!(
+            config.library_using_path(&paths::default_data_path()),
+            Some(AppConfig::DEFAULT_LIBRARY_NAME)
+        );
+        assert_eq!(
+            config.library_using_path(&DataManager::library_data_path("Other")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_encrypted_data_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+        let entries = vec![FileEntry::new(
+            PathBuf::from("/clients/acme"),
+            "acme".to_string(),
+            None,
+            vec!["#secret".to_string()],
+            true,
+        )];
+
+        let mut manager = DataManager::new_with_path(path.clone());
+        manager.set_passphrase(Some("pw")).unwrap();
+        manager.save_data(&UserData::default(), &entries).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("acme"));
+
+        // 新打开的实例需要先解锁，且不会把加密文件当作损坏文件处理
+        let mut reopened = DataManager::new_with_path(path.clone());
+        assert!(reopened.is_locked());
+        assert!(reopened.load_data().is_err());
+        assert!(reopened.save_data(&UserData::default(), &[]).is_err());
+        assert!(reopened.unlock("wrong").is_err());
+        reopened.unlock("pw").unwrap();
+        assert_eq!(reopened.load_data().unwrap().data.entries, entries);
+
+        // 取消加密后恢复明文保存
+        reopened.set_passphrase(None).unwrap();
+        reopened.save_data(&UserData::default(), &entries).unwrap();
+        assert!(!reopened.is_encrypted());
+        assert!(std::fs::read_to_string(&path).unwrap().contains("acme"));
+    }
+}
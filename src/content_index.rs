@@ -0,0 +1,463 @@
+//! 文件内容的全文索引（可选功能）
+//!
+//! 后台线程读取文件条目以及文件夹条目下的文本文件，搜索框中的 `content:` 条件在这里查找。
+//! 只索引常见文本格式且不超过大小限制的文件；再次扫描时按修改时间和大小跳过没有变化的文件。
+
+use crate::atomic_file;
+use crate::file_entry::{EntryType, FileEntry};
+use crate::sync::file_fingerprint;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+/// 超过这个大小的文件不索引
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// 每个文件夹条目最多索引的文件数
+pub const MAX_FILES_PER_DIRECTORY: usize = 2000;
+/// 文件夹条目向下查找的最大层数
+const MAX_DEPTH: usize = 8;
+/// 片段中匹配位置前后保留的字符数
+const SNIPPET_CONTEXT: usize = 30;
+
+/// 会被索引的文件扩展名（小写）
+pub const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "rst", "org", "tex", "csv", "tsv", "log", "json", "toml", "yaml",
+    "yml", "xml", "html", "htm", "ini", "cfg", "conf", "rs", "py", "js", "ts", "c", "h", "cpp",
+    "hpp", "java", "go", "sh", "sql",
+];
+
+/// 已索引的文件内容，按路径排序，便于找出某个文件夹下的所有文件
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ContentIndex {
+    files: BTreeMap<PathBuf, IndexedFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexedFile {
+    /// 读取时的文件指纹（修改时间 + 大小）
+    fingerprint: String,
+    text: String,
+    /// ASCII 转成小写后的内容，字节位置与 `text` 一致
+    #[serde(skip)]
+    folded: String,
+}
+
+impl IndexedFile {
+    fn new(fingerprint: String, text: String) -> Self {
+        let folded = text.to_ascii_lowercase();
+        Self {
+            fingerprint,
+            text,
+            folded,
+        }
+    }
+}
+
+/// 一次扫描的结果：新增或修改过的文件，以及已经不存在（或不再需要索引）的文件
+#[derive(Default)]
+pub struct IndexUpdate {
+    pub changed: Vec<(PathBuf, IndexedFile)>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl IndexUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// 内容匹配的片段，`highlight` 为匹配部分在 `text` 中的字节范围
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentSnippet {
+    pub path: PathBuf,
+    pub text: String,
+    pub highlight: Range<usize>,
+}
+
+impl ContentIndex {
+    /// 索引文件路径，例如 `data.json.content`
+    pub fn index_path(data_path: &Path) -> PathBuf {
+        let mut name = data_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".content");
+        data_path.with_file_name(name)
+    }
+
+    /// 读取保存的索引，文件不存在或无法解析时返回空索引
+    pub fn load(path: &Path) -> Self {
+        let mut index: Self = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        for file in index.files.values_mut() {
+            file.folded = file.text.to_ascii_lowercase();
+        }
+        index
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| format!("序列化内容索引失败: {}", e))?;
+        atomic_file::write_atomic(path, json.as_bytes())
+    }
+
+    /// 已索引的文件数
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// 每个已索引文件的指纹，交给后台扫描用于跳过没有变化的文件
+    pub fn fingerprints(&self) -> HashMap<PathBuf, String> {
+        self.files
+            .iter()
+            .map(|(path, file)| (path.clone(), file.fingerprint.clone()))
+            .collect()
+    }
+
+    pub fn apply(&mut self, update: IndexUpdate) {
+        for path in update.removed {
+            self.files.remove(&path);
+        }
+        self.files.extend(update.changed);
+    }
+
+    /// 在条目对应的文件（文件夹条目为其下的所有文件）中查找短语，不区分 ASCII 大小写，
+    /// 返回第一处匹配的片段
+    pub fn find(&self, entry: &FileEntry, phrase: &str) -> Option<ContentSnippet> {
+        let phrase = phrase.to_ascii_lowercase();
+        if phrase.is_empty() {
+            return None;
+        }
+        let candidates: Box<dyn Iterator<Item = (&PathBuf, &IndexedFile)>> = match entry.entry_type
+        {
+            EntryType::File => Box::new(self.files.get_key_value(&entry.path).into_iter()),
+            EntryType::Directory => Box::new(
+                self.files
+                    .range(entry.path.clone()..)
+                    .take_while(|(path, _)| path.starts_with(&entry.path)),
+            ),
+            EntryType::WebLink | EntryType::Collection | EntryType::Command => return None,
+        };
+
+        candidates.into_iter().find_map(|(path, file)| {
+            let start = file.folded.find(&phrase)?;
+            Some(snippet(path, &file.text, start..start + phrase.len()))
+        })
+    }
+}
+
+/// 截取匹配位置前后的一段文字，空白字符（包括换行）换成空格
+fn snippet(path: &Path, text: &str, matched: Range<usize>) -> ContentSnippet {
+    let flatten = |s: &str| {
+        s.chars()
+            .map(|c| if c.is_whitespace() { ' ' } else { c })
+            .collect::<String>()
+    };
+    let before_start = text[..matched.start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let after_end = text[matched.end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(text.len(), |(i, _)| matched.end + i);
+
+    let mut snippet_text = String::new();
+    if before_start > 0 {
+        snippet_text.push('…');
+    }
+    snippet_text.push_str(&flatten(&text[before_start..matched.start]));
+    let highlight_start = snippet_text.len();
+    snippet_text.push_str(&flatten(&text[matched.clone()]));
+    let highlight = highlight_start..snippet_text.len();
+    snippet_text.push_str(&flatten(&text[matched.end..after_end]));
+    if after_end < text.len() {
+        snippet_text.push('…');
+    }
+
+    ContentSnippet {
+        path: path.to_path_buf(),
+        text: snippet_text,
+        highlight,
+    }
+}
+
+/// 需要索引的根路径：文件和文件夹条目的路径
+pub fn roots(entries: &[FileEntry]) -> Vec<PathBuf> {
+    entries
+        .iter()
+        .filter(|e| matches!(e.entry_type, EntryType::File | EntryType::Directory))
+        .map(|e| e.path.clone())
+        .collect()
+}
+
+/// 扫描根路径下需要索引的文件：指纹与 `known` 相同的文件不再读取，
+/// `known` 中没有被扫描到的文件记为已删除
+pub fn scan(roots: &[PathBuf], known: &HashMap<PathBuf, String>) -> IndexUpdate {
+    let mut update = IndexUpdate::default();
+    let mut seen = HashSet::new();
+
+    for root in roots {
+        let mut files = Vec::new();
+        if root.is_dir() {
+            collect_files(root, 0, &mut files);
+        } else if is_text_file(root) {
+            files.push(root.clone());
+        }
+
+        for path in files {
+            if seen.contains(&path) {
+                continue;
+            }
+            let Some(fingerprint) = file_fingerprint(&path) else {
+                continue;
+            };
+            if known.get(&path) != Some(&fingerprint) {
+                let Some(text) = read_text(&path) else {
+                    continue;
+                };
+                update
+                    .changed
+                    .push((path.clone(), IndexedFile::new(fingerprint, text)));
+            }
+            seen.insert(path);
+        }
+    }
+
+    update.removed = known
+        .keys()
+        .filter(|path| !seen.contains(*path))
+        .cloned()
+        .collect();
+    update
+}
+
+/// 递归收集文件夹下的文本文件，跳过隐藏文件夹，不跟随符号链接
+fn collect_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    if depth > MAX_DEPTH || files.len() >= MAX_FILES_PER_DIRECTORY {
+        return;
+    }
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut children: Vec<_> = read_dir.filter_map(|e| e.ok()).collect();
+    children.sort_by_key(|e| e.file_name());
+
+    for child in children {
+        if files.len() >= MAX_FILES_PER_DIRECTORY {
+            return;
+        }
+        let Ok(file_type) = child.file_type() else {
+            continue;
+        };
+        let path = child.path();
+        if file_type.is_dir() {
+            if !child.file_name().to_string_lossy().starts_with('.') {
+                collect_files(&path, depth + 1, files);
+            }
+        } else if file_type.is_file() && is_text_file(&path) {
+            files.push(path);
+        }
+    }
+}
+
+fn is_text_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// 读取文本内容；超过大小限制或看起来是二进制文件时返回 None
+fn read_text(path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if metadata.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// 在后台线程中扫描并更新索引，界面每帧调用 `poll` 取回更新后的索引；
+/// 丢弃时取消正在进行的扫描，被取消的扫描不再写入索引文件
+#[derive(Default)]
+pub struct ContentIndexer {
+    receiver: Option<Receiver<Option<ContentIndex>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ContentIndexer {
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// 开始一次后台扫描，已有扫描在进行时不做任何事。
+    /// 有变化时在后台线程中生成新的索引，并在 `save_to` 不为空时写入该文件
+    pub fn start(
+        &mut self,
+        roots: Vec<PathBuf>,
+        index: Arc<ContentIndex>,
+        save_to: Option<PathBuf>,
+    ) {
+        if self.is_running() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::clone(&self.cancelled);
+        std::thread::spawn(move || {
+            let update = scan(&roots, &index.fingerprints());
+            if update.is_empty() || cancelled.load(Ordering::Relaxed) {
+                let _ = sender.send(None);
+                return;
+            }
+            let mut index = ContentIndex::clone(&index);
+            index.apply(update);
+            if let Some(path) = save_to
+                && !cancelled.load(Ordering::Relaxed)
+            {
+                let _ = index.save(&path);
+            }
+            let _ = sender.send(Some(index));
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// 取回已完成的扫描结果，没有变化时为 `None`
+    pub fn poll(&mut self) -> Option<ContentIndex> {
+        let receiver = self.receiver.as_ref()?;
+        match receiver.try_recv() {
+            Ok(index) => {
+                self.receiver = None;
+                index
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.receiver = None;
+                None
+            }
+        }
+    }
+}
+
+impl Drop for ContentIndexer {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn directory_entry(path: &Path) -> FileEntry {
+        FileEntry::new(path.to_path_buf(), "docs".to_string(), None, vec![], true)
+    }
+
+    #[test]
+    fn test_scan_and_find() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("docs");
+        fs::create_dir_all(root.join("2024")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(
+            root.join("2024/notes.md"),
+            "Agenda\nThe Quarterly Report is due on Friday.",
+        )
+        .unwrap();
+        fs::write(root.join(".git/config"), "quarterly report").unwrap();
+        fs::write(root.join("scan.png"), "quarterly report").unwrap();
+        fs::write(root.join("binary.txt"), b"quarterly report\0").unwrap();
+        fs::write(
+            root.join("large.txt"),
+            "quarterly report ".repeat(MAX_FILE_SIZE as usize / 10),
+        )
+        .unwrap();
+
+        let entries = vec![directory_entry(&root)];
+        let mut index = ContentIndex::default();
+        index.apply(scan(&roots(&entries), &index.fingerprints()));
+        assert_eq!(index.len(), 1);
+
+        let hit = index.find(&entries[0], "quarterly report").unwrap();
+        assert_eq!(hit.path, root.join("2024/notes.md"));
+        assert_eq!(hit.text, "Agenda The Quarterly Report is due on Friday.");
+        assert_eq!(&hit.text[hit.highlight.clone()], "Quarterly Report");
+        assert!(index.find(&entries[0], "annual report").is_none());
+
+        // 文件条目只查找它自己
+        let file = FileEntry::new(
+            root.join("2024/notes.md"),
+            "notes".to_string(),
+            None,
+            vec![],
+            false,
+        );
+        assert!(index.find(&file, "friday").is_some());
+
+        // 保存后重新读取，不区分大小写的查找仍然可用
+        let path = ContentIndex::index_path(&dir.path().join("data.json"));
+        index.save(&path).unwrap();
+        assert!(ContentIndex::load(&path).find(&file, "FRIDAY").is_some());
+    }
+
+    #[test]
+    fn test_rescan_only_reads_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "alpha").unwrap();
+        fs::write(dir.path().join("b.txt"), "beta").unwrap();
+        let roots = vec![dir.path().to_path_buf()];
+
+        let mut index = ContentIndex::default();
+        let update = scan(&roots, &index.fingerprints());
+        assert_eq!(update.changed.len(), 2);
+        index.apply(update);
+        assert!(scan(&roots, &index.fingerprints()).is_empty());
+
+        fs::write(dir.path().join("a.txt"), "alpha, revised").unwrap();
+        fs::remove_file(dir.path().join("b.txt")).unwrap();
+        let update = scan(&roots, &index.fingerprints());
+        assert_eq!(update.changed.len(), 1);
+        assert_eq!(update.removed, vec![dir.path().join("b.txt")]);
+        index.apply(update);
+
+        let entry = directory_entry(dir.path());
+        assert!(index.find(&entry, "revised").is_some());
+        assert!(index.find(&entry, "beta").is_none());
+    }
+
+    #[test]
+    fn test_indexer_saves_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "alpha").unwrap();
+        let path = ContentIndex::index_path(&dir.path().join("data.json"));
+
+        let mut indexer = ContentIndexer::default();
+        indexer.start(
+            vec![dir.path().to_path_buf()],
+            Arc::new(ContentIndex::default()),
+            Some(path.clone()),
+        );
+        let index = loop {
+            if let Some(index) = indexer.poll() {
+                break index;
+            }
+            assert!(indexer.is_running());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert_eq!(index.len(), 1);
+        assert_eq!(ContentIndex::load(&path).len(), 1);
+
+        // 没有变化时不返回新的索引
+        indexer.start(vec![dir.path().to_path_buf()], Arc::new(index), None);
+        while indexer.is_running() {
+            assert!(indexer.poll().is_none());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+}
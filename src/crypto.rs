@@ -0,0 +1,235 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+/// 加密容器的格式标识，写在文件的 `format` 字段中
+const FORMAT: &str = "file_manager_encrypted";
+const FORMAT_VERSION: u32 = 1;
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+/// 密钥派生参数（Argon2id），随文件保存，解密时按文件中的参数重新派生
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+}
+
+/// 加密后的文件内容：明文为序列化后的 `UserData`
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    format: String,
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// 只用于识别文件是否为加密容器
+#[derive(Deserialize)]
+struct FormatProbe {
+    #[serde(default)]
+    format: String,
+}
+
+/// 由密码派生的密钥，解锁后保存在内存中用于后续保存
+#[derive(Clone)]
+pub struct EncryptionKey {
+    key: [u8; KEY_LEN],
+    kdf: KdfParams,
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptionKey").finish_non_exhaustive()
+    }
+}
+
+impl Drop for EncryptionKey {
+    fn drop(&mut self) {
+        self.key.fill(0);
+    }
+}
+
+impl EncryptionKey {
+    /// 用新的随机盐从密码派生密钥（设置或修改密码时使用）
+    pub fn derive(passphrase: &str) -> Result<Self, String> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kdf = KdfParams {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+            salt: BASE64.encode(salt),
+        };
+        Self::derive_with(passphrase, kdf)
+    }
+
+    fn derive_with(passphrase: &str, kdf: KdfParams) -> Result<Self, String> {
+        if passphrase.is_empty() {
+            return Err("密码不能为空".to_string());
+        }
+        let salt = BASE64
+            .decode(&kdf.salt)
+            .map_err(|_| "加密文件头已损坏".to_string())?;
+        let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(KEY_LEN))
+            .map_err(|e| format!("密钥派生参数无效: {}", e))?;
+
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| format!("派生密钥失败: {}", e))?;
+        Ok(Self { key, kdf })
+    }
+}
+
+/// 内容是否为加密容器
+pub fn is_encrypted(content: &str) -> bool {
+    serde_json::from_str::<FormatProbe>(content).is_ok_and(|probe| probe.format == FORMAT)
+}
+
+/// 附加认证数据：文件头被篡改时解密会失败
+fn associated_data(version: u32, kdf: &KdfParams) -> Vec<u8> {
+    format!(
+        "{}:{}:{}:{}:{}:{}",
+        FORMAT, version, kdf.m_cost, kdf.t_cost, kdf.p_cost, kdf.salt
+    )
+    .into_bytes()
+}
+
+/// 加密明文，每次使用新的随机 nonce
+pub fn encrypt(plaintext: &str, key: &EncryptionKey) -> Result<String, String> {
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key.key));
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let aad = associated_data(FORMAT_VERSION, &key.kdf);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad: &aad,
+            },
+        )
+        .map_err(|_| "加密失败".to_string())?;
+
+    let envelope = Envelope {
+        format: FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        kdf: key.kdf.clone(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&envelope).map_err(|e| format!("序列化失败: {}", e))
+}
+
+/// 用密码解密，同时返回派生出的密钥供之后保存使用
+pub fn decrypt(content: &str, passphrase: &str) -> Result<(String, EncryptionKey), String> {
+    let envelope = parse_envelope(content)?;
+    let key = EncryptionKey::derive_with(passphrase, envelope.kdf.clone())?;
+    let plaintext = open_envelope(&envelope, &key)?;
+    Ok((plaintext, key))
+}
+
+/// 用已解锁的密钥解密；文件在其他地方换了密码时返回错误
+pub fn decrypt_with_key(content: &str, key: &EncryptionKey) -> Result<String, String> {
+    let envelope = parse_envelope(content)?;
+    if envelope.kdf != key.kdf {
+        return Err("数据文件的密码已在其他地方修改，请重新解锁".to_string());
+    }
+    open_envelope(&envelope, key)
+}
+
+/// 加密文件是否使用该密钥（相同的盐和派生参数）
+pub fn matches_key(content: &str, key: &EncryptionKey) -> bool {
+    parse_envelope(content).is_ok_and(|envelope| envelope.kdf == key.kdf)
+}
+
+fn parse_envelope(content: &str) -> Result<Envelope, String> {
+    let envelope: Envelope =
+        serde_json::from_str(content).map_err(|e| format!("加密文件格式错误: {}", e))?;
+    if envelope.format != FORMAT {
+        return Err("不是加密的数据文件".to_string());
+    }
+    if envelope.version > FORMAT_VERSION {
+        return Err(format!(
+            "加密格式版本 {} 由更新版本的程序写入，请升级程序",
+            envelope.version
+        ));
+    }
+    Ok(envelope)
+}
+
+fn open_envelope(envelope: &Envelope, key: &EncryptionKey) -> Result<String, String> {
+    let nonce = BASE64
+        .decode(&envelope.nonce)
+        .ok()
+        .filter(|n| n.len() == 24)
+        .ok_or_else(|| "加密文件头已损坏".to_string())?;
+    let ciphertext = BASE64
+        .decode(&envelope.ciphertext)
+        .map_err(|_| "加密数据已损坏".to_string())?;
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key.key));
+    let aad = associated_data(envelope.version, &envelope.kdf);
+    let plaintext = cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| "密码错误或数据已损坏".to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "解密后的数据不是有效文本".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let key = EncryptionKey::derive("correct horse").unwrap();
+        let content = encrypt(r#"{"entries":[]}"#, &key).unwrap();
+
+        assert!(is_encrypted(&content));
+        assert!(!is_encrypted(r#"{"entries":[]}"#));
+        assert!(!content.contains("entries"));
+
+        let (plaintext, unlocked) = decrypt(&content, "correct horse").unwrap();
+        assert_eq!(plaintext, r#"{"entries":[]}"#);
+        assert_eq!(decrypt_with_key(&content, &unlocked).unwrap(), plaintext);
+
+        assert!(decrypt(&content, "wrong").is_err());
+        // 另一个密钥（不同的盐）不能直接解密
+        let other = EncryptionKey::derive("correct horse").unwrap();
+        assert!(decrypt_with_key(&content, &other).is_err());
+        assert!(matches_key(&content, &unlocked));
+        assert!(!matches_key(&content, &other));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let key = EncryptionKey::derive("pw").unwrap();
+        let content = encrypt("secret", &key).unwrap();
+
+        let mut envelope: Envelope = serde_json::from_str(&content).unwrap();
+        envelope.kdf.t_cost += 1;
+        let tampered = serde_json::to_string(&envelope).unwrap();
+        assert!(decrypt(&tampered, "pw").is_err());
+
+        let mut envelope: Envelope = serde_json::from_str(&content).unwrap();
+        let mut bytes = BASE64.decode(&envelope.ciphertext).unwrap();
+        bytes[0] ^= 1;
+        envelope.ciphertext = BASE64.encode(bytes);
+        let tampered = serde_json::to_string(&envelope).unwrap();
+        assert!(decrypt(&tampered, "pw").is_err());
+    }
+}
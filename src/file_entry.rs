@@ -1,5 +1,9 @@
-use pinyin::ToPinyin;
+use crate::frecency;
+use crate::tags;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -9,6 +13,8 @@ pub enum EntryType {
     Directory,
     WebLink,
     Collection,
+    /// 直接启动的程序或脚本，见 [`CommandSpec`]
+    Command,
 }
 
 impl Default for EntryType {
@@ -17,7 +23,89 @@ impl Default for EntryType {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+/// 集合的打开方式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchMode {
+    /// 按顺序依次打开，每项在上一项之后等待各自的延迟
+    #[default]
+    Sequential,
+    /// 同时开始，每项在开始后等待各自的延迟
+    Parallel,
+    /// 只打开第一个启用的项目
+    FirstOnly,
+}
+
+impl LaunchMode {
+    pub const ALL: [LaunchMode; 3] = [
+        LaunchMode::Sequential,
+        LaunchMode::Parallel,
+        LaunchMode::FirstOnly,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LaunchMode::Sequential => "依次打开",
+            LaunchMode::Parallel => "同时打开",
+            LaunchMode::FirstOnly => "只打开第一个",
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 集合中子项目的打开选项
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChildOptions {
+    /// 停用的子项目打开集合时跳过
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 打开前等待的毫秒数
+    #[serde(default = "default_delay_ms")]
+    pub delay_ms: u64,
+}
+
+/// 依次打开时默认的间隔，避免同时启动太多程序
+pub const DEFAULT_DELAY_MS: u64 = 50;
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_delay_ms() -> u64 {
+    DEFAULT_DELAY_MS
+}
+
+impl Default for ChildOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            delay_ms: DEFAULT_DELAY_MS,
+        }
+    }
+}
+
+/// 命令条目要启动的程序
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CommandSpec {
+    /// 程序路径或 PATH 中的程序名
+    pub program: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// 工作目录，为空时使用启动器的当前目录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+    /// 额外的环境变量
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// 在终端窗口中运行；此时输出显示在终端中，不记录到运行日志
+    #[serde(default)]
+    pub in_terminal: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct FileEntry {
     pub path: PathBuf,
     pub name: String,
@@ -29,15 +117,45 @@ pub struct FileEntry {
     pub url: Option<String>, // 网页链接地址
     #[serde(default)]
     pub child_entries: Vec<String>, // 集合类型的子项目ID
+    /// 智能集合的搜索条件；不为空时集合成员由满足条件的条目组成，忽略 `child_entries`
+    #[serde(default)]
+    pub smart_query: Option<String>,
+    /// 集合的打开方式
+    #[serde(default, skip_serializing_if = "LaunchMode::is_default")]
+    pub launch_mode: LaunchMode,
+    /// 子项目的打开选项（按子项目ID），没有记录的使用默认选项
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub child_options: BTreeMap<String, ChildOptions>,
+    /// 命令条目要启动的程序
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<CommandSpec>,
     // 保持向后兼容性
     #[serde(default)]
     pub is_directory: bool,
     // 新增唯一ID字段
     #[serde(default = "generate_id")]
     pub id: String,
-    // 向后兼容的旧格式索引
+    /// 添加时间，旧数据中为空（未知）
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// 最后修改时间
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// 最后打开时间，从未打开过为空
+    #[serde(default)]
+    pub last_opened_at: Option<DateTime<Utc>>,
+    /// 打开次数
     #[serde(default)]
-    pub legacy_child_entries: Vec<usize>,
+    pub open_count: u32,
+    /// 最近几次打开的时间，用于计算热度
+    #[serde(default)]
+    pub recent_opens: Vec<DateTime<Utc>>,
+    /// 置顶：排序时始终排在最前
+    #[serde(default)]
+    pub pinned: bool,
+    /// 当前版本不认识的字段（例如由更新版本写入），保存时原样写回
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl FileEntry {
@@ -63,42 +181,22 @@ impl FileEntry {
             entry_type,
             url: None,
             child_entries: Vec::new(),
+            smart_query: None,
+            launch_mode: LaunchMode::default(),
+            child_options: BTreeMap::new(),
+            command: None,
             is_directory,
             id: generate_id(),
-            legacy_child_entries: Vec::new(),
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            last_opened_at: None,
+            open_count: 0,
+            recent_opens: Vec::new(),
+            pinned: false,
+            extra: Map::new(),
         }
     }
 
-    /// 从旧版本数据迁移时使用的构造函数
-    pub fn migrate_from_old(mut self) -> Self {
-        // 如果entry_type是默认值，根据is_directory重新设置
-        if self.entry_type == EntryType::File && self.is_directory {
-            self.entry_type = EntryType::Directory;
-        }
-
-        // 如果没有ID，生成一个新的
-        if self.id.is_empty() {
-            self.id = generate_id();
-        }
-
-        // 保存旧的索引引用以便后续迁移
-        if !self.child_entries.is_empty() && self.legacy_child_entries.is_empty() {
-            // 如果child_entries包含的是数字字符串，说明是从索引转换来的
-            let mut legacy_indices = Vec::new();
-            for child_id in &self.child_entries {
-                if let Ok(index) = child_id.parse::<usize>() {
-                    legacy_indices.push(index);
-                }
-            }
-            if !legacy_indices.is_empty() {
-                self.legacy_child_entries = legacy_indices;
-                self.child_entries.clear(); // 清空，等待ID迁移
-            }
-        }
-
-        self
-    }
-
     pub fn new_with_nickname(
         path: PathBuf,
         name: String,
@@ -122,9 +220,19 @@ impl FileEntry {
             entry_type,
             url: None,
             child_entries: Vec::new(),
+            smart_query: None,
+            launch_mode: LaunchMode::default(),
+            child_options: BTreeMap::new(),
+            command: None,
             is_directory,
             id: generate_id(),
-            legacy_child_entries: Vec::new(),
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            last_opened_at: None,
+            open_count: 0,
+            recent_opens: Vec::new(),
+            pinned: false,
+            extra: Map::new(),
         }
     }
 
@@ -145,9 +253,19 @@ impl FileEntry {
             entry_type: EntryType::WebLink,
             url: Some(url),
             child_entries: Vec::new(),
+            smart_query: None,
+            launch_mode: LaunchMode::default(),
+            child_options: BTreeMap::new(),
+            command: None,
             is_directory: false,
             id: generate_id(),
-            legacy_child_entries: Vec::new(),
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            last_opened_at: None,
+            open_count: 0,
+            recent_opens: Vec::new(),
+            pinned: false,
+            extra: Map::new(),
         }
     }
 
@@ -168,12 +286,100 @@ impl FileEntry {
             entry_type: EntryType::Collection,
             url: None,
             child_entries: child_entry_ids,
+            smart_query: None,
+            launch_mode: LaunchMode::default(),
+            child_options: BTreeMap::new(),
+            command: None,
             is_directory: false,
             id: generate_id(),
-            legacy_child_entries: Vec::new(),
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            last_opened_at: None,
+            open_count: 0,
+            recent_opens: Vec::new(),
+            pinned: false,
+            extra: Map::new(),
         }
     }
 
+    /// 创建命令条目，路径为程序，用于显示和搜索
+    pub fn new_command(
+        name: String,
+        command: CommandSpec,
+        nickname: Option<String>,
+        description: Option<String>,
+        tags: Vec<String>,
+    ) -> Self {
+        let mut entry = Self::new_with_nickname(
+            PathBuf::from(&command.program),
+            name,
+            nickname,
+            description,
+            tags,
+            false,
+        );
+        entry.entry_type = EntryType::Command;
+        entry.command = Some(command);
+        entry
+    }
+
+    /// 创建智能集合：保存搜索条件，成员在使用时按当前资料库计算
+    pub fn new_smart_collection(
+        name: String,
+        nickname: Option<String>,
+        description: Option<String>,
+        tags: Vec<String>,
+        query: String,
+    ) -> Self {
+        let mut collection = Self::new_collection(name, nickname, description, tags, Vec::new());
+        collection.smart_query = Some(query);
+        collection
+    }
+
+    /// 导入合并时是否与已有条目重复：ID 相同，或类型相同且指向同一目标
+    /// （文件和文件夹比较路径，网页比较地址，命令比较程序、参数等）；集合只比较 ID
+    pub fn is_duplicate_of(&self, other: &FileEntry) -> bool {
+        if self.id == other.id {
+            return true;
+        }
+        if self.entry_type != other.entry_type {
+            return false;
+        }
+        match self.entry_type {
+            EntryType::File | EntryType::Directory => self.path == other.path,
+            EntryType::WebLink => self.url == other.url,
+            EntryType::Command => self.command == other.command,
+            EntryType::Collection => false,
+        }
+    }
+
+    pub fn is_smart_collection(&self) -> bool {
+        self.entry_type == EntryType::Collection && self.smart_query.is_some()
+    }
+
+    /// 内容被修改后调用，更新修改时间
+    pub fn touch(&mut self) {
+        self.updated_at = Some(Utc::now());
+    }
+
+    /// 记录一次打开
+    pub fn mark_opened(&mut self, at: DateTime<Utc>) {
+        self.last_opened_at = Some(at);
+        self.open_count = self.open_count.saturating_add(1);
+        frecency::push_recent_open(&mut self.recent_opens, at);
+    }
+
+    /// 除打开时间和次数外内容是否相同（打开条目不算修改）
+    pub fn same_content(&self, other: &FileEntry) -> bool {
+        let without_usage = |entry: &FileEntry| FileEntry {
+            last_opened_at: None,
+            open_count: 0,
+            recent_opens: Vec::new(),
+            ..entry.clone()
+        };
+        without_usage(self) == without_usage(other)
+    }
+
     /// 添加子项目到集合（使用ID）
     #[allow(dead_code)]
     pub fn add_child_entry(&mut self, entry_id: &str) {
@@ -192,116 +398,36 @@ impl FileEntry {
         }
     }
 
-    /// 获取子项目ID列表
-    #[allow(dead_code)]
-    pub fn get_child_entries(&self) -> &Vec<String> {
-        &self.child_entries
-    }
-
-    /// 检查是否有需要迁移的旧索引数据
-    pub fn has_legacy_child_entries(&self) -> bool {
-        !self.legacy_child_entries.is_empty()
+    /// 子项目的打开选项
+    pub fn child_options(&self, entry_id: &str) -> ChildOptions {
+        self.child_options
+            .get(entry_id)
+            .copied()
+            .unwrap_or_default()
     }
 
-    /// 获取需要迁移的旧索引数据
-    pub fn get_legacy_child_entries(&self) -> &Vec<usize> {
-        &self.legacy_child_entries
-    }
-
-    /// 清除已迁移的旧索引数据
-    pub fn clear_legacy_child_entries(&mut self) {
-        self.legacy_child_entries.clear();
-    }
-
-    /// 将中文转换为拼音首字母
-    fn to_pinyin_initials(text: &str) -> String {
-        text.to_pinyin()
-            .map(|pinyin| {
-                pinyin
-                    .map(|p| p.first_letter().to_uppercase().to_string())
-                    .unwrap_or_else(|| "".to_string())
-            })
-            .collect::<String>()
-    }
-
-    /// 将中文转换为完整拼音
-    fn to_full_pinyin(text: &str) -> String {
-        text.to_pinyin()
-            .map(|pinyin| {
-                pinyin
-                    .map(|p| p.plain().to_string())
-                    .unwrap_or_else(|| "".to_string())
-            })
-            .collect::<String>()
-    }
-
-    /// 检查文本是否匹配拼音搜索
-    fn matches_pinyin(&self, text: &str, query: &str) -> bool {
-        if query.is_empty() || text.is_empty() {
-            return false;
-        }
-
-        let query_lower = query.to_lowercase();
-
-        // 检查拼音首字母匹配
-        let pinyin_initials = Self::to_pinyin_initials(text).to_lowercase();
-        if pinyin_initials.contains(&query_lower) {
-            return true;
-        }
-
-        // 检查完整拼音匹配
-        let full_pinyin = Self::to_full_pinyin(text).to_lowercase();
-        if full_pinyin.contains(&query_lower) {
-            return true;
+    /// 设置子项目的打开选项，默认选项不单独保存
+    pub fn set_child_options(&mut self, entry_id: &str, options: ChildOptions) {
+        if options == ChildOptions::default() {
+            self.child_options.remove(entry_id);
+        } else {
+            self.child_options.insert(entry_id.to_string(), options);
         }
-
-        false
     }
 
-    pub fn matches_query(&self, query: &str) -> bool {
-        if query.is_empty() {
-            return true;
-        }
-
-        let query_lower = query.to_lowercase();
-
-        // 搜索名称（包括拼音）
-        if self.name.to_lowercase().contains(&query_lower) || self.matches_pinyin(&self.name, query)
-        {
-            return true;
-        }
-
-        // 搜索昵称（包括拼音）
-        if let Some(nickname) = &self.nickname {
-            if nickname.to_lowercase().contains(&query_lower)
-                || self.matches_pinyin(nickname, query)
-            {
-                return true;
-            }
-        }
-
-        // 搜索标签
-        if self
-            .tags
-            .iter()
-            .any(|tag| tag.to_lowercase().contains(&query_lower))
-        {
-            return true;
-        }
-
-        // 搜索描述（包括拼音）
-        if let Some(description) = &self.description {
-            if description.to_lowercase().contains(&query_lower)
-                || self.matches_pinyin(description, query)
-            {
-                return true;
-            }
-        }
+    /// 删除已不在集合中的子项目的打开选项
+    pub fn prune_child_options(&mut self) {
+        let children = &self.child_entries;
+        self.child_options.retain(|id, _| children.contains(id));
+    }
 
-        false
+    /// 获取子项目ID列表
+    #[allow(dead_code)]
+    pub fn get_child_entries(&self) -> &Vec<String> {
+        &self.child_entries
     }
 
-    /// 解析标签字符串，只支持 # 标签
+    /// 解析标签字符串，只支持 # 标签；`/` 分隔层级，见 [`tags::normalize`]
     pub fn parse_tags(tag_input: &str) -> Vec<String> {
         let mut tags = Vec::new();
 
@@ -313,23 +439,15 @@ impl FileEntry {
                     continue;
                 }
 
-                // 处理 # 标签
-                if tag.starts_with('#') {
-                    let tag_name = tag.strip_prefix('#').unwrap_or(tag);
-                    if !tag_name.is_empty() {
-                        tags.push(format!("#{}", tag_name));
-                    }
-                }
-                // 普通标签，自动添加 # 前缀
-                else {
-                    tags.push(format!("#{}", tag));
+                // 没有 # 前缀的自动加上
+                if let Some(tag) = tags::normalize(tag) {
+                    tags.push(tag);
                 }
             }
         }
 
-        // 去重并排序
-        tags.sort();
-        tags.dedup();
+        // 去重，保持输入的顺序
+        tags::dedup(&mut tags);
         tags
     }
 
@@ -349,7 +467,7 @@ impl FileEntry {
 }
 
 /// 生成唯一ID
-fn generate_id() -> String {
+pub fn generate_id() -> String {
     Uuid::new_v4().to_string()
 }
 
@@ -384,6 +502,36 @@ mod tests {
         assert_ne!(entry.id, entry2.id);
     }
 
+    #[test]
+    fn test_is_duplicate_of() {
+        let command = |args: &[&str]| {
+            FileEntry::new_command(
+                "cargo".to_string(),
+                CommandSpec {
+                    program: "cargo".to_string(),
+                    args: args.iter().map(|a| a.to_string()).collect(),
+                    ..CommandSpec::default()
+                },
+                None,
+                None,
+                vec![],
+            )
+        };
+        let build = command(&["build"]);
+        // 同一个程序的不同命令不算重复
+        assert!(!command(&["test"]).is_duplicate_of(&build));
+        assert!(command(&["build"]).is_duplicate_of(&build));
+
+        let file = FileEntry::new(PathBuf::from("/a"), "a".to_string(), None, vec![], false);
+        let same_path = FileEntry::new(PathBuf::from("/a"), "b".to_string(), None, vec![], false);
+        assert!(same_path.is_duplicate_of(&file));
+
+        let first = FileEntry::new_collection("c".to_string(), None, None, vec![], vec![]);
+        let second = FileEntry::new_collection("c".to_string(), None, None, vec![], vec![]);
+        assert!(!second.is_duplicate_of(&first));
+        assert!(first.clone().is_duplicate_of(&first));
+    }
+
     #[test]
     fn test_collection_with_id_system() {
         // 创建一些测试条目
@@ -420,33 +568,6 @@ mod tests {
         assert_eq!(collection.entry_type, EntryType::Collection);
     }
 
-    #[test]
-    fn test_legacy_migration() {
-        // 模拟旧格式的数据（使用索引）
-        let mut old_collection = FileEntry {
-            path: PathBuf::from("collection://Test"),
-            name: "Test Collection".to_string(),
-            nickname: None,
-            description: None,
-            tags: vec![],
-            entry_type: EntryType::Collection,
-            url: None,
-            child_entries: vec!["0".to_string(), "1".to_string()], // 模拟从索引转换的字符串
-            is_directory: false,
-            id: "".to_string(), // 旧数据没有ID
-            legacy_child_entries: vec![],
-        };
-
-        // 执行迁移
-        old_collection = old_collection.migrate_from_old();
-
-        // 验证迁移结果
-        assert!(!old_collection.id.is_empty()); // 应该生成了新的ID
-        assert!(old_collection.has_legacy_child_entries()); // 应该保存了旧的索引数据
-        assert_eq!(old_collection.get_legacy_child_entries(), &vec![0, 1]);
-        assert!(old_collection.child_entries.is_empty()); // 子项目列表应该被清空等待ID迁移
-    }
-
     #[test]
     fn test_add_remove_child_entries_by_id() {
         let mut collection =
@@ -541,6 +662,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unknown_fields_round_trip() {
+        // 模拟由更新版本写入、带有未知字段的条目
+        let json = r##"{
+            "path": "/test/path",
+            "name": "Test",
+            "nickname": null,
+            "description": null,
+            "tags": ["#a"],
+            "url": null,
+            "id": "entry-id",
+            "rating": 5,
+            "color": {"r": 1, "g": 2, "b": 3}
+        }"##;
+
+        let entry: FileEntry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.extra.len(), 2);
+        assert_eq!(entry.extra["rating"], 5);
+
+        // 重新序列化后未知字段应保持不变
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(value["rating"], 5);
+        assert_eq!(value["color"]["g"], 2);
+        assert_eq!(value["id"], "entry-id");
+    }
+
     #[test]
     fn test_web_link_entry_has_id() {
         let web_entry = FileEntry::new_web_link(
@@ -555,4 +702,27 @@ mod tests {
         assert_eq!(web_entry.entry_type, EntryType::WebLink);
         assert_eq!(web_entry.url, Some("https://example.com".to_string()));
     }
+
+    #[test]
+    fn test_command_entry_round_trip() {
+        let command = CommandSpec {
+            program: "cargo".to_string(),
+            args: vec!["build".to_string(), "--release".to_string()],
+            working_dir: Some(PathBuf::from("/work/app")),
+            env: BTreeMap::from([("RUST_LOG".to_string(), "debug".to_string())]),
+            in_terminal: false,
+        };
+        let entry = FileEntry::new_command("构建".to_string(), command.clone(), None, None, vec![]);
+        assert_eq!(entry.entry_type, EntryType::Command);
+        assert_eq!(entry.path, PathBuf::from("cargo"));
+
+        let json = serde_json::to_string(&entry).unwrap();
+        let loaded: FileEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.command, Some(command));
+        assert!(loaded.extra.is_empty());
+
+        // 其他类型的条目不写入命令字段
+        let file = FileEntry::new(PathBuf::from("/a"), "a".to_string(), None, vec![], false);
+        assert!(!serde_json::to_string(&file).unwrap().contains("command"));
+    }
 }
@@ -0,0 +1,100 @@
+use crate::file_entry::FileEntry;
+use chrono::{DateTime, Utc};
+
+/// 每个条目保留的最近打开时间数量，用作计算热度的样本
+pub const RECENT_OPENS_LIMIT: usize = 10;
+
+/// 默认半衰期（天）：一次打开的权重每过这么多天减半
+pub const DEFAULT_HALF_LIFE_DAYS: f64 = 14.0;
+
+/// 热度（frecency）= 打开次数 × 最近几次打开的平均时间权重
+///
+/// 与 Firefox 的做法类似：次数反映“常用”，样本的时间衰减反映“最近在用”。
+/// 没有样本的旧数据退回到最后打开时间；从未打开的条目为 0。
+pub fn score(entry: &FileEntry, now: DateTime<Utc>, half_life_days: f64) -> f64 {
+    if entry.open_count == 0 {
+        return 0.0;
+    }
+
+    let samples: Vec<DateTime<Utc>> = if entry.recent_opens.is_empty() {
+        entry.last_opened_at.into_iter().collect()
+    } else {
+        entry.recent_opens.clone()
+    };
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let weight_sum: f64 = samples
+        .iter()
+        .map(|&at| decay_weight(now, at, half_life_days))
+        .sum();
+    entry.open_count as f64 * weight_sum / samples.len() as f64
+}
+
+/// 一次打开在 `now` 时的权重，刚打开时为 1，每个半衰期减半
+fn decay_weight(now: DateTime<Utc>, at: DateTime<Utc>, half_life_days: f64) -> f64 {
+    let age_days = (now - at).num_seconds().max(0) as f64 / 86_400.0;
+    0.5_f64.powf(age_days / half_life_days.max(0.1))
+}
+
+/// 记录一次打开时间，只保留最近的若干次
+pub fn push_recent_open(recent_opens: &mut Vec<DateTime<Utc>>, at: DateTime<Utc>) {
+    recent_opens.push(at);
+    recent_opens.sort();
+    if recent_opens.len() > RECENT_OPENS_LIMIT {
+        let excess = recent_opens.len() - RECENT_OPENS_LIMIT;
+        recent_opens.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::file;
+    use chrono::{Duration, TimeZone};
+
+    fn opened(times: &[DateTime<Utc>]) -> FileEntry {
+        let mut entry = file("a");
+        for &at in times {
+            entry.mark_opened(at);
+        }
+        entry
+    }
+
+    #[test]
+    fn test_frequency_and_recency() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let days_ago = |d: i64| now - Duration::days(d);
+
+        let never = opened(&[]);
+        let once_today = opened(&[now]);
+        let daily = opened(&(0..10).map(days_ago).collect::<Vec<_>>());
+        let stale = opened(&(300..310).map(days_ago).collect::<Vec<_>>());
+
+        assert_eq!(score(&never, now, DEFAULT_HALF_LIFE_DAYS), 0.0);
+        assert!((score(&once_today, now, DEFAULT_HALF_LIFE_DAYS) - 1.0).abs() < 1e-9);
+        // 常用且最近在用的条目排在前面，很久以前常用的排在后面
+        assert!(
+            score(&daily, now, DEFAULT_HALF_LIFE_DAYS)
+                > score(&once_today, now, DEFAULT_HALF_LIFE_DAYS)
+        );
+        assert!(
+            score(&stale, now, DEFAULT_HALF_LIFE_DAYS)
+                < score(&once_today, now, DEFAULT_HALF_LIFE_DAYS)
+        );
+        // 半衰期越长，旧的打开记录衰减越慢
+        assert!(score(&stale, now, 365.0) > score(&stale, now, DEFAULT_HALF_LIFE_DAYS));
+    }
+
+    #[test]
+    fn test_recent_opens_are_capped() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let mut recent = Vec::new();
+        for i in 0..(RECENT_OPENS_LIMIT as i64 + 5) {
+            push_recent_open(&mut recent, now + Duration::hours(i));
+        }
+        assert_eq!(recent.len(), RECENT_OPENS_LIMIT);
+        assert_eq!(recent[0], now + Duration::hours(5));
+    }
+}
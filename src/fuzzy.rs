@@ -0,0 +1,696 @@
+use crate::content_index::ContentSnippet;
+use crate::search_index::EntryIndex;
+use crate::tags;
+use pinyin::ToPinyinMulti;
+use std::ops::Range;
+
+// 打分参数，参考 fzf 的取值：匹配一个字符得分，字符之间的间隔扣分，
+// 单词开头、驼峰和连续匹配加分
+const SCORE_MATCH: i32 = 16;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_CAMEL: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = 4;
+/// 查询的第一个字符落在单词开头时，加分翻倍
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+/// 容错匹配（跳过查询中的一个字符）的扣分
+const PENALTY_TYPO: i32 = 24;
+/// 查询至少这么长才允许容错，太短的查询容错后几乎什么都能匹配
+const MIN_TYPO_QUERY_LEN: usize = 4;
+
+/// 命中昵称的额外加分：昵称是用户自己起的名字，比原名更能代表意图
+const BONUS_NICKNAME: i32 = 16;
+/// 搜索词与标签完全相同时的加分
+const BONUS_EXACT_TAG: i32 = 200;
+
+const UNREACHABLE: i32 = i32::MIN / 2;
+
+/// 一段文本的匹配结果，`ranges` 为匹配到的字符的字节范围
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextMatch {
+    pub score: i32,
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// 一个条目的匹配结果：总分以及名称、昵称、描述中需要高亮的范围
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntryMatch {
+    pub score: i32,
+    pub name: Vec<Range<usize>>,
+    pub nickname: Vec<Range<usize>>,
+    pub description: Vec<Range<usize>>,
+    /// `content:` 条件命中的文件内容片段
+    pub snippet: Option<ContentSnippet>,
+}
+
+/// 小写后的原文，以及每个字符作为匹配开头时的加分
+struct Haystack {
+    chars: Vec<char>,
+    bonus: Vec<i32>,
+}
+
+/// 一个字符可以匹配的一种写法：汉字本身或它的某个读音的拼音
+struct Reading {
+    chars: Vec<char>,
+    bonus: i32,
+}
+
+impl EntryMatch {
+    /// 合并另一个匹配结果：分数相加，高亮范围取并集，内容片段保留第一个
+    pub fn merge(&mut self, other: EntryMatch) {
+        self.score += other.score;
+        if self.snippet.is_none() {
+            self.snippet = other.snippet;
+        }
+        for (ranges, more) in [
+            (&mut self.name, other.name),
+            (&mut self.nickname, other.nickname),
+            (&mut self.description, other.description),
+        ] {
+            ranges.extend(more);
+            *ranges = merge_ranges(std::mem::take(ranges));
+        }
+    }
+}
+
+/// 对条目打分；搜索词以空白分隔，每个词都必须匹配，总分为各词得分之和
+pub fn score_entry(index: &EntryIndex, query: &str) -> Option<EntryMatch> {
+    let mut result = EntryMatch::default();
+    for term in query.split_whitespace() {
+        result.merge(score_term(index, term)?);
+    }
+    Some(result)
+}
+
+/// 对单个搜索词打分
+///
+/// `#` 开头的词只匹配标签。其余的词匹配名称、昵称、描述（都支持拼音）和标签。
+pub fn score_term(index: &EntryIndex, term: &str) -> Option<EntryMatch> {
+    if let Some(tag_query) = term.strip_prefix('#') {
+        return score_tag(index, tag_query);
+    }
+    score_fields(index, term, |text| text.match_query(term))
+}
+
+/// 短语匹配：名称、昵称、描述或标签中包含完整的短语（不区分大小写）
+pub fn score_phrase(index: &EntryIndex, phrase: &str) -> Option<EntryMatch> {
+    score_fields(index, phrase, |text| text.match_phrase(phrase))
+}
+
+/// 只匹配标签
+pub fn score_tag(index: &EntryIndex, tag_query: &str) -> Option<EntryMatch> {
+    score_tags(&index.tags, &tag_query.to_lowercase()).map(|score| EntryMatch {
+        score,
+        ..Default::default()
+    })
+}
+
+/// 用 `matcher` 匹配名称、昵称和描述，同时检查标签，取得分最高的字段
+fn score_fields(
+    index: &EntryIndex,
+    term: &str,
+    matcher: impl Fn(&IndexedText) -> Option<TextMatch>,
+) -> Option<EntryMatch> {
+    let mut result = EntryMatch::default();
+    let mut best = score_tags(&index.tags, &term.to_lowercase());
+    let mut consider = |score: i32| best = Some(best.map_or(score, |b| b.max(score)));
+
+    if let Some(m) = matcher(&index.name) {
+        consider(m.score);
+        result.name = m.ranges;
+    }
+    if let Some(m) = index.nickname.as_ref().and_then(&matcher) {
+        consider(m.score + BONUS_NICKNAME);
+        result.nickname = m.ranges;
+    }
+    if let Some(m) = index.description.as_ref().and_then(&matcher) {
+        // 描述通常较长，命中的权重减半
+        consider(m.score / 2);
+        result.description = m.ranges;
+    }
+
+    result.score = best?;
+    Some(result)
+}
+
+/// 标签匹配：完全相同或是搜索词的下级标签（`project/alpha` 之于 `project`）得分最高，
+/// 包含搜索词次之；`tags` 为小写且不带 `#`
+fn score_tags(tags: &[String], query: &str) -> Option<i32> {
+    let mut best = None;
+    for tag in tags {
+        let score = if tags::is_within(tag, query) {
+            BONUS_EXACT_TAG
+        } else if tag.contains(query) {
+            SCORE_MATCH * query.chars().count() as i32
+        } else {
+            continue;
+        };
+        best = best.max(Some(score));
+    }
+    best
+}
+
+/// 预先处理好的文本：小写后的原文以及拼音首字母和完整拼音，搜索时不再重复转换
+pub struct IndexedText {
+    /// 每个字符在原文中的字节范围，用于生成高亮
+    offsets: Vec<Range<usize>>,
+    direct: Haystack,
+    /// 含有汉字时为每个字符的所有写法（多音字的每个读音都算），否则为空
+    readings: Vec<Vec<Reading>>,
+}
+
+impl IndexedText {
+    pub fn new(text: &str) -> Self {
+        let original: Vec<char> = text.chars().collect();
+        let direct = Haystack::direct(&original);
+        let readings = if original.iter().any(|c| c.to_pinyin_multi().is_some()) {
+            original
+                .iter()
+                .enumerate()
+                .map(|(i, c)| readings_of(*c, &direct, i))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            offsets: text
+                .char_indices()
+                .map(|(start, c)| start..start + c.len_utf8())
+                .collect(),
+            direct,
+            readings,
+        }
+    }
+
+    /// 模糊匹配，同时尝试原文和按音节的拼音匹配，取得分最高的
+    ///
+    /// 都匹配不上时，较长的查询允许跳过一个字符（容错一个输入错误）。
+    pub fn match_query(&self, query: &str) -> Option<TextMatch> {
+        let query: Vec<char> = query.chars().map(fold_case).collect();
+        if query.is_empty() || self.offsets.is_empty() {
+            return None;
+        }
+
+        let (score, positions) = self
+            .direct
+            .find(&query)
+            .into_iter()
+            .chain(best_segmented(&self.readings, &query))
+            .max_by_key(|(score, _)| *score)
+            .or_else(|| {
+                if query.len() < MIN_TYPO_QUERY_LEN {
+                    return None;
+                }
+                skippable_positions(&self.direct.chars, &query)
+                    .into_iter()
+                    .filter_map(|skip| {
+                        let mut shortened = query.clone();
+                        shortened.remove(skip);
+                        self.direct.find(&shortened)
+                    })
+                    .max_by_key(|(score, _)| *score)
+                    .map(|(score, positions)| (score - PENALTY_TYPO, positions))
+            })?;
+
+        Some(TextMatch {
+            score,
+            ranges: self.ranges(&positions),
+        })
+    }
+
+    /// 不区分大小写地查找完整短语，得分与连续匹配相同
+    pub fn match_phrase(&self, phrase: &str) -> Option<TextMatch> {
+        let phrase: Vec<char> = phrase.chars().map(fold_case).collect();
+        if phrase.is_empty() {
+            return None;
+        }
+
+        let start = self
+            .direct
+            .chars
+            .windows(phrase.len())
+            .position(|window| window == phrase.as_slice())?;
+        let positions: Vec<usize> = (start..start + phrase.len()).collect();
+        let score = SCORE_MATCH * phrase.len() as i32
+            + self.direct.bonus[start] * BONUS_FIRST_CHAR_MULTIPLIER
+            + BONUS_CONSECUTIVE * (phrase.len() as i32 - 1);
+        Some(TextMatch {
+            score,
+            ranges: self.ranges(&positions),
+        })
+    }
+
+    /// 把字符位置转换成字节范围，相邻的字符合并成一段
+    fn ranges(&self, positions: &[usize]) -> Vec<Range<usize>> {
+        merge_ranges(
+            positions
+                .iter()
+                .filter_map(|&p| self.offsets.get(p).cloned())
+                .collect(),
+        )
+    }
+}
+
+/// 模糊匹配一段文本，见 [`IndexedText::match_query`]
+#[cfg(test)]
+pub fn match_text(text: &str, query: &str) -> Option<TextMatch> {
+    IndexedText::new(text).match_query(query)
+}
+
+/// 跳过查询中的哪些字符后，剩下的部分是文本的子序列
+///
+/// 分别从前往后、从后往前贪心匹配，O(文本长度 + 查询长度)，
+/// 避免对每个位置都做一次完整的动态规划。
+fn skippable_positions(text: &[char], query: &[char]) -> Vec<usize> {
+    let m = query.len();
+    // prefix_end[k]：前 k 个查询字符贪心匹配后，下一个可用的文本位置
+    let mut prefix_end = vec![usize::MAX; m + 1];
+    prefix_end[0] = 0;
+    let mut position = 0;
+    for (k, q) in query.iter().enumerate() {
+        match text[position..].iter().position(|c| c == q) {
+            Some(offset) => {
+                position += offset + 1;
+                prefix_end[k + 1] = position;
+            }
+            None => break,
+        }
+    }
+    // suffix_start[k]：从第 k 个字符起的查询后缀从后往前贪心匹配时，最早的起始位置
+    let mut suffix_start = vec![None; m + 1];
+    suffix_start[m] = Some(text.len());
+    let mut end = text.len();
+    for k in (0..m).rev() {
+        match text[..end].iter().rposition(|c| *c == query[k]) {
+            Some(found) => {
+                end = found;
+                suffix_start[k] = Some(found);
+            }
+            None => break,
+        }
+    }
+
+    (0..m)
+        .filter(|&k| {
+            prefix_end[k] != usize::MAX
+                && suffix_start[k + 1].is_some_and(|start| prefix_end[k] <= start)
+        })
+        .collect()
+}
+
+impl Haystack {
+    fn direct(chars: &[char]) -> Self {
+        let bonus = chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| match i.checked_sub(1).map(|p| chars[p]) {
+                None => BONUS_BOUNDARY,
+                Some(prev) if is_separator(prev) && !is_separator(c) => BONUS_BOUNDARY,
+                Some(prev) if prev.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+                Some(prev) if !prev.is_ascii_digit() && c.is_ascii_digit() => BONUS_CAMEL,
+                Some(_) => 0,
+            })
+            .collect();
+        Self {
+            chars: chars.iter().copied().map(fold_case).collect(),
+            bonus,
+        }
+    }
+
+    /// 找到得分最高的匹配，返回分数和匹配到的字符位置
+    fn find(&self, query: &[char]) -> Option<(i32, Vec<usize>)> {
+        best_alignment(&self.chars, &self.bonus, query)
+    }
+}
+
+/// 第 `i` 个字符的所有写法：字符本身，以及汉字每个读音的拼音（多音字有多个）
+fn readings_of(c: char, direct: &Haystack, i: usize) -> Vec<Reading> {
+    let mut readings = vec![Reading {
+        chars: vec![direct.chars[i]],
+        bonus: direct.bonus[i],
+    }];
+    if let Some(multi) = c.to_pinyin_multi() {
+        for pinyin in multi {
+            let chars: Vec<char> = pinyin.plain().chars().collect();
+            if !readings.iter().any(|r| r.chars == chars) {
+                // 每个音节的开头都算作单词开头
+                readings.push(Reading {
+                    chars,
+                    bonus: BONUS_BOUNDARY,
+                });
+            }
+        }
+    }
+    readings
+}
+
+/// 按音节匹配：查询被切成若干段，每段对应一个字符，且必须是该字符某种写法的前缀，
+/// 因此 "zhongw"、"zhwd"、"zw文档" 这样混合了完整音节、半个音节和汉字的输入都能匹配
+///
+/// `score[j][i]` 表示查询的前 j 个字符已匹配、且最后一段落在第 i 个字符上时的最高分，
+/// 打分规则与 [`best_alignment`] 相同：同一段内的字母按连续匹配计分。
+fn best_segmented(readings: &[Vec<Reading>], query: &[char]) -> Option<(i32, Vec<usize>)> {
+    let (n, m) = (readings.len(), query.len());
+    if n == 0 || m == 0 {
+        return None;
+    }
+
+    let mut score = vec![UNREACHABLE; (m + 1) * n];
+    // 上一段的结束位置：(已匹配的查询字符数, 字符位置)
+    let mut from = vec![(0usize, 0usize); (m + 1) * n];
+    // gapped[j]：前 j 个查询字符已匹配、最后一段落在 i-2 及之前时，扣除间隔罚分后的最高分和位置
+    let mut gapped = vec![(UNREACHABLE, 0usize); m + 1];
+
+    for i in 0..n {
+        if i >= 2 {
+            for (j, gap) in gapped.iter_mut().enumerate().skip(1) {
+                let started = score[j * n + i - 2] - PENALTY_GAP_START;
+                let extended = gap.0 - PENALTY_GAP_EXTENSION;
+                *gap = if started >= extended {
+                    (started, i - 2)
+                } else {
+                    (extended, gap.1)
+                };
+            }
+        }
+
+        for start in 0..m {
+            let (previous, k) = if start == 0 {
+                (0, 0)
+            } else if i >= 1 && score[start * n + i - 1] + BONUS_CONSECUTIVE >= gapped[start].0 {
+                (score[start * n + i - 1] + BONUS_CONSECUTIVE, i - 1)
+            } else {
+                gapped[start]
+            };
+            if previous <= UNREACHABLE / 2 {
+                continue;
+            }
+
+            for reading in &readings[i] {
+                let bonus = if start == 0 {
+                    reading.bonus * BONUS_FIRST_CHAR_MULTIPLIER
+                } else {
+                    reading.bonus
+                };
+                let common = reading
+                    .chars
+                    .iter()
+                    .zip(&query[start..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                for len in 1..=common {
+                    let total = previous
+                        + SCORE_MATCH * len as i32
+                        + bonus
+                        + BONUS_CONSECUTIVE * (len as i32 - 1);
+                    let cell = (start + len) * n + i;
+                    if total > score[cell] {
+                        score[cell] = total;
+                        from[cell] = (start, k);
+                    }
+                }
+            }
+        }
+    }
+
+    let (mut i, best) = (0..n)
+        .map(|i| (i, score[m * n + i]))
+        .filter(|&(_, s)| s > UNREACHABLE / 2)
+        .max_by_key(|&(i, s)| (s, std::cmp::Reverse(i)))?;
+
+    let mut positions = Vec::new();
+    let mut j = m;
+    while j > 0 {
+        positions.push(i);
+        (j, i) = from[j * n + i];
+    }
+    positions.reverse();
+    Some((best, positions))
+}
+
+/// 子序列匹配的动态规划，与 fzf 的算法类似，时间复杂度 O(文本长度 × 查询长度)
+///
+/// `score[j][i]` 表示查询的前 j+1 个字符已匹配、且第 j 个字符落在 `text[i]` 时的最高分。
+fn best_alignment(text: &[char], bonus: &[i32], query: &[char]) -> Option<(i32, Vec<usize>)> {
+    let (n, m) = (text.len(), query.len());
+    if m == 0 || n < m || !is_subsequence(text, query) {
+        return None;
+    }
+
+    let mut score = vec![UNREACHABLE; n * m];
+    let mut from = vec![0usize; n * m];
+
+    for j in 0..m {
+        // 上一个查询字符在 i-2 及之前匹配时（中间有间隔），扣除间隔罚分后的最高分和位置
+        let mut gapped = (UNREACHABLE, 0);
+        for i in 0..n {
+            if j > 0 && i >= 2 {
+                let started = score[(j - 1) * n + i - 2] - PENALTY_GAP_START;
+                let extended = gapped.0 - PENALTY_GAP_EXTENSION;
+                gapped = if started >= extended {
+                    (started, i - 2)
+                } else {
+                    (extended, gapped.1)
+                };
+            }
+            if text[i] != query[j] {
+                continue;
+            }
+
+            let gain = SCORE_MATCH
+                + if j == 0 {
+                    bonus[i] * BONUS_FIRST_CHAR_MULTIPLIER
+                } else {
+                    bonus[i]
+                };
+            if j == 0 {
+                score[i] = gain;
+                continue;
+            }
+
+            let consecutive = if i >= 1 {
+                (score[(j - 1) * n + i - 1] + BONUS_CONSECUTIVE, i - 1)
+            } else {
+                (UNREACHABLE, 0)
+            };
+            let (previous, k) = if consecutive.0 >= gapped.0 {
+                consecutive
+            } else {
+                gapped
+            };
+            if previous > UNREACHABLE / 2 {
+                score[j * n + i] = previous + gain;
+                from[j * n + i] = k;
+            }
+        }
+    }
+
+    let last = (m - 1) * n;
+    let (mut i, best) = (0..n)
+        .map(|i| (i, score[last + i]))
+        .filter(|&(_, s)| s > UNREACHABLE / 2)
+        .max_by_key(|&(i, s)| (s, std::cmp::Reverse(i)))?;
+
+    let mut positions = vec![0; m];
+    for j in (0..m).rev() {
+        positions[j] = i;
+        i = from[j * n + i];
+    }
+    Some((best, positions))
+}
+
+fn is_subsequence(text: &[char], query: &[char]) -> bool {
+    let mut rest = text.iter();
+    query.iter().all(|q| rest.any(|c| c == q))
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '/' | '\\'
+                | '_'
+                | '-'
+                | '.'
+                | ','
+                | ':'
+                | '('
+                | ')'
+                | '['
+                | ']'
+                | '（'
+                | '）'
+                | '，'
+                | '。'
+        )
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// 排序并合并重叠或相邻的范围
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_entry::FileEntry;
+    use crate::test_support::tagged;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, nickname: Option<&str>, tags: &[&str]) -> EntryIndex {
+        let mut entry = tagged(name, tags);
+        entry.nickname = nickname.map(|n| n.to_string());
+        EntryIndex::new(&entry)
+    }
+
+    fn highlighted<'a>(text: &'a str, ranges: &[Range<usize>]) -> Vec<&'a str> {
+        ranges.iter().map(|r| &text[r.clone()]).collect()
+    }
+
+    #[test]
+    fn test_subsequence_and_ranges() {
+        let m = match_text("FileManager.rs", "fmgr").unwrap();
+        assert_eq!(
+            highlighted("FileManager.rs", &m.ranges),
+            vec!["F", "M", "g", "r"]
+        );
+
+        // 连续匹配和单词开头得分更高
+        let prefix = match_text("report_final", "rep").unwrap();
+        let scattered = match_text("rebuild_plan", "rep").unwrap();
+        assert!(prefix.score > scattered.score);
+        let boundary = match_text("my_report", "rep").unwrap();
+        let inner = match_text("prepared", "rep").unwrap();
+        assert!(boundary.score > inner.score);
+
+        assert!(match_text("abc", "abd").is_none());
+        assert_eq!(match_text("ABC", "abc").unwrap().ranges, vec![0..3]);
+    }
+
+    #[test]
+    fn test_typo_tolerance() {
+        // 打错一个字符仍能匹配，但得分低于正确输入
+        let typo = match_text("document", "dicument").unwrap();
+        let exact = match_text("document", "document").unwrap();
+        assert!(typo.score < exact.score);
+        // 短查询不做容错
+        assert!(match_text("doc", "dxc").is_none());
+
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(
+            skippable_positions(&chars("document"), &chars("dicument")),
+            vec![1]
+        );
+        assert_eq!(skippable_positions(&chars("abc"), &chars("abxc")), vec![2]);
+        assert!(skippable_positions(&chars("abc"), &chars("xabyc")).is_empty());
+    }
+
+    #[test]
+    fn test_pinyin_matches_map_to_characters() {
+        let m = match_text("我的项目", "wdxm").unwrap();
+        assert_eq!(m.ranges, vec![0.."我的项目".len()]);
+
+        let m = match_text("我的项目", "xiangmu").unwrap();
+        assert_eq!(highlighted("我的项目", &m.ranges), vec!["项目"]);
+    }
+
+    #[test]
+    fn test_segmented_pinyin() {
+        // 完整音节、半个音节、首字母和汉字可以混在一起
+        for query in [
+            "zhongwen",
+            "zhongw",
+            "zhwd",
+            "zw文档",
+            "中wwd",
+            "zhongwenwendang",
+        ] {
+            assert!(match_text("中文文档", query).is_some(), "{}", query);
+        }
+        let m = match_text("中文文档", "zhongw").unwrap();
+        assert_eq!(highlighted("中文文档", &m.ranges), vec!["中文"]);
+        // 音节只能从开头匹配
+        assert!(match_text("中文文档", "hongwen").is_none());
+
+        // 多音字的每个读音都能匹配
+        for query in ["yinhang", "yh", "yinx"] {
+            assert!(match_text("银行", query).is_some(), "{}", query);
+        }
+        assert!(match_text("重庆", "chongqing").is_some());
+        assert!(match_text("重要", "zhongyao").is_some());
+    }
+
+    #[test]
+    fn test_former_matches_query_cases() {
+        // 原来 FileEntry::matches_query 支持的搜索：名称、昵称、标签、描述，以及拼音首字母和完整拼音
+        let mut entry = FileEntry::new(
+            PathBuf::from("/docs/report.pdf"),
+            "项目报告".to_string(),
+            Some("Quarterly Summary".to_string()),
+            vec!["#Work".to_string()],
+            false,
+        );
+        entry.nickname = Some("季度总结".to_string());
+        let index = EntryIndex::new(&entry);
+
+        for query in [
+            "项目",
+            "报告",
+            "xmbg",
+            "XMBG",
+            "mbg",
+            "xiangmubaogao",
+            "季度",
+            "jdzj",
+            "jidu",
+            "work",
+            "WORK",
+            "quarterly",
+            "summary",
+        ] {
+            assert!(score_entry(&index, query).is_some(), "{}", query);
+        }
+        assert!(score_entry(&index, "").is_some());
+        assert!(score_entry(&index, "xyz").is_none());
+    }
+
+    #[test]
+    fn test_entry_scoring() {
+        let plain = entry("notes", None, &[]);
+        let nicknamed = entry("n", Some("notes"), &[]);
+        let tagged = entry("misc", None, &["#notes"]);
+
+        let score = |e: &EntryIndex, q: &str| score_entry(e, q).map(|m| m.score);
+        assert!(score(&nicknamed, "notes") > score(&plain, "notes"));
+        assert!(score(&tagged, "notes") > score(&nicknamed, "notes"));
+
+        // 所有搜索词都必须匹配，# 开头的词只匹配标签
+        assert!(score_entry(&plain, "notes missing").is_none());
+        assert!(score_entry(&plain, "#notes").is_none());
+        assert_eq!(score(&tagged, "#notes"), Some(BONUS_EXACT_TAG));
+        // 上级标签包含所有下级
+        let nested = entry("misc", None, &["#Project/Alpha"]);
+        assert_eq!(score(&nested, "#project"), Some(BONUS_EXACT_TAG));
+        assert!(score(&nested, "#proj").is_some_and(|s| s < BONUS_EXACT_TAG));
+
+        let m = score_entry(&nicknamed, "note").unwrap();
+        assert!(score_phrase(&nicknamed, "NOTE").is_some());
+        assert!(score_phrase(&nicknamed, "nte").is_none());
+        assert_eq!(m.nickname, vec![0..4]);
+        assert!(m.name.is_empty());
+    }
+}
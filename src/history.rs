@@ -0,0 +1,430 @@
+use crate::atomic_file;
+use crate::config::UserData;
+use crate::file_entry::FileEntry;
+use crate::tags::TagRegistry;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// 撤销栈最多保留的操作数
+pub const HISTORY_LIMIT: usize = 100;
+
+/// 对条目列表的单个修改，按顺序执行即可从修改前得到修改后
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Change {
+    Insert {
+        index: usize,
+        entry: FileEntry,
+    },
+    Remove {
+        index: usize,
+        entry: FileEntry,
+    },
+    Update {
+        index: usize,
+        before: Box<FileEntry>,
+        after: Box<FileEntry>,
+    },
+    /// 调整顺序，记录调整前后的ID顺序
+    Reorder {
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+}
+
+impl Change {
+    fn inverse(&self) -> Change {
+        match self {
+            Change::Insert { index, entry } => Change::Remove {
+                index: *index,
+                entry: entry.clone(),
+            },
+            Change::Remove { index, entry } => Change::Insert {
+                index: *index,
+                entry: entry.clone(),
+            },
+            Change::Update {
+                index,
+                before,
+                after,
+            } => Change::Update {
+                index: *index,
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Change::Reorder { before, after } => Change::Reorder {
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+
+    /// 执行修改；索引与当前列表对不上时（例如数据被外部修改过）按ID定位
+    fn apply(&self, entries: &mut Vec<FileEntry>) {
+        let locate = |entries: &[FileEntry], index: usize, id: &str| {
+            if entries.get(index).is_some_and(|e| e.id == id) {
+                Some(index)
+            } else {
+                entries.iter().position(|e| e.id == id)
+            }
+        };
+
+        match self {
+            Change::Insert { index, entry } => {
+                if !entries.iter().any(|e| e.id == entry.id) {
+                    entries.insert((*index).min(entries.len()), entry.clone());
+                }
+            }
+            Change::Remove { index, entry } => {
+                if let Some(position) = locate(entries, *index, &entry.id) {
+                    entries.remove(position);
+                }
+            }
+            Change::Update {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(position) = locate(entries, *index, &before.id) {
+                    entries[position] = (**after).clone();
+                }
+            }
+            Change::Reorder { after, .. } => {
+                let order: HashMap<&str, usize> = after
+                    .iter()
+                    .enumerate()
+                    .map(|(i, id)| (id.as_str(), i))
+                    .collect();
+                entries.sort_by_key(|e| order.get(e.id.as_str()).copied().unwrap_or(usize::MAX));
+            }
+        }
+    }
+}
+
+/// 随条目一起撤销的标签设置：别名和显示信息
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+struct TagMetadata {
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    registry: TagRegistry,
+}
+
+impl TagMetadata {
+    fn of(user_data: &UserData) -> Self {
+        Self {
+            aliases: user_data.tag_aliases.clone(),
+            registry: user_data.tag_registry.clone(),
+        }
+    }
+
+    fn restore(&self, user_data: &mut UserData) {
+        user_data.tag_aliases = self.aliases.clone();
+        user_data.tag_registry = self.registry.clone();
+    }
+}
+
+/// 一次用户操作（可撤销的最小单位）
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Command {
+    pub label: String,
+    changes: Vec<Change>,
+    /// 标签设置修改前后的内容，没有修改时为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tags: Option<Box<(TagMetadata, TagMetadata)>>,
+}
+
+impl Command {
+    /// 比较修改前后的条目列表和标签设置生成操作，没有变化时返回 None
+    pub fn from_diff(
+        label: &str,
+        before: &[FileEntry],
+        after: &[FileEntry],
+        data_before: &UserData,
+        data_after: &UserData,
+    ) -> Option<Self> {
+        let changes = diff_entries(before, after);
+        let tags = (TagMetadata::of(data_before), TagMetadata::of(data_after));
+        let tags = (tags.0 != tags.1).then(|| Box::new(tags));
+        if changes.is_empty() && tags.is_none() {
+            None
+        } else {
+            Some(Self {
+                label: label.to_string(),
+                changes,
+                tags,
+            })
+        }
+    }
+
+    pub fn undo(&self, entries: &mut Vec<FileEntry>, user_data: &mut UserData) {
+        for change in self.changes.iter().rev() {
+            change.inverse().apply(entries);
+        }
+        if let Some(tags) = &self.tags {
+            tags.0.restore(user_data);
+        }
+    }
+
+    pub fn redo(&self, entries: &mut Vec<FileEntry>, user_data: &mut UserData) {
+        for change in &self.changes {
+            change.apply(entries);
+        }
+        if let Some(tags) = &self.tags {
+            tags.1.restore(user_data);
+        }
+    }
+}
+
+/// 计算把 `before` 变成 `after` 的修改序列：先删除，再更新，最后插入（必要时调整顺序）
+fn diff_entries(before: &[FileEntry], after: &[FileEntry]) -> Vec<Change> {
+    let after_map: HashMap<&str, &FileEntry> = after.iter().map(|e| (e.id.as_str(), e)).collect();
+    let mut work: Vec<FileEntry> = before.to_vec();
+    let mut changes = Vec::new();
+
+    for index in (0..work.len()).rev() {
+        if !after_map.contains_key(work[index].id.as_str()) {
+            let entry = work.remove(index);
+            changes.push(Change::Remove { index, entry });
+        }
+    }
+
+    for (index, entry) in work.iter_mut().enumerate() {
+        let updated = after_map[entry.id.as_str()];
+        if entry != updated {
+            changes.push(Change::Update {
+                index,
+                before: Box::new(entry.clone()),
+                after: Box::new(updated.clone()),
+            });
+            *entry = updated.clone();
+        }
+    }
+
+    let kept: HashSet<String> = work.iter().map(|e| e.id.clone()).collect();
+    let same_order = work
+        .iter()
+        .map(|e| &e.id)
+        .eq(after.iter().map(|e| &e.id).filter(|id| kept.contains(*id)));
+
+    if same_order {
+        // 按最终位置从前往后插入，插入时前面的条目都已就位
+        for (index, entry) in after.iter().enumerate() {
+            if !kept.contains(&entry.id) {
+                changes.push(Change::Insert {
+                    index,
+                    entry: entry.clone(),
+                });
+            }
+        }
+    } else {
+        for entry in after.iter().filter(|e| !kept.contains(&e.id)) {
+            changes.push(Change::Insert {
+                index: work.len(),
+                entry: entry.clone(),
+            });
+            work.push(entry.clone());
+        }
+        changes.push(Change::Reorder {
+            before: work.iter().map(|e| e.id.clone()).collect(),
+            after: after.iter().map(|e| e.id.clone()).collect(),
+        });
+    }
+
+    changes
+}
+
+/// 撤销/重做栈
+#[derive(Serialize, Deserialize, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    /// 记录新操作，会清空重做栈
+    pub fn record(&mut self, command: Command) {
+        self.undo.push(command);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn take_undo(&mut self) -> Option<Command> {
+        self.undo.pop()
+    }
+
+    pub fn take_redo(&mut self) -> Option<Command> {
+        self.redo.pop()
+    }
+
+    /// 撤销完成后放入重做栈
+    pub fn push_redo(&mut self, command: Command) {
+        self.redo.push(command);
+    }
+
+    /// 重做完成后放回撤销栈（不清空重做栈）
+    pub fn push_undo(&mut self, command: Command) {
+        self.undo.push(command);
+    }
+
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo.last().map(|c| c.label.as_str())
+    }
+
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo.last().map(|c| c.label.as_str())
+    }
+
+    /// 持久化历史的文件路径，例如 `data.json.history`
+    pub fn journal_path(data_path: &Path) -> PathBuf {
+        let mut name = data_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".history");
+        data_path.with_file_name(name)
+    }
+
+    /// 读取持久化的历史，文件不存在或无法解析时返回空历史
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| format!("序列化历史失败: {}", e))?;
+        atomic_file::write_atomic(path, json.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags;
+    use crate::test_support::entry;
+
+    fn ids(entries: &[FileEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.id.as_str()).collect()
+    }
+
+    fn diff(before: &[FileEntry], after: &[FileEntry]) -> Option<Command> {
+        let data = UserData::default();
+        Command::from_diff("test", before, after, &data, &data)
+    }
+
+    fn assert_round_trip(before: Vec<FileEntry>, after: Vec<FileEntry>) {
+        let command = diff(&before, &after).unwrap();
+        let mut data = UserData::default();
+
+        let mut entries = before.clone();
+        command.redo(&mut entries, &mut data);
+        assert!(entries == after, "redo 结果不一致: {:?}", ids(&entries));
+
+        command.undo(&mut entries, &mut data);
+        assert!(entries == before, "undo 结果不一致: {:?}", ids(&entries));
+    }
+
+    #[test]
+    fn test_insert_remove_update_round_trip() {
+        let before = vec![entry("a", "A"), entry("b", "B"), entry("c", "C")];
+        let after = vec![
+            entry("x", "X"),
+            entry("a", "A2"),
+            entry("c", "C"),
+            entry("y", "Y"),
+        ];
+        assert_round_trip(before, after);
+    }
+
+    #[test]
+    fn test_reorder_round_trip() {
+        let before = vec![entry("a", "A"), entry("b", "B"), entry("c", "C")];
+        let after = vec![entry("c", "C"), entry("new", "N"), entry("a", "A")];
+        assert_round_trip(before, after);
+    }
+
+    #[test]
+    fn test_replace_import_round_trip() {
+        let before = vec![entry("a", "A"), entry("b", "B")];
+        let after = vec![entry("x", "X"), entry("y", "Y"), entry("z", "Z")];
+        assert_round_trip(before, after);
+    }
+
+    #[test]
+    fn test_no_change_records_nothing() {
+        let entries = vec![entry("a", "A")];
+        assert!(diff(&entries, &entries).is_none());
+    }
+
+    #[test]
+    fn test_tag_registry_round_trip() {
+        let mut before = UserData::default();
+        before
+            .tag_registry
+            .entry("#wrok".to_string())
+            .or_default()
+            .color = Some([200, 0, 0]);
+        let mut after = before.clone();
+        tags::move_info(&mut after.tag_registry, "#wrok", "#work");
+
+        let command = Command::from_diff(
+            "合并标签",
+            &[entry("a", "A")],
+            &[entry("a", "A2")],
+            &before,
+            &after,
+        )
+        .unwrap();
+        let mut entries = vec![entry("a", "A2")];
+        let mut data = after.clone();
+        command.undo(&mut entries, &mut data);
+        assert!(data.tag_registry.contains_key("#wrok"));
+        assert!(!data.tag_registry.contains_key("#work"));
+    }
+
+    #[test]
+    fn test_tag_aliases_round_trip() {
+        let before = UserData::default();
+        let mut after = before.clone();
+        after
+            .tag_aliases
+            .insert("#proj".to_string(), "#project".to_string());
+
+        // 只修改了别名也记为一次操作
+        let entries = vec![entry("a", "A")];
+        let command = Command::from_diff("添加标签别名", &entries, &entries, &before, &after);
+        let command = command.unwrap();
+
+        let mut data = after.clone();
+        let mut undone = entries.clone();
+        command.undo(&mut undone, &mut data);
+        assert!(data.tag_aliases.is_empty());
+        command.redo(&mut undone, &mut data);
+        assert_eq!(data.tag_aliases, after.tag_aliases);
+    }
+
+    #[test]
+    fn test_history_stacks_and_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = History::journal_path(&dir.path().join("data.json"));
+
+        let mut history = History::default();
+        let data = UserData::default();
+        let command =
+            Command::from_diff("删除条目", &[entry("a", "A")], &[], &data, &data).unwrap();
+        history.record(command);
+        history.save(&journal).unwrap();
+
+        let mut restored = History::load(&journal);
+        assert_eq!(restored.undo_label(), Some("删除条目"));
+        let command = restored.take_undo().unwrap();
+        let mut entries = Vec::new();
+        command.undo(&mut entries, &mut UserData::default());
+        assert_eq!(ids(&entries), vec!["a"]);
+
+        restored.push_redo(command);
+        assert_eq!(restored.redo_label(), Some("删除条目"));
+        assert!(restored.undo_label().is_none());
+    }
+}
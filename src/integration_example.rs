@@ -1,6 +1,6 @@
 use crate::async_ops::{AsyncOperationBuilder, AsyncOperationManager};
 use crate::file_entry::FileEntry;
-use crate::plugins::{BackupPlugin, PluginManager, SearchPlugin};
+use crate::plugins::{AutoTagPlugin, BackupPlugin, PluginManager, SearchPlugin};
 use crate::state::{AppState, StateEvent, StateManager};
 use std::path::PathBuf;
 
@@ -47,6 +47,10 @@ impl IntegratedFileManager {
             .register_plugin(Box::new(BackupPlugin::default()))
             .map_err(|e| format!("注册备份插件失败: {}", e))?;
 
+        plugin_manager
+            .register_plugin(Box::new(AutoTagPlugin::default()))
+            .map_err(|e| format!("注册自动标签插件失败: {}", e))?;
+
         // 初始化完成，转换状态
         state_manager.handle_event(StateEvent::InitializationComplete)?;
 
@@ -546,12 +550,13 @@ mod examples {
 
         // 获取插件列表
         let plugins = manager.plugin_manager.get_plugin_list();
-        assert_eq!(plugins.len(), 2); // 搜索插件和备份插件
+        assert_eq!(plugins.len(), 3); // 搜索插件、备份插件和自动标签插件
 
         // 检查插件名称
         let plugin_names: Vec<String> = plugins.iter().map(|p| p.name.clone()).collect();
         assert!(plugin_names.contains(&"Search Plugin".to_string()));
         assert!(plugin_names.contains(&"Backup Plugin".to_string()));
+        assert!(plugin_names.contains(&"Auto Tag Plugin".to_string()));
     }
 
     /// 状态管理示例
@@ -0,0 +1,311 @@
+use crate::file_entry::FileEntry;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 变更类型
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Updated,
+    Removed,
+}
+
+/// 变更日志中的一条记录，对应一个条目的一次修改
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JournalEvent {
+    pub ts: DateTime<Utc>,
+    /// 执行修改的用户和机器，例如 `alice@laptop`
+    pub actor: String,
+    pub entry_id: String,
+    pub kind: ChangeKind,
+    /// 删除前条目所在的位置，恢复时放回原处
+    #[serde(default)]
+    pub index: Option<usize>,
+    pub before: Option<FileEntry>,
+    pub after: Option<FileEntry>,
+}
+
+impl JournalEvent {
+    /// 条目在该次修改后的名称（删除时为删除前的名称）
+    pub fn entry_name(&self) -> String {
+        self.after
+            .as_ref()
+            .or(self.before.as_ref())
+            .map(|e| e.nickname.clone().unwrap_or_else(|| e.name.clone()))
+            .unwrap_or_default()
+    }
+
+    /// 简要描述本次修改了哪些字段
+    pub fn summary(&self) -> String {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => {
+                let mut fields = Vec::new();
+                if before.name != after.name {
+                    fields.push(format!("名称: {} → {}", before.name, after.name));
+                }
+                if before.nickname != after.nickname {
+                    fields.push(format!(
+                        "别名: {} → {}",
+                        before.nickname.as_deref().unwrap_or("无"),
+                        after.nickname.as_deref().unwrap_or("无")
+                    ));
+                }
+                if before.path != after.path {
+                    fields.push(format!(
+                        "路径: {} → {}",
+                        before.path.display(),
+                        after.path.display()
+                    ));
+                }
+                if before.url != after.url {
+                    fields.push("链接".to_string());
+                }
+                if before.description != after.description {
+                    fields.push("描述".to_string());
+                }
+                if before.tags != after.tags {
+                    fields.push(format!(
+                        "标签: [{}] → [{}]",
+                        before.tags.join(" "),
+                        after.tags.join(" ")
+                    ));
+                }
+                if before.smart_query != after.smart_query {
+                    fields.push(format!(
+                        "搜索条件: {} → {}",
+                        before.smart_query.as_deref().unwrap_or("无"),
+                        after.smart_query.as_deref().unwrap_or("无")
+                    ));
+                }
+                if before.child_entries != after.child_entries {
+                    fields.push(format!(
+                        "子项目: {} → {}",
+                        before.child_entries.len(),
+                        after.child_entries.len()
+                    ));
+                }
+                if fields.is_empty() {
+                    "其他字段".to_string()
+                } else {
+                    fields.join("；")
+                }
+            }
+            (None, Some(after)) => format!("添加 {}", after.path.display()),
+            (Some(before), None) => format!("删除 {}", before.path.display()),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// 日志文件路径，例如 `data.json.journal.jsonl`
+pub fn journal_path(data_path: &Path) -> PathBuf {
+    let mut name = data_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".journal.jsonl");
+    data_path.with_file_name(name)
+}
+
+/// 当前用户和机器名
+pub fn current_actor() -> String {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    let host = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| {
+            fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|h| h.trim().to_string())
+        })
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!("{}@{}", user, host)
+}
+
+/// 比较保存前后的条目，为每个变化的条目生成一条记录
+pub fn diff_events(
+    before: &[FileEntry],
+    after: &[FileEntry],
+    actor: &str,
+    ts: DateTime<Utc>,
+) -> Vec<JournalEvent> {
+    let before_map: HashMap<&str, (usize, &FileEntry)> = before
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.id.as_str(), (i, e)))
+        .collect();
+    let after_map: HashMap<&str, &FileEntry> = after.iter().map(|e| (e.id.as_str(), e)).collect();
+
+    let event =
+        |entry_id: &str, kind, index, before: Option<&FileEntry>, after: Option<&FileEntry>| {
+            JournalEvent {
+                ts,
+                actor: actor.to_string(),
+                entry_id: entry_id.to_string(),
+                kind,
+                index,
+                before: before.cloned(),
+                after: after.cloned(),
+            }
+        };
+
+    let mut events = Vec::new();
+    for (index, old) in before.iter().enumerate() {
+        if !after_map.contains_key(old.id.as_str()) {
+            events.push(event(
+                &old.id,
+                ChangeKind::Removed,
+                Some(index),
+                Some(old),
+                None,
+            ));
+        }
+    }
+    for new in after {
+        match before_map.get(new.id.as_str()) {
+            None => events.push(event(&new.id, ChangeKind::Added, None, None, Some(new))),
+            // 只有打开时间和次数变化的不记录
+            Some((_, old)) if !old.same_content(new) => events.push(event(
+                &new.id,
+                ChangeKind::Updated,
+                None,
+                Some(old),
+                Some(new),
+            )),
+            Some(_) => {}
+        }
+    }
+    events
+}
+
+/// 追加记录到日志文件（只追加，从不改写已有内容）
+pub fn append(path: &Path, events: &[JournalEvent]) -> Result<(), String> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines = String::new();
+    for event in events {
+        let line = serde_json::to_string(event).map_err(|e| format!("序列化日志失败: {}", e))?;
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("打开变更日志失败: {}", e))?;
+    file.write_all(lines.as_bytes())
+        .map_err(|e| format!("写入变更日志失败: {}", e))
+}
+
+/// 读取全部记录，无法解析的行（例如写入中断留下的半行）会被跳过
+pub fn read_events(path: &Path) -> Vec<JournalEvent> {
+    fs::read_to_string(path)
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 从当前条目出发倒放 `at` 之后的记录，得到资料库在 `at` 时刻的状态
+pub fn state_at(
+    current: &[FileEntry],
+    events: &[JournalEvent],
+    at: DateTime<Utc>,
+) -> Vec<FileEntry> {
+    let mut entries = current.to_vec();
+
+    for event in events.iter().rev().filter(|e| e.ts > at) {
+        let position = entries.iter().position(|e| e.id == event.entry_id);
+        match event.kind {
+            ChangeKind::Added => {
+                if let Some(position) = position {
+                    entries.remove(position);
+                }
+            }
+            ChangeKind::Removed => {
+                if let (None, Some(before)) = (position, &event.before) {
+                    let index = event.index.unwrap_or(entries.len()).min(entries.len());
+                    entries.insert(index, before.clone());
+                }
+            }
+            ChangeKind::Updated => {
+                if let Some(before) = &event.before {
+                    match position {
+                        Some(position) => entries[position] = before.clone(),
+                        None => entries.push(before.clone()),
+                    }
+                }
+            }
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::entry;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_diff_events() {
+        let before = vec![entry("a", "A"), entry("b", "B")];
+        let after = vec![entry("a", "A2"), entry("c", "C")];
+
+        let events = diff_events(&before, &after, "me@host", at(1));
+        let kinds: Vec<(&str, ChangeKind)> = events
+            .iter()
+            .map(|e| (e.entry_id.as_str(), e.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("b", ChangeKind::Removed),
+                ("a", ChangeKind::Updated),
+                ("c", ChangeKind::Added)
+            ]
+        );
+        assert_eq!(events[0].index, Some(1));
+        assert!(events[1].summary().contains("A → A2"));
+    }
+
+    #[test]
+    fn test_append_and_restore_as_of() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = journal_path(&dir.path().join("data.json"));
+
+        let v1 = vec![entry("a", "A"), entry("b", "B")];
+        let v2 = vec![entry("a", "A2"), entry("b", "B")];
+        let v3 = vec![entry("a", "A2"), entry("c", "C")];
+
+        append(&path, &diff_events(&[], &v1, "me@host", at(1))).unwrap();
+        append(&path, &diff_events(&v1, &v2, "me@host", at(2))).unwrap();
+        append(&path, &diff_events(&v2, &v3, "me@host", at(3))).unwrap();
+
+        let events = read_events(&path);
+        assert_eq!(events.len(), 5);
+
+        let names = |entries: Vec<FileEntry>| -> Vec<String> {
+            entries.into_iter().map(|e| e.name).collect()
+        };
+        assert_eq!(names(state_at(&v3, &events, at(3))), vec!["A2", "C"]);
+        assert_eq!(names(state_at(&v3, &events, at(2))), vec!["A2", "B"]);
+        assert_eq!(names(state_at(&v3, &events, at(1))), vec!["A", "B"]);
+        assert!(state_at(&v3, &events, at(0)).is_empty());
+    }
+}
@@ -0,0 +1,585 @@
+//! 启动条目：打开文件、文件夹和网页，运行命令并记录输出，集合在后台线程中按顺序和延迟
+//! 依次启动，界面不会卡住
+
+use crate::file_entry::{CommandSpec, EntryType, FileEntry, LaunchMode};
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// 可以直接启动的目标
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Url(String),
+    Path(PathBuf),
+    /// 命令条目：条目名称和要运行的程序
+    Command(String, CommandSpec),
+}
+
+impl Target {
+    /// 条目对应的启动目标；集合没有单独的目标
+    pub fn of(entry: &FileEntry) -> Option<Self> {
+        match entry.entry_type {
+            EntryType::WebLink => entry.url.clone().map(Target::Url),
+            EntryType::Collection => None,
+            EntryType::Command => entry
+                .command
+                .clone()
+                .map(|spec| Target::Command(entry.name.clone(), spec)),
+            _ => Some(Target::Path(entry.path.clone())),
+        }
+    }
+
+    /// 启动目标，命令的运行情况记录到 `log`
+    pub fn open(&self, log: &RunLog) {
+        match self {
+            Target::Url(url) => open_url(url),
+            Target::Path(path) => open_path(path),
+            Target::Command(name, spec) => run_command(name, spec, log),
+        }
+    }
+}
+
+fn open_path(path: &Path) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = std::process::Command::new("explorer").arg(path).spawn();
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let _ = std::process::Command::new("open").arg(path).spawn();
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let _ = std::process::Command::new("xdg-open").arg(path).spawn();
+    }
+}
+
+fn open_url(url: &str) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = std::process::Command::new("cmd")
+            .args(["/C", "start", url])
+            .spawn();
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let _ = std::process::Command::new("open").arg(url).spawn();
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let _ = std::process::Command::new("xdg-open").arg(url).spawn();
+    }
+}
+
+/// 每条运行记录最多保留的输出行数，超出时丢弃最早的行
+const MAX_OUTPUT_LINES: usize = 5000;
+/// 最多保留的运行记录数，超出时丢弃最早的已结束记录
+const MAX_RUNS: usize = 50;
+
+/// 命令的运行状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum RunStatus {
+    Running,
+    /// 已结束；没有退出码时表示被信号终止
+    Exited(Option<i32>),
+    /// 无法启动
+    Failed(String),
+    /// 已在终端窗口中启动，输出和退出状态不在此记录
+    InTerminal,
+}
+
+impl RunStatus {
+    pub fn label(&self) -> String {
+        match self {
+            RunStatus::Running => "运行中".to_string(),
+            RunStatus::Exited(Some(0)) => "成功".to_string(),
+            RunStatus::Exited(Some(code)) => format!("退出码 {}", code),
+            RunStatus::Exited(None) => "被终止".to_string(),
+            RunStatus::Failed(error) => format!("启动失败: {}", error),
+            RunStatus::InTerminal => "已在终端中启动".to_string(),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, RunStatus::Exited(Some(0)) | RunStatus::InTerminal)
+    }
+}
+
+/// 一行输出
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputLine {
+    pub stderr: bool,
+    pub text: String,
+}
+
+/// 一次命令运行
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub id: u64,
+    /// 条目名称
+    pub name: String,
+    pub command_line: String,
+    pub started_at: DateTime<Local>,
+    pub finished_at: Option<DateTime<Local>>,
+    pub status: RunStatus,
+    pub output: VecDeque<OutputLine>,
+    /// 因超出行数被丢弃的输出行数
+    pub dropped_lines: usize,
+}
+
+/// 命令的运行记录，由启动线程和界面共享
+#[derive(Clone, Default)]
+pub struct RunLog {
+    runs: Arc<Mutex<Vec<Run>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl RunLog {
+    /// 运行记录，按开始时间排列
+    pub fn runs(&self) -> MutexGuard<'_, Vec<Run>> {
+        // 记录线程不会在持锁时出错，锁被污染时照常使用其中的数据
+        self.runs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.runs()
+            .iter()
+            .any(|run| run.status == RunStatus::Running)
+    }
+
+    /// 删除已结束的记录
+    pub fn clear_finished(&self) {
+        self.runs().retain(|run| run.status == RunStatus::Running);
+    }
+
+    fn start(&self, name: &str, spec: &CommandSpec) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut runs = self.runs();
+        if runs.len() >= MAX_RUNS
+            && let Some(oldest) = runs.iter().position(|run| run.status != RunStatus::Running)
+        {
+            runs.remove(oldest);
+        }
+        runs.push(Run {
+            id,
+            name: name.to_string(),
+            command_line: command_line(spec),
+            started_at: Local::now(),
+            finished_at: None,
+            status: RunStatus::Running,
+            output: VecDeque::new(),
+            dropped_lines: 0,
+        });
+        id
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Run)) {
+        if let Some(run) = self.runs().iter_mut().find(|run| run.id == id) {
+            f(run);
+        }
+    }
+
+    fn push_line(&self, id: u64, stderr: bool, text: String) {
+        self.update(id, |run| {
+            if run.output.len() >= MAX_OUTPUT_LINES {
+                run.output.pop_front();
+                run.dropped_lines += 1;
+            }
+            run.output.push_back(OutputLine { stderr, text });
+        });
+    }
+
+    fn finish(&self, id: u64, status: RunStatus) {
+        self.update(id, |run| {
+            run.status = status;
+            run.finished_at = Some(Local::now());
+        });
+    }
+}
+
+/// 启动命令：在终端中运行时只负责打开终端，否则在后台线程中收集输出并等待退出
+fn run_command(name: &str, spec: &CommandSpec, log: &RunLog) {
+    let id = log.start(name, spec);
+    if spec.in_terminal {
+        let status = match terminal_command(spec).spawn() {
+            Ok(_) => RunStatus::InTerminal,
+            Err(e) => RunStatus::Failed(e.to_string()),
+        };
+        log.finish(id, status);
+        return;
+    }
+
+    let mut command = direct_command(spec);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            log.finish(id, RunStatus::Failed(e.to_string()));
+            return;
+        }
+    };
+
+    let readers: Vec<_> = [
+        child
+            .stdout
+            .take()
+            .map(|out| (false, Box::new(out) as Box<dyn Read + Send>)),
+        child
+            .stderr
+            .take()
+            .map(|err| (true, Box::new(err) as Box<dyn Read + Send>)),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|(stderr, stream)| {
+        let log = log.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).split(b'\n').map_while(Result::ok) {
+                let text = String::from_utf8_lossy(&line)
+                    .trim_end_matches('\r')
+                    .to_string();
+                log.push_line(id, stderr, text);
+            }
+        })
+    })
+    .collect();
+
+    let log = log.clone();
+    std::thread::spawn(move || {
+        for reader in readers {
+            let _ = reader.join();
+        }
+        let status = match child.wait() {
+            Ok(status) => RunStatus::Exited(status.code()),
+            Err(e) => RunStatus::Failed(e.to_string()),
+        };
+        log.finish(id, status);
+    });
+}
+
+/// 直接运行程序的命令，`~/` 开头的程序和工作目录按主目录展开
+fn direct_command(spec: &CommandSpec) -> Command {
+    let mut command = Command::new(expand_home(Path::new(&spec.program)));
+    command.args(&spec.args).envs(&spec.env);
+    if let Some(dir) = &spec.working_dir {
+        command.current_dir(expand_home(dir));
+    }
+    command
+}
+
+/// 在新的终端窗口中运行程序的命令
+fn terminal_command(spec: &CommandSpec) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        let mut command = Command::new("cmd");
+        command
+            .args(["/C", "start", "", "cmd", "/K"])
+            .arg(expand_home(Path::new(&spec.program)))
+            .args(&spec.args)
+            .envs(&spec.env);
+        if let Some(dir) = &spec.working_dir {
+            command.current_dir(expand_home(dir));
+        }
+        command
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // Terminal 中的 shell 不继承启动器的环境，目录和环境变量写在命令行里
+        let mut script = String::new();
+        if let Some(dir) = &spec.working_dir {
+            script.push_str(&format!(
+                "cd {} && ",
+                shell_quote(&expand_home(dir).to_string_lossy())
+            ));
+        }
+        if !spec.env.is_empty() {
+            script.push_str("env ");
+            for (key, value) in &spec.env {
+                script.push_str(&shell_quote(&format!("{}={}", key, value)));
+                script.push(' ');
+            }
+        }
+        script.push_str(&shell_quote(
+            &expand_home(Path::new(&spec.program)).to_string_lossy(),
+        ));
+        for arg in &spec.args {
+            script.push(' ');
+            script.push_str(&shell_quote(arg));
+        }
+        let script = script.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut command = Command::new("osascript");
+        command
+            .arg("-e")
+            .arg(format!(
+                "tell application \"Terminal\" to do script \"{}\"",
+                script
+            ))
+            .arg("-e")
+            .arg("tell application \"Terminal\" to activate");
+        command
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // 使用 $TERMINAL 指定的终端，没有时使用系统默认终端
+        let terminal =
+            std::env::var("TERMINAL").unwrap_or_else(|_| "x-terminal-emulator".to_string());
+        let mut command = Command::new(terminal);
+        command
+            .arg("-e")
+            .arg(expand_home(Path::new(&spec.program)))
+            .args(&spec.args)
+            .envs(&spec.env);
+        if let Some(dir) = &spec.working_dir {
+            command.current_dir(expand_home(dir));
+        }
+        command
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~")
+        && let Some(home) = dirs::home_dir()
+    {
+        return home.join(rest);
+    }
+    path.to_path_buf()
+}
+
+/// 按 POSIX shell 的规则加单引号
+#[cfg(any(target_os = "macos", test))]
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// 显示用的完整命令行
+pub fn command_line(spec: &CommandSpec) -> String {
+    let mut words = vec![spec.program.clone()];
+    words.extend(spec.args.iter().cloned());
+    join_args(&words)
+}
+
+/// 按空白拆分参数，单引号或双引号括起的部分作为一个整体（可以连接其他字符），
+/// 引号中不处理转义，以免 Windows 路径中的 `\` 被改写
+pub fn split_args(input: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in input.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => args.extend(current.take()),
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("缺少结束的引号 {}", q));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// 把参数拼回可由 [`split_args`] 拆分的文本，含空白或引号的参数加上引号
+pub fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && !arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'')
+            {
+                arg.clone()
+            } else if arg.contains('"') {
+                format!("'{}'", arg)
+            } else {
+                format!("\"{}\"", arg)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 解析每行一个的 `KEY=VALUE` 环境变量，忽略空行和 `#` 开头的注释
+pub fn parse_env(input: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut env = BTreeMap::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("第 {} 行缺少 =: {}", number + 1, line));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("第 {} 行的变量名无效: {}", number + 1, line));
+        }
+        env.insert(key.to_string(), value.to_string());
+    }
+    Ok(env)
+}
+
+/// 把环境变量写成 [`parse_env`] 的格式
+pub fn format_env(env: &BTreeMap<String, String>) -> String {
+    env.iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 每个目标相对开始时的启动时间：依次打开时延迟逐项累加，同时打开时各自从开始计算。
+/// 延迟来自数据文件，累加时饱和而不溢出
+pub fn schedule(delays: &[Duration], mode: LaunchMode) -> Vec<Duration> {
+    match mode {
+        LaunchMode::Parallel => delays.to_vec(),
+        LaunchMode::Sequential | LaunchMode::FirstOnly => delays
+            .iter()
+            .scan(Duration::ZERO, |at, delay| {
+                *at = at.saturating_add(*delay);
+                Some(*at)
+            })
+            .collect(),
+    }
+}
+
+/// 在后台线程中按 `mode` 启动目标，`steps` 为每个目标的延迟
+pub fn launch(steps: Vec<(Duration, Target)>, mode: LaunchMode, log: &RunLog) {
+    if steps.is_empty() {
+        return;
+    }
+    let delays: Vec<Duration> = steps.iter().map(|(delay, _)| *delay).collect();
+    let mut plan: Vec<(Duration, Target)> = schedule(&delays, mode)
+        .into_iter()
+        .zip(steps.into_iter().map(|(_, target)| target))
+        .collect();
+    plan.sort_by_key(|(at, _)| *at);
+
+    let log = log.clone();
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        for (at, target) in plan {
+            if let Some(wait) = at.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+            target.open(&log);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let ms = Duration::from_millis;
+        let delays = [ms(50), ms(1000), ms(0)];
+        assert_eq!(
+            schedule(&delays, LaunchMode::Sequential),
+            vec![ms(50), ms(1050), ms(1050)]
+        );
+        assert_eq!(schedule(&delays, LaunchMode::Parallel), delays.to_vec());
+
+        let huge = [Duration::MAX, ms(1)];
+        assert_eq!(
+            schedule(&huge, LaunchMode::Sequential),
+            vec![Duration::MAX, Duration::MAX]
+        );
+    }
+
+    #[test]
+    fn test_split_and_join_args() {
+        let args = split_args(r#"build --release "my file.txt" --name='a "b"' C:\tools\"#).unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "build",
+                "--release",
+                "my file.txt",
+                r#"--name=a "b""#,
+                r"C:\tools\"
+            ]
+        );
+        assert_eq!(split_args(&join_args(&args)).unwrap(), args);
+        assert_eq!(split_args(r#"a "" b"#).unwrap(), vec!["a", "", "b"]);
+        assert!(split_args(r#"echo "open"#).is_err());
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_parse_env() {
+        let env = parse_env("# 注释\nRUST_LOG=debug\n\n PATH_EXTRA = /opt/bin=x\n").unwrap();
+        assert_eq!(env["RUST_LOG"], "debug");
+        assert_eq!(env["PATH_EXTRA"], " /opt/bin=x");
+        assert_eq!(parse_env(&format_env(&env)).unwrap(), env);
+        assert!(parse_env("NO_VALUE").is_err());
+        assert!(parse_env("=x").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command_captures_output() {
+        let log = RunLog::default();
+        let spec = CommandSpec {
+            program: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "echo \"$GREETING\"; pwd; echo oops >&2; exit 3".to_string(),
+            ],
+            working_dir: Some(std::env::temp_dir()),
+            env: BTreeMap::from([("GREETING".to_string(), "你好".to_string())]),
+            in_terminal: false,
+        };
+        Target::Command("脚本".to_string(), spec).open(&log);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while log.is_running() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let runs = log.runs();
+        let run = &runs[0];
+        assert_eq!(run.status, RunStatus::Exited(Some(3)));
+        let stdout: Vec<&str> = run
+            .output
+            .iter()
+            .filter(|line| !line.stderr)
+            .map(|line| line.text.as_str())
+            .collect();
+        assert_eq!(stdout[0], "你好");
+        assert_eq!(
+            Path::new(stdout[1]).canonicalize().unwrap(),
+            std::env::temp_dir().canonicalize().unwrap()
+        );
+        assert!(run.output.contains(&OutputLine {
+            stderr: true,
+            text: "oops".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_missing_program_fails() {
+        let log = RunLog::default();
+        let spec = CommandSpec {
+            program: "/nonexistent/program".to_string(),
+            ..Default::default()
+        };
+        Target::Command("不存在".to_string(), spec).open(&log);
+        assert!(matches!(log.runs()[0].status, RunStatus::Failed(_)));
+    }
+}
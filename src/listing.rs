@@ -0,0 +1,270 @@
+use crate::file_entry::FileEntry;
+use crate::frecency;
+use crate::query;
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// 条目列表的排序方式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum SortOrder {
+    /// 按热度（打开频率和最近打开时间）
+    #[default]
+    Frecency,
+    /// 保存时的顺序（可手动上移/下移）
+    Manual,
+    RecentlyAdded,
+    RecentlyUpdated,
+    RecentlyOpened,
+    MostOpened,
+    Name,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 7] = [
+        SortOrder::Frecency,
+        SortOrder::Manual,
+        SortOrder::RecentlyAdded,
+        SortOrder::RecentlyUpdated,
+        SortOrder::RecentlyOpened,
+        SortOrder::MostOpened,
+        SortOrder::Name,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::Frecency => "热度",
+            SortOrder::Manual => "手动顺序",
+            SortOrder::RecentlyAdded => "最近添加",
+            SortOrder::RecentlyUpdated => "最近修改",
+            SortOrder::RecentlyOpened => "最近打开",
+            SortOrder::MostOpened => "打开次数",
+            SortOrder::Name => "名称",
+        }
+    }
+
+    /// 对条目索引排序；时间未知的条目排在最后，相同时保持原有顺序
+    ///
+    /// 除手动顺序外，置顶的条目总是排在最前。
+    pub fn sort(self, entries: &[FileEntry], indices: &mut [usize], half_life_days: f64) {
+        let newest_first = |a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>| match (a, b) {
+            (Some(a), Some(b)) => b.cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+
+        match self {
+            SortOrder::Frecency => {
+                let now = Utc::now();
+                indices.sort_by_cached_key(|&i| {
+                    // 分数非负，按位比较即可得到正确的顺序
+                    std::cmp::Reverse(frecency::score(&entries[i], now, half_life_days).to_bits())
+                })
+            }
+            SortOrder::Manual => return,
+            SortOrder::RecentlyAdded => {
+                indices.sort_by(|&a, &b| newest_first(entries[a].created_at, entries[b].created_at))
+            }
+            SortOrder::RecentlyUpdated => {
+                indices.sort_by(|&a, &b| newest_first(entries[a].updated_at, entries[b].updated_at))
+            }
+            SortOrder::RecentlyOpened => indices.sort_by(|&a, &b| {
+                newest_first(entries[a].last_opened_at, entries[b].last_opened_at)
+            }),
+            SortOrder::MostOpened => {
+                indices.sort_by(|&a, &b| entries[b].open_count.cmp(&entries[a].open_count))
+            }
+            SortOrder::Name => indices.sort_by_cached_key(|&i| {
+                let entry = &entries[i];
+                entry
+                    .nickname
+                    .as_ref()
+                    .unwrap_or(&entry.name)
+                    .to_lowercase()
+            }),
+        }
+        indices.sort_by_key(|&i| !entries[i].pinned);
+    }
+
+    /// 对搜索结果排序：按匹配得分从高到低，得分相同的按排序方式排列；
+    /// 与 `sort` 一样，除手动顺序外置顶的条目总是排在最前
+    pub fn sort_matches(
+        self,
+        entries: &[FileEntry],
+        indices: &mut [usize],
+        half_life_days: f64,
+        score: impl Fn(usize) -> i32,
+    ) {
+        self.sort(entries, indices, half_life_days);
+        let pin = self != SortOrder::Manual;
+        indices.sort_by_key(|&i| (pin && !entries[i].pinned, std::cmp::Reverse(score(i))));
+    }
+}
+
+/// 按时间筛选条目
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum TimeFilter {
+    #[default]
+    All,
+    AddedToday,
+    AddedThisWeek,
+    AddedThisMonth,
+    UpdatedThisWeek,
+    /// 一年以上没有打开（从未打开的也算在内）
+    NotOpenedForYear,
+    NeverOpened,
+}
+
+impl TimeFilter {
+    pub const ALL: [TimeFilter; 7] = [
+        TimeFilter::All,
+        TimeFilter::AddedToday,
+        TimeFilter::AddedThisWeek,
+        TimeFilter::AddedThisMonth,
+        TimeFilter::UpdatedThisWeek,
+        TimeFilter::NotOpenedForYear,
+        TimeFilter::NeverOpened,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimeFilter::All => "全部时间",
+            TimeFilter::AddedToday => "今天添加",
+            TimeFilter::AddedThisWeek => "最近7天添加",
+            TimeFilter::AddedThisMonth => "最近30天添加",
+            TimeFilter::UpdatedThisWeek => "最近7天修改",
+            TimeFilter::NotOpenedForYear => "一年未打开",
+            TimeFilter::NeverOpened => "从未打开",
+        }
+    }
+
+    pub fn matches(self, entry: &FileEntry, now: DateTime<Utc>) -> bool {
+        let within = |time: Option<DateTime<Utc>>, days: i64| {
+            time.is_some_and(|time| now - time <= Duration::days(days))
+        };
+
+        match self {
+            TimeFilter::All => true,
+            TimeFilter::AddedToday => {
+                let today = query::local_midnight(now.with_timezone(&Local).date_naive());
+                entry.created_at.is_some_and(|time| time >= today)
+            }
+            TimeFilter::AddedThisWeek => within(entry.created_at, 7),
+            TimeFilter::AddedThisMonth => within(entry.created_at, 30),
+            TimeFilter::UpdatedThisWeek => within(entry.updated_at, 7),
+            TimeFilter::NotOpenedForYear => !within(entry.last_opened_at, 365),
+            TimeFilter::NeverOpened => entry.open_count == 0,
+        }
+    }
+}
+
+/// 用于界面显示的本地时间，未知时显示“未知”
+pub fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|t| {
+        t.with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    })
+    .unwrap_or_else(|| "未知".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::file;
+    use chrono::TimeZone;
+
+    fn entry(name: &str, created_day: Option<u32>, open_count: u32) -> FileEntry {
+        let mut entry = file(name);
+        entry.created_at = created_day.map(|d| Utc.with_ymd_and_hms(2024, 5, d, 0, 0, 0).unwrap());
+        entry.updated_at = entry.created_at;
+        entry.open_count = open_count;
+        if open_count > 0 {
+            entry.last_opened_at = entry.created_at;
+        }
+        entry
+    }
+
+    #[test]
+    fn test_sort_orders() {
+        let entries = vec![
+            entry("b", Some(2), 5),
+            entry("old", None, 0),
+            entry("a", Some(10), 1),
+        ];
+        let sorted = |order: SortOrder| {
+            let mut indices: Vec<usize> = (0..entries.len()).collect();
+            order.sort(&entries, &mut indices, frecency::DEFAULT_HALF_LIFE_DAYS);
+            indices
+        };
+
+        assert_eq!(sorted(SortOrder::Manual), vec![0, 1, 2]);
+        assert_eq!(sorted(SortOrder::RecentlyAdded), vec![2, 0, 1]);
+        assert_eq!(sorted(SortOrder::MostOpened), vec![0, 2, 1]);
+        assert_eq!(sorted(SortOrder::Name), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn test_pinned_entries_come_first() {
+        let mut entries = vec![
+            entry("busy", Some(2), 50),
+            entry("pinned", None, 0),
+            entry("rare", Some(3), 1),
+        ];
+        entries[1].pinned = true;
+
+        let mut indices: Vec<usize> = (0..entries.len()).collect();
+        SortOrder::Frecency.sort(&entries, &mut indices, frecency::DEFAULT_HALF_LIFE_DAYS);
+        assert_eq!(indices, vec![1, 0, 2]);
+
+        let mut indices: Vec<usize> = (0..entries.len()).collect();
+        SortOrder::Manual.sort(&entries, &mut indices, frecency::DEFAULT_HALF_LIFE_DAYS);
+        assert_eq!(indices, vec![0, 1, 2]);
+
+        // 有搜索词时，得分最低的置顶条目仍排在最前，其余按得分排列
+        let scores = [10, 1, 30];
+        let mut indices: Vec<usize> = (0..entries.len()).collect();
+        SortOrder::Frecency.sort_matches(
+            &entries,
+            &mut indices,
+            frecency::DEFAULT_HALF_LIFE_DAYS,
+            |i| scores[i],
+        );
+        assert_eq!(indices, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_time_filters() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 12, 0, 0, 0).unwrap();
+        let recent = entry("recent", Some(10), 1);
+        let old = entry("old", Some(1), 0);
+        let unknown = entry("unknown", None, 0);
+
+        assert!(TimeFilter::AddedThisWeek.matches(&recent, now));
+        assert!(!TimeFilter::AddedThisWeek.matches(&old, now));
+        assert!(!TimeFilter::AddedThisMonth.matches(&unknown, now));
+        assert!(TimeFilter::AddedThisMonth.matches(&old, now));
+
+        assert!(!TimeFilter::NotOpenedForYear.matches(&recent, now));
+        assert!(TimeFilter::NotOpenedForYear.matches(&unknown, now));
+        assert!(TimeFilter::NeverOpened.matches(&old, now));
+    }
+
+    #[test]
+    fn test_added_today_starts_at_local_midnight() {
+        let midnight = query::local_midnight(chrono::NaiveDate::from_ymd_opt(2024, 5, 12).unwrap());
+        let now = midnight + Duration::hours(1);
+        let mut today = file("today");
+        today.created_at = Some(now - Duration::minutes(30));
+        // 不到24小时，但是昨天添加的
+        let mut yesterday = file("yesterday");
+        yesterday.created_at = Some(now - Duration::hours(2));
+
+        assert!(TimeFilter::AddedToday.matches(&today, now));
+        assert!(!TimeFilter::AddedToday.matches(&yesterday, now));
+        let mut unknown = file("unknown");
+        unknown.created_at = None;
+        assert!(!TimeFilter::AddedToday.matches(&unknown, now));
+    }
+}
@@ -2,12 +2,32 @@
 
 mod app;
 mod async_ops;
+mod atomic_file;
+mod auto_tag;
+mod collections;
 mod config;
+mod content_index;
+mod crypto;
 mod file_entry;
 mod fonts;
+mod frecency;
+mod fuzzy;
+mod history;
 mod integration_example;
+mod journal;
+mod launcher;
+mod listing;
+mod migration;
+mod paths;
 mod plugins;
+mod query;
+mod search_index;
 mod state;
+mod storage;
+mod sync;
+mod tags;
+#[cfg(test)]
+mod test_support;
 mod theme;
 
 use eframe::egui;
@@ -20,6 +40,35 @@ fn main() -> Result<(), eframe::Error> {
         println!("启动文件管理器...");
     }
 
+    // 命令行迁移：--migrate-to-sqlite <JSON数据文件> <SQLite数据库>
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--migrate-to-sqlite") {
+        std::process::exit(run_sqlite_migration(&args[2..]));
+    }
+
+    // --library <名称>：启动时打开指定资料库
+    let library = args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--library" {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix("--library=").map(str::to_string)
+        }
+    });
+
+    // 把旧版本放在程序目录下的配置和数据迁移到新的默认位置
+    match paths::migrate_legacy_files() {
+        Ok(_moved) => {
+            #[cfg(debug_assertions)]
+            for path in &_moved {
+                println!("已迁移旧文件到: {}", path.display());
+            }
+        }
+        Err(_e) => {
+            #[cfg(debug_assertions)]
+            eprintln!("迁移旧文件失败: {}", _e);
+        }
+    }
+
     // 检查配置目录是否可访问
     if let Err(_e) = check_config_access() {
         #[cfg(debug_assertions)]
@@ -62,7 +111,7 @@ fn main() -> Result<(), eframe::Error> {
             setup_rendering(&cc.egui_ctx);
 
             // 创建应用实例
-            let app = app::FileManagerApp::new();
+            let app = app::FileManagerApp::with_library(library);
 
             #[cfg(debug_assertions)]
             println!("应用程序初始化完成");
@@ -90,17 +139,32 @@ fn setup_rendering(ctx: &egui::Context) {
     theme::ModernTheme::apply_theme(ctx, theme::ThemeMode::System);
 }
 
-/// 检查配置文件访问权限
+/// 执行 JSON → SQLite 的命令行迁移，返回进程退出码
+fn run_sqlite_migration(args: &[String]) -> i32 {
+    let [json_path, db_path] = args else {
+        eprintln!("用法: file_manager --migrate-to-sqlite <JSON数据文件> <SQLite数据库>");
+        return 2;
+    };
+
+    match storage::migrate_json_to_sqlite(
+        std::path::Path::new(json_path),
+        std::path::Path::new(db_path),
+    ) {
+        Ok(count) => {
+            println!("已迁移 {} 个条目到 {}", count, db_path);
+            0
+        }
+        Err(e) => {
+            eprintln!("迁移失败: {}", e);
+            1
+        }
+    }
+}
+
+/// 检查配置文件访问权限（只读取，不写入任何文件）
 fn check_config_access() -> Result<(), String> {
     let config_manager = config::ConfigManager::new();
 
-    // 尝试创建一个临时配置以测试写入权限
-    let test_config = config::AppConfig::default();
-    config_manager
-        .save_config(&test_config)
-        .map_err(|e| format!("无法访问配置目录: {}", e))?;
-
-    // 尝试读取配置
     config_manager
         .load_config()
         .map_err(|e| format!("无法读取配置文件: {}", e))?;
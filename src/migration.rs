@@ -0,0 +1,281 @@
+use serde_json::{Map, Value};
+
+/// 当前数据文件的结构版本，每新增一个迁移步骤加一
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// 单个迁移步骤：把原始 JSON 从 `from_version` 升级到 `from_version + 1`
+struct Migration {
+    from_version: u32,
+    description: &'static str,
+    migrate: fn(&mut Value) -> Result<(), String>,
+}
+
+/// 迁移注册表，按版本顺序排列
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from_version: 0,
+        description: "统一为对象格式，补全条目ID，并把集合的索引引用转换为ID引用",
+        migrate: migrate_v0_to_v1,
+    },
+    Migration {
+        from_version: 1,
+        description: "条目增加添加/修改/打开时间和打开次数",
+        migrate: migrate_v1_to_v2,
+    },
+];
+
+/// 读取原始数据的结构版本，旧格式（没有 schema_version 字段）视为版本 0；
+/// 超出 u32 范围的版本按最大值处理，由 `check_supported` 拒绝
+pub fn schema_version_of(value: &Value) -> u32 {
+    value
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .map(|v| u32::try_from(v).unwrap_or(u32::MAX))
+        .unwrap_or(0)
+}
+
+/// 检查数据版本是否受支持，比当前版本新的数据会被拒绝
+pub fn check_supported(value: &Value) -> Result<u32, String> {
+    let version = schema_version_of(value);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "数据文件的结构版本为 {}，高于当前程序支持的版本 {}，请升级程序后再打开",
+            value["schema_version"], CURRENT_SCHEMA_VERSION
+        ));
+    }
+    Ok(version)
+}
+
+/// 依次执行迁移步骤，把原始数据升级到当前版本
+pub fn migrate_to_current(mut value: Value) -> Result<Value, String> {
+    let mut version = check_supported(&value)?;
+
+    while version < CURRENT_SCHEMA_VERSION {
+        let step = MIGRATIONS
+            .iter()
+            .find(|m| m.from_version == version)
+            .ok_or_else(|| format!("缺少从版本 {} 开始的迁移步骤", version))?;
+
+        (step.migrate)(&mut value).map_err(|e| {
+            format!(
+                "数据迁移失败 (版本 {} → {}，{}): {}",
+                version,
+                version + 1,
+                step.description,
+                e
+            )
+        })?;
+
+        version += 1;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("schema_version".to_string(), Value::from(version));
+        }
+    }
+
+    Ok(value)
+}
+
+/// 版本 0 → 1
+fn migrate_v0_to_v1(value: &mut Value) -> Result<(), String> {
+    // 最早的格式直接是 entries 数组
+    if value.is_array() {
+        let mut obj = Map::new();
+        obj.insert("entries".to_string(), value.take());
+        *value = Value::Object(obj);
+    }
+
+    let obj = value
+        .as_object_mut()
+        .ok_or_else(|| "顶层数据不是对象".to_string())?;
+    obj.entry("version").or_insert_with(|| Value::from("0.2.3"));
+
+    let entries = obj
+        .get_mut("entries")
+        .and_then(|v| v.as_array_mut())
+        .ok_or_else(|| "缺少 entries 字段".to_string())?;
+
+    // 第一步：补全ID和条目类型
+    for entry in entries.iter_mut() {
+        let entry = entry
+            .as_object_mut()
+            .ok_or_else(|| "条目不是对象".to_string())?;
+
+        let has_id = entry
+            .get("id")
+            .and_then(|v| v.as_str())
+            .is_some_and(|id| !id.is_empty());
+        if !has_id {
+            entry.insert(
+                "id".to_string(),
+                Value::from(uuid::Uuid::new_v4().to_string()),
+            );
+        }
+
+        // 旧数据只有 is_directory 标记
+        let is_directory = entry
+            .get("is_directory")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let is_file_type = entry
+            .get("entry_type")
+            .is_none_or(|v| v.as_str() == Some("File"));
+        if is_directory && is_file_type {
+            entry.insert("entry_type".to_string(), Value::from("Directory"));
+        }
+    }
+
+    let ids: Vec<String> = entries
+        .iter()
+        .map(|entry| entry["id"].as_str().unwrap_or_default().to_string())
+        .collect();
+
+    // 第二步：把集合中基于索引的子项目引用转换为ID
+    for entry in entries.iter_mut() {
+        let Some(entry) = entry.as_object_mut() else {
+            continue;
+        };
+
+        let mut legacy_indices: Vec<usize> = entry
+            .remove("legacy_child_entries")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        if let Some(children) = entry.get("child_entries").and_then(|v| v.as_array()) {
+            legacy_indices.extend(
+                children
+                    .iter()
+                    .filter_map(|child| child.as_str())
+                    .filter_map(|child| child.parse::<usize>().ok()),
+            );
+        }
+
+        if !legacy_indices.is_empty() {
+            let child_ids: Vec<Value> = legacy_indices
+                .iter()
+                .filter_map(|&index| ids.get(index))
+                .map(|id| Value::from(id.clone()))
+                .collect();
+            entry.insert("child_entries".to_string(), Value::Array(child_ids));
+        }
+    }
+
+    Ok(())
+}
+
+/// 版本 1 → 2
+///
+/// 旧条目的添加和修改时间无从得知，保持为空（排序时排在最后），不伪造为迁移时间；
+/// 打开次数从 0 开始计数。
+fn migrate_v1_to_v2(value: &mut Value) -> Result<(), String> {
+    let entries = value
+        .get_mut("entries")
+        .and_then(|v| v.as_array_mut())
+        .ok_or_else(|| "缺少 entries 字段".to_string())?;
+
+    for entry in entries.iter_mut() {
+        let entry = entry
+            .as_object_mut()
+            .ok_or_else(|| "条目不是对象".to_string())?;
+        for field in ["created_at", "updated_at", "last_opened_at"] {
+            entry.entry(field).or_insert(Value::Null);
+        }
+        entry.entry("open_count").or_insert_with(|| Value::from(0));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_registry_covers_every_version() {
+        for version in 0..CURRENT_SCHEMA_VERSION {
+            assert!(
+                MIGRATIONS.iter().any(|m| m.from_version == version),
+                "缺少从版本 {} 开始的迁移步骤",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn test_legacy_migration() {
+        // 模拟旧格式的数据（直接是数组，集合使用索引引用）
+        let old_data = json!([
+            {"path": "/a", "name": "A", "nickname": null, "description": null, "tags": [],
+             "url": null, "is_directory": true},
+            {"path": "/b", "name": "B", "nickname": null, "description": null, "tags": [],
+             "url": null, "id": "b-id"},
+            {"path": "collection://Test", "name": "Test", "nickname": null, "description": null,
+             "tags": [], "url": null, "entry_type": "Collection", "child_entries": ["0", "1"]}
+        ]);
+
+        let migrated = migrate_to_current(old_data).unwrap();
+        assert_eq!(schema_version_of(&migrated), CURRENT_SCHEMA_VERSION);
+
+        let entries = migrated["entries"].as_array().unwrap();
+        // 应该生成了新的ID，并根据 is_directory 修正类型
+        let first_id = entries[0]["id"].as_str().unwrap();
+        assert!(!first_id.is_empty());
+        assert_eq!(entries[0]["entry_type"], "Directory");
+        assert_eq!(entries[1]["id"], "b-id");
+
+        // 集合的索引引用应被转换为ID引用
+        assert_eq!(entries[2]["child_entries"], json!([first_id, "b-id"]));
+        assert!(entries[2].get("legacy_child_entries").is_none());
+    }
+
+    #[test]
+    fn test_timestamps_migration() {
+        let data = json!({
+            "entries": [{"path": "/a", "name": "A", "nickname": null, "description": null,
+                         "tags": [], "url": null, "id": "a"}],
+            "version": "0.3.1",
+            "schema_version": 1
+        });
+
+        let migrated = migrate_to_current(data).unwrap();
+        let entry = &migrated["entries"][0];
+        assert!(entry["created_at"].is_null());
+        assert_eq!(entry["open_count"], 0);
+
+        let data: crate::config::UserData = serde_json::from_value(migrated).unwrap();
+        assert_eq!(data.entries[0].created_at, None);
+        assert!(data.entries[0].extra.is_empty());
+    }
+
+    #[test]
+    fn test_newer_version_is_refused() {
+        let future_data = json!({
+            "entries": [],
+            "version": "9.9.9",
+            "schema_version": CURRENT_SCHEMA_VERSION + 1
+        });
+
+        assert!(migrate_to_current(future_data).is_err());
+
+        // 超出 u32 范围的版本不能被截断成旧版本
+        let overflowing = json!({
+            "entries": [],
+            "version": "9.9.9",
+            "schema_version": u64::from(u32::MAX) + 2
+        });
+        assert_eq!(schema_version_of(&overflowing), u32::MAX);
+        let error = migrate_to_current(overflowing).unwrap_err();
+        assert!(error.contains("4294967297"));
+    }
+
+    #[test]
+    fn test_current_version_is_untouched() {
+        let data = json!({
+            "entries": [{"id": "x", "child_entries": ["3"]}],
+            "version": "0.3.1",
+            "schema_version": CURRENT_SCHEMA_VERSION
+        });
+
+        assert_eq!(migrate_to_current(data.clone()).unwrap(), data);
+    }
+}
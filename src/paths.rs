@@ -0,0 +1,235 @@
+use crate::config::ConfigManager;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = "file_manager_config.json";
+pub const DATA_FILE_NAME: &str = "file_manager_data.json";
+
+/// 可执行文件旁存在该文件时进入便携模式，配置和数据都保存在程序目录
+pub const PORTABLE_MARKER: &str = "file_manager.portable";
+
+/// 环境变量覆盖：分别指定配置目录和数据目录
+pub const CONFIG_DIR_ENV: &str = "FILE_MANAGER_CONFIG_DIR";
+pub const DATA_DIR_ENV: &str = "FILE_MANAGER_DATA_DIR";
+
+/// 系统配置/数据目录下使用的子目录名
+const APP_DIR_NAME: &str = "file_manager";
+
+/// 可执行文件所在目录
+pub fn exe_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// 是否处于便携模式
+pub fn is_portable() -> bool {
+    exe_dir().join(PORTABLE_MARKER).exists()
+}
+
+/// 配置文件所在目录
+pub fn config_dir() -> PathBuf {
+    resolve_dir(
+        std::env::var_os(CONFIG_DIR_ENV),
+        is_portable(),
+        exe_dir(),
+        dirs::config_dir(),
+    )
+}
+
+/// 用户数据文件所在目录
+pub fn data_dir() -> PathBuf {
+    resolve_dir(
+        std::env::var_os(DATA_DIR_ENV),
+        is_portable(),
+        exe_dir(),
+        dirs::data_dir(),
+    )
+}
+
+pub fn default_config_path() -> PathBuf {
+    config_dir().join(CONFIG_FILE_NAME)
+}
+
+pub fn default_data_path() -> PathBuf {
+    data_dir().join(DATA_FILE_NAME)
+}
+
+/// 按优先级决定目录：环境变量 > 便携模式 > 系统目录 > 程序目录
+fn resolve_dir(
+    env_override: Option<OsString>,
+    portable: bool,
+    exe_dir: PathBuf,
+    system_dir: Option<PathBuf>,
+) -> PathBuf {
+    if let Some(dir) = env_override.filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if portable {
+        return exe_dir;
+    }
+    system_dir
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .unwrap_or(exe_dir)
+}
+
+/// 把旧版本保存在程序目录下的配置和数据文件迁移到新的默认位置
+///
+/// 便携模式或新位置就是程序目录时不做任何事。返回已迁移的文件（新路径）。
+pub fn migrate_legacy_files() -> Result<Vec<PathBuf>, String> {
+    if is_portable() {
+        return Ok(Vec::new());
+    }
+    migrate_legacy_files_between(&exe_dir(), &config_dir(), &data_dir())
+}
+
+fn migrate_legacy_files_between(
+    legacy_dir: &Path,
+    config_dir: &Path,
+    data_dir: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let mut moves: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    let legacy_config = legacy_dir.join(CONFIG_FILE_NAME);
+    if config_dir != legacy_dir && legacy_config.is_file() {
+        moves.push((legacy_config, config_dir.join(CONFIG_FILE_NAME)));
+    }
+
+    // 默认数据文件以及命名资料库的数据文件（file_manager_data_<名称>.json）
+    if data_dir != legacy_dir
+        && let Ok(read_dir) = fs::read_dir(legacy_dir)
+    {
+        for dir_entry in read_dir.flatten() {
+            let name = dir_entry.file_name().to_string_lossy().to_string();
+            let is_data_file = name.starts_with("file_manager_data")
+                && (name.ends_with(".json") || name.ends_with(".db"));
+            if is_data_file && dir_entry.path().is_file() {
+                moves.push((dir_entry.path(), data_dir.join(&name)));
+            }
+        }
+    }
+
+    // 新位置已有文件时不覆盖
+    moves.retain(|(_, to)| !to.exists());
+    if moves.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    for (from, to) in &moves {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+        // 先复制再删除原文件；程序目录只读时保留原文件
+        fs::copy(from, to).map_err(|e| format!("迁移 {} 失败: {}", from.display(), e))?;
+        let _ = fs::remove_file(from);
+    }
+
+    // 配置中记录的数据文件路径如果指向被迁移的文件，需要一并更新
+    let config_manager = ConfigManager::new_with_path(config_dir.join(CONFIG_FILE_NAME));
+    if let Ok(mut config) = config_manager.load_config().map(|loaded| loaded.config) {
+        let remap = |path: &mut Option<String>| {
+            if let Some((_, to)) = moves
+                .iter()
+                .find(|(from, _)| path.as_deref().map(Path::new) == Some(from.as_path()))
+            {
+                *path = Some(to.to_string_lossy().to_string());
+                true
+            } else {
+                false
+            }
+        };
+
+        let mut changed = remap(&mut config.data_file_path);
+        for lib in &mut config.libraries {
+            changed |= remap(&mut lib.data_file_path);
+        }
+        if changed {
+            config_manager.save_config(&config)?;
+        }
+    }
+
+    Ok(moves.into_iter().map(|(_, to)| to).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, LibraryProfile};
+
+    #[test]
+    fn test_resolve_dir_priority() {
+        let exe = PathBuf::from("/opt/app");
+        let system = Some(PathBuf::from("/home/u/.config"));
+
+        assert_eq!(
+            resolve_dir(Some("/custom".into()), true, exe.clone(), system.clone()),
+            PathBuf::from("/custom")
+        );
+        assert_eq!(
+            resolve_dir(None, true, exe.clone(), system.clone()),
+            exe.clone()
+        );
+        assert_eq!(
+            resolve_dir(Some("".into()), false, exe.clone(), system),
+            PathBuf::from("/home/u/.config/file_manager")
+        );
+        assert_eq!(resolve_dir(None, false, exe.clone(), None), exe);
+    }
+
+    #[test]
+    fn test_migrate_legacy_files() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = root.path().join("bin");
+        let config_dir = root.path().join("config");
+        let data_dir = root.path().join("data");
+        fs::create_dir_all(&legacy).unwrap();
+
+        let legacy_data = legacy.join(DATA_FILE_NAME);
+        let legacy_work = legacy.join("file_manager_data_Work.json");
+        fs::write(&legacy_data, "{}").unwrap();
+        fs::write(&legacy_work, "{}").unwrap();
+
+        let mut config = AppConfig {
+            data_file_path: Some(legacy_data.to_string_lossy().to_string()),
+            ..AppConfig::default()
+        };
+        config.libraries.push(LibraryProfile::new(
+            "Work".to_string(),
+            Some(legacy_work.to_string_lossy().to_string()),
+        ));
+        ConfigManager::new_with_path(legacy.join(CONFIG_FILE_NAME))
+            .save_config(&config)
+            .unwrap();
+
+        let moved = migrate_legacy_files_between(&legacy, &config_dir, &data_dir).unwrap();
+        assert_eq!(moved.len(), 3);
+        assert!(!legacy_data.exists());
+        assert!(data_dir.join(DATA_FILE_NAME).exists());
+
+        // 配置中的路径应指向新位置
+        let migrated = ConfigManager::new_with_path(config_dir.join(CONFIG_FILE_NAME))
+            .load_config()
+            .unwrap()
+            .config;
+        assert_eq!(
+            migrated.data_file_path.map(PathBuf::from),
+            Some(data_dir.join(DATA_FILE_NAME))
+        );
+        assert_eq!(
+            migrated.libraries[0]
+                .data_file_path
+                .clone()
+                .map(PathBuf::from),
+            Some(data_dir.join("file_manager_data_Work.json"))
+        );
+
+        // 再次运行不会重复迁移
+        assert!(
+            migrate_legacy_files_between(&legacy, &config_dir, &data_dir)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::auto_tag::{AutoTagRule, RuleSet};
 use crate::file_entry::FileEntry;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -629,6 +630,67 @@ impl Plugin for BackupPlugin {
     }
 }
 
+/// 内置自动标签插件：按配置中的规则（`rules`）为处理的条目添加标签
+pub struct AutoTagPlugin {
+    name: String,
+    version: String,
+    config: PluginConfig,
+    rules: RuleSet,
+}
+
+impl Default for AutoTagPlugin {
+    fn default() -> Self {
+        Self {
+            name: "Auto Tag Plugin".to_string(),
+            version: "1.0.0".to_string(),
+            config: PluginConfig::default(),
+            rules: RuleSet::new(&[]),
+        }
+    }
+}
+
+impl Plugin for AutoTagPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn description(&self) -> &str {
+        "按路径、扩展名、网址域名或名称自动添加标签"
+    }
+
+    fn author(&self) -> &str {
+        "File Manager Team"
+    }
+
+    fn initialize(&mut self, _context: &mut PluginContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn process_entry(&self, entry: &FileEntry) -> Option<FileEntry> {
+        let mut entry = entry.clone();
+        self.rules.apply(&mut entry).then_some(entry)
+    }
+
+    fn get_config(&self) -> Option<PluginConfig> {
+        Some(self.config.clone())
+    }
+
+    fn set_config(&mut self, config: PluginConfig) -> Result<(), String> {
+        let rules: Vec<AutoTagRule> = config.get("rules")?.unwrap_or_default();
+        self.rules = RuleSet::new(&rules);
+        self.config = config;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -683,6 +745,32 @@ mod tests {
         assert_eq!(plugins.len(), 0);
     }
 
+    #[test]
+    fn test_auto_tag_plugin() {
+        use crate::auto_tag::RuleKind;
+
+        let mut manager = PluginManager::new(std::env::temp_dir());
+        manager
+            .register_plugin(Box::new(AutoTagPlugin::default()))
+            .unwrap();
+
+        let mut config = PluginConfig::new();
+        let rule = AutoTagRule::new(RuleKind::Extension, "pdf", vec!["文档".to_string()]);
+        config.set("rules", vec![rule.unwrap()]).unwrap();
+        manager
+            .set_plugin_config("Auto Tag Plugin", config)
+            .unwrap();
+
+        let entry = FileEntry::new(
+            PathBuf::from("/a.pdf"),
+            "a.pdf".to_string(),
+            None,
+            Vec::new(),
+            false,
+        );
+        assert_eq!(manager.process_entry(&entry).tags, vec!["#文档"]);
+    }
+
     #[test]
     fn test_context_menu_item() {
         let item = ContextMenuItem::new("test_id", "Test Item")
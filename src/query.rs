@@ -0,0 +1,732 @@
+//! 搜索框的查询语言
+//!
+//! 语法示例：
+//! - `报告 #工作`：多个条件同时满足
+//! - `#work OR #home`：满足任意一个（`OR` 的优先级低于并列）
+//! - `-#archived`、`NOT type:dir`：排除
+//! - `"年度 报告"`：完整短语
+//! - `type:dir`、`path:~/src`、`url:github.com`、`tag:工作`
+//! - `added:>2026-01-01`、`opened:<30d`、`updated:>=2w`
+//! - `content:"季度 报告"`：文件内容中包含短语（需要开启内容索引）
+//! - 括号分组：`(#work OR #home) -#archived`
+//!
+//! 解析结果与界面无关，命令行工具等也可以直接使用。
+
+use crate::content_index::ContentIndex;
+use crate::file_entry::{EntryType, FileEntry};
+use crate::fuzzy::{self, EntryMatch};
+use crate::search_index::{EntryIndex, SearchIndex};
+use crate::tags;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// 解析后的查询
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+    /// 所有子条件都满足；空列表匹配所有条目
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Term(Term),
+}
+
+/// 单个搜索条件
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    /// 普通搜索词，模糊匹配名称、昵称、描述和标签
+    Text(String),
+    /// 引号中的短语，必须完整出现
+    Phrase(String),
+    Tag(String),
+    Type(EntryType),
+    Path(String),
+    Url(String),
+    /// 文件内容中包含的短语，见 [`ContentIndex`]
+    Content(String),
+    Time {
+        field: TimeField,
+        comparison: Comparison,
+        value: TimeValue,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeField {
+    Added,
+    Updated,
+    Opened,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Before,
+    AtOrBefore,
+    Equal,
+    AtOrAfter,
+    After,
+}
+
+/// 时间值：具体日期（本地时间）或距今多久
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeValue {
+    Date(NaiveDate),
+    Ago(Duration),
+}
+
+impl Query {
+    /// 计算匹配结果；文本条件提供得分和高亮范围，其他条件只做筛选（得分为 0）
+    ///
+    /// `index` 为该条目的搜索索引（见 [`SearchIndex`]）；`content` 为 None 时 `content:` 条件不满足。
+    pub fn evaluate(
+        &self,
+        entry: &FileEntry,
+        index: &EntryIndex,
+        content: Option<&ContentIndex>,
+        now: DateTime<Utc>,
+    ) -> Option<EntryMatch> {
+        match self {
+            Query::And(children) => {
+                let mut result = EntryMatch::default();
+                for child in children {
+                    result.merge(child.evaluate(entry, index, content, now)?);
+                }
+                Some(result)
+            }
+            Query::Or(children) => {
+                let mut best: Option<EntryMatch> = None;
+                let mut highlights = EntryMatch::default();
+                for m in children
+                    .iter()
+                    .filter_map(|c| c.evaluate(entry, index, content, now))
+                {
+                    if best.as_ref().is_none_or(|b| m.score > b.score) {
+                        best = Some(m.clone());
+                    }
+                    highlights.merge(EntryMatch { score: 0, ..m });
+                }
+                // 得分取最好的分支，高亮所有满足的分支
+                highlights.score = best?.score;
+                Some(highlights)
+            }
+            Query::Not(child) => match child.evaluate(entry, index, content, now) {
+                Some(_) => None,
+                None => Some(EntryMatch::default()),
+            },
+            Query::Term(term) => term.evaluate(entry, index, content, now),
+        }
+    }
+
+    /// 把标签条件中的别名换成标准标签，见 [`tags::resolve_alias`]
+    pub fn resolve_tag_aliases(&mut self, aliases: &BTreeMap<String, String>) {
+        match self {
+            Query::And(children) | Query::Or(children) => {
+                for child in children {
+                    child.resolve_tag_aliases(aliases);
+                }
+            }
+            Query::Not(child) => child.resolve_tag_aliases(aliases),
+            Query::Term(Term::Tag(tag)) => {
+                if let Some(normalized) = tags::normalize(tag) {
+                    let resolved = tags::resolve_alias(&normalized, aliases);
+                    if resolved != normalized {
+                        *tag = resolved.trim_start_matches('#').to_string();
+                    }
+                }
+            }
+            Query::Term(_) => {}
+        }
+    }
+
+    /// 用标签倒排索引找出可能满足查询的条目位置（升序）；None 表示需要逐个检查所有条目
+    pub fn candidates(&self, index: &SearchIndex) -> Option<Vec<usize>> {
+        match self {
+            Query::And(children) => {
+                children
+                    .iter()
+                    .filter_map(|c| c.candidates(index))
+                    .reduce(|a, b| {
+                        a.into_iter()
+                            .filter(|p| b.binary_search(p).is_ok())
+                            .collect()
+                    })
+            }
+            Query::Or(children) => {
+                let mut union = Vec::new();
+                for child in children {
+                    union.extend(child.candidates(index)?);
+                }
+                union.sort_unstable();
+                union.dedup();
+                Some(union)
+            }
+            Query::Term(Term::Tag(tag)) => Some(index.entries_with_tag(tag)),
+            Query::Not(_) | Query::Term(_) => None,
+        }
+    }
+}
+
+impl Term {
+    fn evaluate(
+        &self,
+        entry: &FileEntry,
+        index: &EntryIndex,
+        content: Option<&ContentIndex>,
+        now: DateTime<Utc>,
+    ) -> Option<EntryMatch> {
+        let filter = |matched: bool| matched.then(EntryMatch::default);
+        match self {
+            Term::Text(text) => fuzzy::score_term(index, text),
+            Term::Phrase(phrase) => fuzzy::score_phrase(index, phrase),
+            Term::Tag(tag) => fuzzy::score_tag(index, tag),
+            Term::Type(entry_type) => filter(&entry.entry_type == entry_type),
+            Term::Path(path) => filter(contains_ignore_case(
+                &entry.path.to_string_lossy(),
+                &expand_home(path),
+            )),
+            Term::Url(url) => filter(
+                entry
+                    .url
+                    .as_deref()
+                    .is_some_and(|u| contains_ignore_case(u, url)),
+            ),
+            Term::Content(phrase) => content?.find(entry, phrase).map(|snippet| EntryMatch {
+                snippet: Some(snippet),
+                ..Default::default()
+            }),
+            Term::Time {
+                field,
+                comparison,
+                value,
+            } => {
+                let time = match field {
+                    TimeField::Added => entry.created_at,
+                    TimeField::Updated => entry.updated_at,
+                    TimeField::Opened => entry.last_opened_at,
+                };
+                // 时间未知（例如从未打开）的条目不满足任何时间条件
+                filter(time.is_some_and(|time| value.compare(time, *comparison, now)))
+            }
+        }
+    }
+}
+
+impl TimeValue {
+    fn compare(self, time: DateTime<Utc>, comparison: Comparison, now: DateTime<Utc>) -> bool {
+        match self {
+            TimeValue::Date(date) => {
+                let start = local_midnight(date);
+                let end = local_midnight(date + Duration::days(1));
+                match comparison {
+                    Comparison::Before => time < start,
+                    Comparison::AtOrBefore => time < end,
+                    Comparison::Equal => start <= time && time < end,
+                    Comparison::AtOrAfter => time >= start,
+                    Comparison::After => time >= end,
+                }
+            }
+            // 距今多久比较的是“过去了多久”：`<30d` 表示最近 30 天内
+            TimeValue::Ago(duration) => {
+                let age = now - time;
+                match comparison {
+                    Comparison::Before => age < duration,
+                    Comparison::AtOrBefore | Comparison::Equal => age <= duration,
+                    Comparison::AtOrAfter => age >= duration,
+                    Comparison::After => age > duration,
+                }
+            }
+        }
+    }
+}
+
+/// 本地时区中某天开始的时刻
+pub fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+fn contains_ignore_case(text: &str, needle: &str) -> bool {
+    text.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// 把开头的 `~` 展开为用户主目录
+fn expand_home(path: &str) -> String {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => dirs::home_dir()
+            .map(|home| {
+                let mut expanded = home.to_string_lossy().into_owned();
+                expanded.push_str(rest);
+                PathBuf::from(expanded).to_string_lossy().into_owned()
+            })
+            .unwrap_or_else(|| path.to_string()),
+        _ => path.to_string(),
+    }
+}
+
+/// 解析查询字符串，语法错误时返回说明位置和原因的提示
+pub fn parse(input: &str) -> Result<Query, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        end: input.chars().count(),
+    };
+    let query = parser.parse_or()?;
+    match parser.peek() {
+        None => Ok(query),
+        Some(token) => Err(error_at(token.position, "多余的右括号")),
+    }
+}
+
+fn error_at(position: usize, message: &str) -> String {
+    format!("第 {} 个字符处：{}", position + 1, message)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    LeftParen,
+    RightParen,
+    Or,
+    And,
+    Not,
+    /// 普通词或 `字段:值`，值可以带引号
+    Word(String),
+    Phrase(String),
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    /// 在输入中的字符位置，用于错误提示
+    position: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let read_quoted = |start: usize| -> Result<(String, usize), String> {
+        let close = chars[start + 1..]
+            .iter()
+            .position(|&c| c == '"')
+            .ok_or_else(|| error_at(start, "引号没有闭合"))?;
+        let text = chars[start + 1..start + 1 + close].iter().collect();
+        Ok((text, start + close + 2))
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                TokenKind::LeftParen
+            }
+            ')' => {
+                i += 1;
+                TokenKind::RightParen
+            }
+            '-' if chars.get(i + 1).is_some_and(|n| !n.is_whitespace()) => {
+                i += 1;
+                TokenKind::Not
+            }
+            '"' => {
+                let (text, next) = read_quoted(i)?;
+                i = next;
+                TokenKind::Phrase(text)
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.get(i) {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    if c == '"' && word.ends_with(':') {
+                        // 字段值带引号，例如 path:"My Documents"
+                        let (text, next) = read_quoted(i)?;
+                        word.push_str(&text);
+                        i = next;
+                        break;
+                    }
+                    word.push(c);
+                    i += 1;
+                }
+                match word.as_str() {
+                    "OR" | "|" => TokenKind::Or,
+                    "AND" => TokenKind::And,
+                    "NOT" => TokenKind::Not,
+                    _ => TokenKind::Word(word),
+                }
+            }
+        };
+        tokens.push(Token { kind, position });
+    }
+    Ok(tokens)
+}
+
+/// 递归下降解析，优先级从低到高：OR、并列（AND）、取反
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// 输入的长度，用于在末尾报错
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_position(&self) -> usize {
+        self.peek().map_or(self.end, |t| t.position)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, String> {
+        let mut branches = vec![self.parse_and()?];
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Or {
+                break;
+            }
+            let position = token.position;
+            self.position += 1;
+            let branch = self.parse_and()?;
+            if branch == Query::And(Vec::new()) {
+                return Err(error_at(position, "OR 后面需要搜索条件"));
+            }
+            branches.push(branch);
+        }
+
+        if branches.len() == 1 {
+            return Ok(branches.remove(0));
+        }
+        if branches[0] == Query::And(Vec::new()) {
+            return Err(error_at(0, "OR 前面需要搜索条件"));
+        }
+        Ok(Query::Or(branches))
+    }
+
+    fn parse_and(&mut self) -> Result<Query, String> {
+        let mut children = Vec::new();
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::Or | TokenKind::RightParen => break,
+                TokenKind::And => {
+                    let position = token.position;
+                    self.position += 1;
+                    if children.is_empty()
+                        || matches!(
+                            self.peek().map(|t| &t.kind),
+                            None | Some(TokenKind::Or | TokenKind::RightParen | TokenKind::And)
+                        )
+                    {
+                        return Err(error_at(position, "AND 前后都需要搜索条件"));
+                    }
+                }
+                _ => children.push(self.parse_unary()?),
+            }
+        }
+
+        if children.len() == 1 {
+            Ok(children.remove(0))
+        } else {
+            Ok(Query::And(children))
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, String> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(error_at(self.end, "缺少搜索条件"));
+        };
+        self.position += 1;
+
+        match token.kind {
+            TokenKind::Not => {
+                if matches!(
+                    self.peek().map(|t| &t.kind),
+                    None | Some(TokenKind::Or | TokenKind::And | TokenKind::RightParen)
+                ) {
+                    return Err(error_at(token.position, "取反后面需要搜索条件"));
+                }
+                Ok(Query::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::LeftParen => {
+                let inner = self.parse_or()?;
+                match self.peek() {
+                    Some(t) if t.kind == TokenKind::RightParen => self.position += 1,
+                    _ => return Err(error_at(token.position, "括号没有闭合")),
+                }
+                if inner == Query::And(Vec::new()) {
+                    return Err(error_at(token.position, "括号中没有搜索条件"));
+                }
+                Ok(inner)
+            }
+            TokenKind::Phrase(text) => {
+                if text.trim().is_empty() {
+                    return Err(error_at(token.position, "引号中没有内容"));
+                }
+                Ok(Query::Term(Term::Phrase(text)))
+            }
+            TokenKind::Word(word) => parse_word(&word, token.position).map(Query::Term),
+            TokenKind::RightParen | TokenKind::Or | TokenKind::And => {
+                Err(error_at(self.next_position(), "缺少搜索条件"))
+            }
+        }
+    }
+}
+
+/// 解析一个词：`#标签`、`字段:值` 或普通搜索词；未知的字段按普通搜索词处理
+fn parse_word(word: &str, position: usize) -> Result<Term, String> {
+    if let Some(tag) = word.strip_prefix('#') {
+        return Ok(Term::Tag(tag.to_string()));
+    }
+
+    let Some((field, value)) = word.split_once(':') else {
+        return Ok(Term::Text(word.to_string()));
+    };
+    let field = field.to_lowercase();
+    let known = matches!(
+        field.as_str(),
+        "type" | "tag" | "path" | "url" | "content" | "added" | "updated" | "opened"
+    );
+    if !known {
+        return Ok(Term::Text(word.to_string()));
+    }
+    if value.is_empty() {
+        return Err(error_at(position, &format!("“{}:” 后面需要填写值", field)));
+    }
+
+    let time_field = |field| parse_time(field, value, position);
+    match field.as_str() {
+        "type" => parse_type(value).map(Term::Type).ok_or_else(|| {
+            error_at(
+                position,
+                &format!("未知的类型“{}”，可用：file、dir、link、collection、command", value),
+            )
+        }),
+        "tag" => Ok(Term::Tag(value.trim_start_matches('#').to_string())),
+        "path" => Ok(Term::Path(value.to_string())),
+        "url" => Ok(Term::Url(value.to_string())),
+        "content" => Ok(Term::Content(value.to_string())),
+        "added" => time_field(TimeField::Added),
+        "updated" => time_field(TimeField::Updated),
+        _ => time_field(TimeField::Opened),
+    }
+}
+
+fn parse_type(value: &str) -> Option<EntryType> {
+    match value.to_lowercase().as_str() {
+        "file" | "f" | "文件" => Some(EntryType::File),
+        "dir" | "directory" | "folder" | "d" | "文件夹" | "目录" => Some(EntryType::Directory),
+        "link" | "url" | "web" | "链接" | "网页" => Some(EntryType::WebLink),
+        "collection" | "c" | "集合" => Some(EntryType::Collection),
+        "command" | "cmd" | "命令" => Some(EntryType::Command),
+        _ => None,
+    }
+}
+
+/// 解析时间条件，例如 `>2026-01-01`、`<=30d`、`2026-03-15`
+fn parse_time(field: TimeField, value: &str, position: usize) -> Result<Term, String> {
+    let (comparison, rest) = [
+        (">=", Comparison::AtOrAfter),
+        ("<=", Comparison::AtOrBefore),
+        (">", Comparison::After),
+        ("<", Comparison::Before),
+        ("=", Comparison::Equal),
+    ]
+    .iter()
+    .find_map(|(prefix, comparison)| value.strip_prefix(prefix).map(|rest| (*comparison, rest)))
+    .unwrap_or((Comparison::Equal, value));
+
+    let value = parse_time_value(rest).ok_or_else(|| {
+        error_at(
+            position,
+            &format!(
+                "无法识别时间“{}”，可以写日期（2026-01-01）或距今多久（30d、2w、6m、1y）",
+                rest
+            ),
+        )
+    })?;
+    Ok(Term::Time {
+        field,
+        comparison,
+        value,
+    })
+}
+
+fn parse_time_value(text: &str) -> Option<TimeValue> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(TimeValue::Date(date));
+    }
+
+    let unit = text.chars().last()?;
+    let amount: i64 = text[..text.len() - unit.len_utf8()].parse().ok()?;
+    let days_per_unit = match unit {
+        'd' => 1,
+        'w' => 7,
+        'm' => 30,
+        'y' => 365,
+        _ => return None,
+    };
+    // 数值过大时按无法识别处理
+    let days = amount.checked_mul(days_per_unit)?;
+    Duration::try_days(days).map(TimeValue::Ago)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(term: Term) -> Query {
+        Query::Term(term)
+    }
+
+    fn text(s: &str) -> Query {
+        term(Term::Text(s.to_string()))
+    }
+
+    fn tag(s: &str) -> Query {
+        term(Term::Tag(s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_precedence_and_negation() {
+        assert_eq!(parse("").unwrap(), Query::And(vec![]));
+        assert_eq!(
+            parse("a b OR c").unwrap(),
+            Query::Or(vec![Query::And(vec![text("a"), text("b")]), text("c")])
+        );
+        assert_eq!(
+            parse("(#work OR #home) -#archived").unwrap(),
+            Query::And(vec![
+                Query::Or(vec![tag("work"), tag("home")]),
+                Query::Not(Box::new(tag("archived"))),
+            ])
+        );
+        assert_eq!(
+            parse(r#"path:"My Docs" "年度 报告" NOT type:dir"#).unwrap(),
+            Query::And(vec![
+                term(Term::Path("My Docs".to_string())),
+                term(Term::Phrase("年度 报告".to_string())),
+                Query::Not(Box::new(term(Term::Type(EntryType::Directory)))),
+            ])
+        );
+        assert_eq!(
+            parse(r#"content:"quarterly report""#).unwrap(),
+            term(Term::Content("quarterly report".to_string()))
+        );
+        // 未知字段和单独的减号按普通文本处理
+        assert_eq!(parse("http://x").unwrap(), text("http://x"));
+        assert_eq!(
+            parse("a - b").unwrap(),
+            Query::And(vec![text("a"), text("-"), text("b")])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "\"unclosed",
+            "(a OR b",
+            "a)",
+            "OR a",
+            "a OR",
+            "()",
+            "type:folderz",
+            "added:>yesterday",
+            "url:",
+        ] {
+            assert!(parse(bad).is_err(), "应当报错: {}", bad);
+        }
+        assert_eq!(parse("a)").unwrap_err(), "第 2 个字符处：多余的右括号");
+    }
+
+    #[test]
+    fn test_resolve_tag_aliases() {
+        let aliases = BTreeMap::from([("#wrok".to_string(), "#work".to_string())]);
+        let mut query = parse("#wrok/notes OR -tag:wrok #wro").unwrap();
+        query.resolve_tag_aliases(&aliases);
+        assert_eq!(
+            query,
+            Query::Or(vec![
+                tag("work/notes"),
+                Query::And(vec![Query::Not(Box::new(tag("work"))), tag("wro")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tag_candidates() {
+        let entries: Vec<FileEntry> = [&["#work"][..], &["#home"], &[], &["#work", "#home"]]
+            .iter()
+            .enumerate()
+            .map(|(i, tags)| {
+                FileEntry::new(
+                    PathBuf::from(format!("/{}", i)),
+                    i.to_string(),
+                    None,
+                    tags.iter().map(|t| t.to_string()).collect(),
+                    false,
+                )
+            })
+            .collect();
+        let index = SearchIndex::new(&entries);
+        let candidates = |q: &str| parse(q).unwrap().candidates(&index);
+
+        assert_eq!(candidates("#work"), Some(vec![0, 3]));
+        assert_eq!(candidates("#work #home report"), Some(vec![3]));
+        assert_eq!(candidates("#work OR #home"), Some(vec![0, 1, 3]));
+        // 含有非标签条件的 OR 和取反无法用倒排索引缩小范围
+        assert_eq!(candidates("#work OR report"), None);
+        assert_eq!(candidates("-#work"), None);
+    }
+
+    #[test]
+    fn test_evaluate_fields_and_times() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        let mut entry = FileEntry::new_web_link(
+            "GitHub".to_string(),
+            "https://github.com".to_string(),
+            None,
+            None,
+            vec!["#work".to_string()],
+        );
+        entry.created_at = Some(Utc.with_ymd_and_hms(2026, 2, 1, 12, 0, 0).unwrap());
+        entry.last_opened_at = Some(now - Duration::days(3));
+
+        let matches = |entry: &FileEntry, q: &str| {
+            parse(q)
+                .unwrap()
+                .evaluate(entry, &EntryIndex::new(entry), None, now)
+                .is_some()
+        };
+        assert!(matches(&entry, "type:link url:github.com"));
+        assert!(!matches(&entry, "type:dir"));
+        assert!(matches(&entry, "#work OR #home"));
+        assert!(!matches(&entry, "-#work"));
+        assert!(matches(&entry, "added:>2026-01-01 added:<2026-02-02"));
+        assert!(!matches(&entry, "added:>2026-02-01"));
+        assert!(matches(&entry, "added:2026-02-01"));
+        assert!(matches(&entry, "opened:<7d"));
+        assert!(!matches(&entry, "opened:>1w"));
+        assert!(parse("opened:<99999999999999999d").is_err());
+        assert!(parse("opened:<9999999999999999y").is_err());
+        // 从未修改的条目不满足修改时间条件
+        entry.updated_at = None;
+        assert!(!matches(&entry, "updated:<1y"));
+
+        let m = parse("git OR hub")
+            .unwrap()
+            .evaluate(&entry, &EntryIndex::new(&entry), None, now)
+            .unwrap();
+        assert_eq!(m.name, vec![0..6]);
+    }
+}
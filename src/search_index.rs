@@ -0,0 +1,178 @@
+use crate::file_entry::FileEntry;
+use crate::fuzzy::IndexedText;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 单个条目的搜索索引：名称、昵称、描述预先转成小写和拼音，标签预先规范化
+pub struct EntryIndex {
+    pub name: IndexedText,
+    pub nickname: Option<IndexedText>,
+    pub description: Option<IndexedText>,
+    /// 小写、去掉 `#` 前缀的标签
+    pub tags: Vec<String>,
+}
+
+impl EntryIndex {
+    pub fn new(entry: &FileEntry) -> Self {
+        let (hash_tags, _) = entry.get_tag_categories();
+        Self {
+            name: IndexedText::new(&entry.name),
+            nickname: entry.nickname.as_deref().map(IndexedText::new),
+            description: entry.description.as_deref().map(IndexedText::new),
+            tags: hash_tags
+                .iter()
+                .map(|tag| tag.trim_start_matches('#').to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+/// 参与搜索的字段的指纹，字段不变时可以沿用已有的索引
+fn fingerprint(entry: &FileEntry) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    entry.name.hash(&mut hasher);
+    entry.nickname.hash(&mut hasher);
+    entry.description.hash(&mut hasher);
+    entry.tags.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct Slot {
+    id: String,
+    fingerprint: u64,
+    index: EntryIndex,
+}
+
+/// 整个资料库的搜索索引，按位置与条目列表一一对应，另有标签的倒排索引
+#[derive(Default)]
+pub struct SearchIndex {
+    slots: Vec<Slot>,
+    /// 标签（小写、不带 `#`）→ 带有该标签的条目位置，位置按升序排列
+    tags: HashMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn new(entries: &[FileEntry]) -> Self {
+        let mut index = Self::default();
+        index.sync(entries);
+        index
+    }
+
+    /// 与条目列表同步：搜索字段没变的条目沿用已有索引，只为新增或修改过的条目重建，
+    /// 返回重建的条目数
+    pub fn sync(&mut self, entries: &[FileEntry]) -> usize {
+        let mut previous: HashMap<String, Slot> = self
+            .slots
+            .drain(..)
+            .map(|slot| (slot.id.clone(), slot))
+            .collect();
+
+        let mut rebuilt = 0;
+        for entry in entries {
+            let fingerprint = fingerprint(entry);
+            let slot = match previous.remove(&entry.id) {
+                Some(slot) if slot.fingerprint == fingerprint => slot,
+                _ => {
+                    rebuilt += 1;
+                    Slot {
+                        id: entry.id.clone(),
+                        fingerprint,
+                        index: EntryIndex::new(entry),
+                    }
+                }
+            };
+            self.slots.push(slot);
+        }
+
+        self.tags.clear();
+        for (position, slot) in self.slots.iter().enumerate() {
+            for tag in &slot.index.tags {
+                let positions = self.tags.entry(tag.clone()).or_default();
+                if positions.last() != Some(&position) {
+                    positions.push(position);
+                }
+            }
+        }
+        rebuilt
+    }
+
+    /// 第 `position` 个条目的索引；索引已过期（位置上不是该条目）时返回 None
+    pub fn get(&self, position: usize, entry: &FileEntry) -> Option<&EntryIndex> {
+        self.slots
+            .get(position)
+            .filter(|slot| slot.id == entry.id)
+            .map(|slot| &slot.index)
+    }
+
+    /// 标签包含 `query` 的条目位置（升序、不重复），用于 `#标签` 搜索时缩小范围
+    pub fn entries_with_tag(&self, query: &str) -> Vec<usize> {
+        let query = query.to_lowercase();
+        let mut positions: Vec<usize> = self
+            .tags
+            .iter()
+            .filter(|(tag, _)| tag.contains(&query))
+            .flat_map(|(_, positions)| positions.iter().copied())
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tagged;
+
+    #[test]
+    fn test_sync_reuses_unchanged_entries() {
+        let mut entries = vec![
+            tagged("a", &["#Work"]),
+            tagged("b", &[]),
+            tagged("c", &["#work", "#home"]),
+        ];
+        let mut index = SearchIndex::new(&entries);
+        assert_eq!(index.entries_with_tag("work"), vec![0, 2]);
+
+        // 调整顺序和打开次数不需要重建
+        entries.swap(0, 1);
+        entries[0].open_count = 3;
+        assert_eq!(index.sync(&entries), 0);
+        assert_eq!(index.entries_with_tag("work"), vec![1, 2]);
+
+        // 修改搜索字段或新增条目只重建对应条目
+        entries[2].tags.push("#archived".to_string());
+        entries.push(tagged("d", &["#homework"]));
+        assert_eq!(index.sync(&entries), 2);
+        assert_eq!(index.entries_with_tag("home"), vec![2, 3]);
+        assert!(index.get(3, &entries[3]).is_some());
+        assert!(index.get(0, &entries[3]).is_none());
+    }
+
+    #[test]
+    fn test_filtering_large_library_reuses_index() {
+        let entries: Vec<FileEntry> = (0..50_000)
+            .map(|i| {
+                let mut e = tagged(&format!("项目文档 {}", i), &["#工作"]);
+                e.description = Some(format!("第 {} 号条目的说明", i));
+                e
+            })
+            .collect();
+        let mut index = SearchIndex::new(&entries);
+        // 没有修改时同步不重建任何条目
+        assert_eq!(index.sync(&entries), 0);
+
+        let matched = entries
+            .iter()
+            .enumerate()
+            .filter(|(i, e)| {
+                index
+                    .get(*i, e)
+                    .and_then(|ix| crate::fuzzy::score_term(ix, "xmwd"))
+                    .is_some()
+            })
+            .count();
+        assert_eq!(matched, entries.len());
+    }
+}
@@ -0,0 +1,664 @@
+use crate::config::{DataManager, LoadedData, UserData};
+use crate::file_entry::{self, FileEntry};
+use crate::migration;
+use crate::sync;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// 存储后端类型
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum StorageBackend {
+    /// 单个 JSON 文件（默认）
+    #[default]
+    Json,
+    /// 内嵌 SQLite 数据库，按行增量更新
+    Sqlite,
+}
+
+impl StorageBackend {
+    pub fn label(self) -> &'static str {
+        match self {
+            StorageBackend::Json => "JSON 文件",
+            StorageBackend::Sqlite => "SQLite 数据库",
+        }
+    }
+}
+
+const ENCRYPTION_UNSUPPORTED: &str = "只有 JSON 存储后端支持加密";
+
+/// 条目存储接口
+pub trait EntryStore {
+    /// 存储位置（数据文件或数据库文件）
+    fn location(&self) -> &Path;
+
+    /// 加载全部数据，返回的 `UserData.entries` 包含所有条目
+    fn load(&mut self) -> Result<LoadedData, String>;
+
+    /// 保存数据，`meta.entries` 会被忽略，条目以 `entries` 为准
+    fn save(&mut self, meta: &UserData, entries: &[FileEntry]) -> Result<(), String>;
+
+    /// 磁盘上数据的版本标记，被其他实例或程序修改后会发生变化
+    fn disk_version(&self) -> Option<String>;
+
+    /// 按ID查找已保存的条目
+    fn find_by_id(&self, id: &str) -> Result<Option<FileEntry>, String>;
+
+    /// 按路径查找已保存的条目，按保存的顺序返回
+    fn find_by_path(&self, path: &Path) -> Result<Vec<FileEntry>, String>;
+
+    /// 保存时是否加密
+    fn is_encrypted(&self) -> bool {
+        false
+    }
+
+    /// 数据已加密但尚未解锁
+    fn is_locked(&self) -> bool {
+        false
+    }
+
+    /// 验证密码并解锁
+    fn unlock(&mut self, _passphrase: &str) -> Result<(), String> {
+        Err(ENCRYPTION_UNSUPPORTED.to_string())
+    }
+
+    /// 从内存中清除密钥，之后需要重新解锁
+    fn lock(&mut self) {}
+
+    /// 设置新密码（None 表示取消加密），下次保存时生效
+    fn set_passphrase(&mut self, _passphrase: Option<&str>) -> Result<(), String> {
+        Err(ENCRYPTION_UNSUPPORTED.to_string())
+    }
+}
+
+/// 根据后端类型打开存储
+pub fn open_store(backend: StorageBackend, path: PathBuf) -> Result<Box<dyn EntryStore>, String> {
+    match backend {
+        StorageBackend::Json => Ok(Box::new(DataManager::new_with_path(path))),
+        StorageBackend::Sqlite => Ok(Box::new(SqliteStore::open(path)?)),
+    }
+}
+
+/// 一次性把 JSON 数据文件迁移到 SQLite 数据库，返回迁移的条目数
+pub fn migrate_json_to_sqlite(json_path: &Path, db_path: &Path) -> Result<usize, String> {
+    let mut source = DataManager::new_with_path(json_path.to_path_buf());
+    let loaded = source.load()?;
+
+    let mut target = SqliteStore::open(db_path.to_path_buf())?;
+    let existing = target.load()?;
+    if !existing.data.entries.is_empty() {
+        return Err(format!(
+            "目标数据库已包含 {} 个条目，请指定一个新的数据库文件",
+            existing.data.entries.len()
+        ));
+    }
+
+    let mut meta = loaded.data;
+    let entries = std::mem::take(&mut meta.entries);
+    target.save(&meta, &entries)?;
+    Ok(entries.len())
+}
+
+/// 把条目追加到另一个存储（资料库之间复制或移动），返回追加的条目数
+///
+/// `keep_ids` 为 false 时（复制）所有条目都会分配新ID；为 true 时（移动）仅在与目标中已有
+/// 条目冲突时分配新ID。集合的子项目引用会随之更新，目标中不存在的子项目引用会被丢弃。
+pub fn append_entries(
+    target: &mut dyn EntryStore,
+    entries: &[FileEntry],
+    keep_ids: bool,
+) -> Result<usize, String> {
+    let mut meta = target.load()?.data;
+    let mut target_entries = std::mem::take(&mut meta.entries);
+    let mut known_ids: HashSet<String> = target_entries.iter().map(|e| e.id.clone()).collect();
+
+    let mut id_map = HashMap::new();
+    let mut appended: Vec<FileEntry> = entries
+        .iter()
+        .map(|entry| {
+            let mut entry = entry.clone();
+            if !keep_ids || known_ids.contains(&entry.id) {
+                let new_id = file_entry::generate_id();
+                id_map.insert(entry.id.clone(), new_id.clone());
+                entry.id = new_id;
+            }
+            known_ids.insert(entry.id.clone());
+            entry
+        })
+        .collect();
+
+    for entry in &mut appended {
+        entry.child_entries = entry
+            .child_entries
+            .iter()
+            .map(|id| id_map.get(id).unwrap_or(id).clone())
+            .filter(|id| known_ids.contains(id))
+            .collect();
+        entry.child_options = std::mem::take(&mut entry.child_options)
+            .into_iter()
+            .map(|(id, options)| (id_map.get(&id).cloned().unwrap_or(id), options))
+            .collect();
+        entry.prune_child_options();
+    }
+
+    let count = appended.len();
+    target_entries.extend(appended);
+    target.save(&meta, &target_entries)?;
+    Ok(count)
+}
+
+/// 存储中已有的条目数（ID或路径相同），用于复制或移动前提示重复
+pub fn count_existing(target: &dyn EntryStore, entries: &[FileEntry]) -> Result<usize, String> {
+    let mut count = 0;
+    for entry in entries {
+        if target.find_by_id(&entry.id)?.is_some() || !target.find_by_path(&entry.path)?.is_empty()
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+impl EntryStore for DataManager {
+    fn location(&self) -> &Path {
+        self.get_data_path()
+    }
+
+    fn load(&mut self) -> Result<LoadedData, String> {
+        self.load_data()
+    }
+
+    fn save(&mut self, meta: &UserData, entries: &[FileEntry]) -> Result<(), String> {
+        self.save_data(meta, entries)
+    }
+
+    fn disk_version(&self) -> Option<String> {
+        sync::file_fingerprint(self.get_data_path())
+    }
+
+    // JSON 文件没有索引，读取整个文件后查找
+    fn find_by_id(&self, id: &str) -> Result<Option<FileEntry>, String> {
+        Ok(self
+            .load_data()?
+            .data
+            .entries
+            .into_iter()
+            .find(|e| e.id == id))
+    }
+
+    fn find_by_path(&self, path: &Path) -> Result<Vec<FileEntry>, String> {
+        Ok(self
+            .load_data()?
+            .data
+            .entries
+            .into_iter()
+            .filter(|e| e.path == path)
+            .collect())
+    }
+
+    fn is_encrypted(&self) -> bool {
+        DataManager::is_encrypted(self)
+    }
+
+    fn is_locked(&self) -> bool {
+        DataManager::is_locked(self)
+    }
+
+    fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        DataManager::unlock(self, passphrase)
+    }
+
+    fn lock(&mut self) {
+        DataManager::lock(self)
+    }
+
+    fn set_passphrase(&mut self, passphrase: Option<&str>) -> Result<(), String> {
+        DataManager::set_passphrase(self, passphrase)
+    }
+}
+
+/// SQLite 存储：每个条目一行，保存时只写入发生变化的行
+pub struct SqliteStore {
+    db_path: PathBuf,
+    conn: Connection,
+    /// 上次加载/保存时各条目的 (位置, 序列化内容)，用于计算增量
+    snapshot: HashMap<String, (usize, String)>,
+    meta_snapshot: Option<String>,
+}
+
+impl SqliteStore {
+    pub fn open(db_path: PathBuf) -> Result<Self, String> {
+        if let Some(parent) = db_path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+
+        let conn = Connection::open(&db_path).map_err(|e| format!("打开数据库失败: {}", e))?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA foreign_keys = ON;
+             CREATE TABLE IF NOT EXISTS meta (
+                 key TEXT PRIMARY KEY,
+                 value TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS entries (
+                 id TEXT PRIMARY KEY,
+                 position INTEGER NOT NULL,
+                 path TEXT NOT NULL,
+                 data TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_entries_path ON entries(path);
+             CREATE INDEX IF NOT EXISTS idx_entries_position ON entries(position);",
+        )
+        .map_err(|e| format!("初始化数据库失败: {}", e))?;
+
+        Ok(Self {
+            db_path,
+            conn,
+            snapshot: HashMap::new(),
+            meta_snapshot: None,
+        })
+    }
+
+    /// 执行只有一个参数、返回条目内容的查询
+    fn query_entries(&self, sql: &str, param: &str) -> Result<Vec<FileEntry>, String> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .map_err(|e| format!("读取数据库失败: {}", e))?;
+        let rows = stmt
+            .query_map(params![param], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("读取数据库失败: {}", e))?;
+        rows.map(|row| {
+            let data = row.map_err(|e| format!("读取数据库失败: {}", e))?;
+            serde_json::from_str(&data).map_err(|e| format!("解析条目失败: {}", e))
+        })
+        .collect()
+    }
+}
+
+impl EntryStore for SqliteStore {
+    fn location(&self) -> &Path {
+        &self.db_path
+    }
+
+    fn load(&mut self) -> Result<LoadedData, String> {
+        let meta_json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'user_data'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("读取数据库失败: {}", e))?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT data FROM entries ORDER BY position")
+            .map_err(|e| format!("读取数据库失败: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("读取数据库失败: {}", e))?;
+        let mut raw_entries = Vec::new();
+        for row in rows {
+            let data = row.map_err(|e| format!("读取数据库失败: {}", e))?;
+            raw_entries.push(
+                serde_json::from_str::<Value>(&data).map_err(|e| format!("解析条目失败: {}", e))?,
+            );
+        }
+        drop(stmt);
+
+        // 与 JSON 文件共用同一条迁移管道
+        let mut value = match &meta_json {
+            Some(json) => {
+                serde_json::from_str::<Value>(json).map_err(|e| format!("解析元数据失败: {}", e))?
+            }
+            None => serde_json::to_value(UserData::default())
+                .map_err(|e| format!("序列化失败: {}", e))?,
+        };
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("entries".to_string(), Value::Array(raw_entries));
+        }
+        let value = migration::migrate_to_current(value)?;
+        let data: UserData =
+            serde_json::from_value(value).map_err(|e| format!("解析数据失败: {}", e))?;
+
+        // 迁移可能修改了条目，快照按迁移前的内容记录，下次保存时会写回
+        self.snapshot.clear();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, position, data FROM entries")
+            .map_err(|e| format!("读取数据库失败: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)? as usize,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(|e| format!("读取数据库失败: {}", e))?;
+        for row in rows {
+            let (id, position, data) = row.map_err(|e| format!("读取数据库失败: {}", e))?;
+            self.snapshot.insert(id, (position, data));
+        }
+        self.meta_snapshot = meta_json;
+
+        Ok(LoadedData {
+            data,
+            recovered_from: None,
+        })
+    }
+
+    fn save(&mut self, meta: &UserData, entries: &[FileEntry]) -> Result<(), String> {
+        let mut meta_value =
+            serde_json::to_value(meta).map_err(|e| format!("序列化失败: {}", e))?;
+        if let Some(obj) = meta_value.as_object_mut() {
+            obj.insert("entries".to_string(), Value::Array(Vec::new()));
+        }
+        let meta_json = meta_value.to_string();
+
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("开始事务失败: {}", e))?;
+        let mut new_snapshot = HashMap::with_capacity(entries.len());
+
+        {
+            let mut upsert = tx
+                .prepare_cached(
+                    "INSERT INTO entries (id, position, path, data) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(id) DO UPDATE SET position = ?2, path = ?3, data = ?4",
+                )
+                .map_err(|e| format!("保存数据失败: {}", e))?;
+            let mut update_position = tx
+                .prepare_cached("UPDATE entries SET position = ?2 WHERE id = ?1")
+                .map_err(|e| format!("保存数据失败: {}", e))?;
+
+            for (position, entry) in entries.iter().enumerate() {
+                let data =
+                    serde_json::to_string(entry).map_err(|e| format!("序列化失败: {}", e))?;
+
+                match self.snapshot.get(&entry.id) {
+                    Some((old_position, old_data)) if *old_data == data => {
+                        if *old_position != position {
+                            update_position
+                                .execute(params![entry.id, position as i64])
+                                .map_err(|e| format!("保存数据失败: {}", e))?;
+                        }
+                    }
+                    _ => {
+                        upsert
+                            .execute(params![
+                                entry.id,
+                                position as i64,
+                                entry.path.to_string_lossy(),
+                                data
+                            ])
+                            .map_err(|e| format!("保存数据失败: {}", e))?;
+                    }
+                }
+
+                new_snapshot.insert(entry.id.clone(), (position, data));
+            }
+
+            let mut delete = tx
+                .prepare_cached("DELETE FROM entries WHERE id = ?1")
+                .map_err(|e| format!("保存数据失败: {}", e))?;
+            for id in self.snapshot.keys() {
+                if !new_snapshot.contains_key(id) {
+                    delete
+                        .execute(params![id])
+                        .map_err(|e| format!("保存数据失败: {}", e))?;
+                }
+            }
+
+            if self.meta_snapshot.as_deref() != Some(meta_json.as_str()) {
+                tx.execute(
+                    "INSERT INTO meta (key, value) VALUES ('user_data', ?1)
+                     ON CONFLICT(key) DO UPDATE SET value = ?1",
+                    params![meta_json],
+                )
+                .map_err(|e| format!("保存数据失败: {}", e))?;
+            }
+        }
+
+        tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+        self.snapshot = new_snapshot;
+        self.meta_snapshot = Some(meta_json);
+        Ok(())
+    }
+
+    // data_version 只在其他连接提交修改后变化
+    fn disk_version(&self) -> Option<String> {
+        self.conn
+            .query_row("PRAGMA data_version", [], |row| row.get::<_, i64>(0))
+            .ok()
+            .map(|version| version.to_string())
+    }
+
+    fn find_by_id(&self, id: &str) -> Result<Option<FileEntry>, String> {
+        Ok(self
+            .query_entries("SELECT data FROM entries WHERE id = ?1", id)?
+            .into_iter()
+            .next())
+    }
+
+    fn find_by_path(&self, path: &Path) -> Result<Vec<FileEntry>, String> {
+        self.query_entries(
+            "SELECT data FROM entries WHERE path = ?1 ORDER BY position",
+            &path.to_string_lossy(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_entries() -> Vec<FileEntry> {
+        vec![
+            FileEntry::new(
+                PathBuf::from("/work/report.txt"),
+                "Report".to_string(),
+                None,
+                vec!["#work".to_string()],
+                false,
+            ),
+            FileEntry::new(
+                PathBuf::from("/home/photos"),
+                "Photos".to_string(),
+                None,
+                vec!["#home".to_string(), "#work".to_string()],
+                true,
+            ),
+        ]
+    }
+
+    fn row_count(store: &SqliteStore) -> i64 {
+        store
+            .conn
+            .query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sqlite_round_trip_and_lookups() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("data.db");
+        let entries = sample_entries();
+
+        let mut store = SqliteStore::open(db_path.clone()).unwrap();
+        store.save(&UserData::default(), &entries).unwrap();
+
+        let mut reopened = SqliteStore::open(db_path).unwrap();
+        let loaded = reopened.load().unwrap().data;
+        assert_eq!(loaded.entries.len(), 2);
+        assert_eq!(loaded.entries[0].name, "Report");
+        assert_eq!(loaded.entries[1].name, "Photos");
+
+        let by_id = reopened.find_by_id(&entries[1].id).unwrap().unwrap();
+        assert_eq!(by_id.name, "Photos");
+        let by_path = reopened
+            .find_by_path(Path::new("/work/report.txt"))
+            .unwrap();
+        assert_eq!(by_path.len(), 1);
+        assert!(reopened.find_by_id("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_json_lookups() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = DataManager::new_with_path(dir.path().join("data.json"));
+        let entries = sample_entries();
+        store.save(&UserData::default(), &entries).unwrap();
+
+        let by_id = store.find_by_id(&entries[0].id).unwrap().unwrap();
+        assert_eq!(by_id.name, "Report");
+        assert_eq!(
+            store.find_by_path(Path::new("/home/photos")).unwrap()[0].id,
+            entries[1].id
+        );
+        assert!(
+            store
+                .find_by_path(Path::new("/missing"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_sqlite_incremental_update_and_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = SqliteStore::open(dir.path().join("data.db")).unwrap();
+        let mut entries = sample_entries();
+        store.save(&UserData::default(), &entries).unwrap();
+
+        // 修改后只更新对应的行
+        entries[0].path = PathBuf::from("/work/final.txt");
+        store.save(&UserData::default(), &entries).unwrap();
+        assert!(
+            store
+                .find_by_path(Path::new("/work/report.txt"))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            store.find_by_path(Path::new("/work/final.txt")).unwrap()[0].id,
+            entries[0].id
+        );
+
+        // 删除条目后对应的行被移除
+        let removed = entries.remove(0);
+        store.save(&UserData::default(), &entries).unwrap();
+        assert_eq!(row_count(&store), 1);
+        assert!(store.find_by_id(&removed.id).unwrap().is_none());
+
+        let loaded = store.load().unwrap().data;
+        assert_eq!(loaded.entries.len(), 1);
+        assert_eq!(loaded.entries[0].name, "Photos");
+    }
+
+    #[test]
+    fn test_migrate_json_to_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let json_path = dir.path().join("data.json");
+        let db_path = dir.path().join("data.db");
+
+        let mut entries = sample_entries();
+        entries[0]
+            .extra
+            .insert("rating".to_string(), Value::from(4));
+        DataManager::new_with_path(json_path.clone())
+            .save_data(&UserData::default(), &entries)
+            .unwrap();
+
+        assert_eq!(migrate_json_to_sqlite(&json_path, &db_path).unwrap(), 2);
+
+        let mut store = SqliteStore::open(db_path.clone()).unwrap();
+        let loaded = store.load().unwrap().data;
+        assert_eq!(loaded.entries.len(), 2);
+        assert_eq!(loaded.entries[0].extra["rating"], 4);
+
+        // 目标数据库已有数据时拒绝重复迁移
+        assert!(migrate_json_to_sqlite(&json_path, &db_path).is_err());
+    }
+
+    #[test]
+    fn test_append_entries_between_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut target = DataManager::new_with_path(dir.path().join("work.json"));
+        let mut entries = sample_entries();
+        let collection = FileEntry::new_collection(
+            "Both".to_string(),
+            None,
+            None,
+            Vec::new(),
+            vec![
+                entries[0].id.clone(),
+                "missing".to_string(),
+                entries[1].id.clone(),
+            ],
+        );
+        entries.push(collection);
+
+        // 移动：保留原ID，丢弃目标中不存在的子项目引用
+        assert_eq!(append_entries(&mut target, &entries, true).unwrap(), 3);
+        let moved = target.load().unwrap().data.entries;
+        assert_eq!(moved[0].id, entries[0].id);
+        assert_eq!(
+            moved[2].child_entries,
+            vec![entries[0].id.clone(), entries[1].id.clone()]
+        );
+
+        // 复制：分配新ID，集合引用指向新的副本
+        assert_eq!(count_existing(&target, &entries).unwrap(), 3);
+        append_entries(&mut target, &entries, false).unwrap();
+        let copied = target.load().unwrap().data.entries;
+        assert_eq!(copied.len(), 6);
+        let ids: HashSet<&String> = copied.iter().map(|e| &e.id).collect();
+        assert_eq!(ids.len(), 6);
+        assert_eq!(
+            copied[5].child_entries,
+            vec![copied[3].id.clone(), copied[4].id.clone()]
+        );
+
+        // 新条目只按路径判断是否已存在
+        let fresh = sample_entries();
+        assert_eq!(count_existing(&target, &fresh).unwrap(), 2);
+        let other = FileEntry::new(
+            PathBuf::from("/other"),
+            "Other".to_string(),
+            None,
+            Vec::new(),
+            false,
+        );
+        assert_eq!(count_existing(&target, &[other]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_disk_version_detects_other_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("data.db");
+
+        let mut ours = SqliteStore::open(db_path.clone()).unwrap();
+        ours.save(&UserData::default(), &sample_entries()).unwrap();
+        let version = ours.disk_version();
+
+        // 自己的写入不改变版本
+        ours.save(&UserData::default(), &sample_entries()[..1])
+            .unwrap();
+        assert_eq!(ours.disk_version(), version);
+
+        let mut theirs = SqliteStore::open(db_path).unwrap();
+        theirs.load().unwrap();
+        theirs
+            .save(&UserData::default(), &sample_entries())
+            .unwrap();
+        assert_ne!(ours.disk_version(), version);
+    }
+}
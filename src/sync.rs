@@ -0,0 +1,470 @@
+use crate::config::UserData;
+use crate::file_entry::FileEntry;
+use crate::frecency;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 等待其他实例释放写锁的最长时间
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 数据文件的写锁（`<数据文件>.lock` 上的咨询锁），在保存期间持有，离开作用域时释放
+pub struct DataLock {
+    _file: File,
+}
+
+impl DataLock {
+    pub fn acquire(data_path: &Path) -> Result<Self, String> {
+        let path = lock_path(data_path);
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| format!("打开锁文件失败: {}", e))?;
+
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err("数据文件正被另一个实例写入，请稍后重试".to_string());
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(format!("锁定数据文件失败: {}", e));
+                }
+            }
+        }
+    }
+}
+
+/// 锁文件路径，例如 `data.json.lock`
+pub fn lock_path(data_path: &Path) -> PathBuf {
+    let mut name = data_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    data_path.with_file_name(name)
+}
+
+/// 文件指纹（修改时间 + 大小），文件被其他程序改写后会发生变化
+pub fn file_fingerprint(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    Some(format!("{}:{}", modified.as_nanos(), metadata.len()))
+}
+
+/// 合并冲突时选择哪一方的版本
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+}
+
+/// 同一条目在本地和外部都被修改（或一方删除、一方修改）
+pub struct Conflict {
+    pub id: String,
+    /// 本地版本，None 表示本地已删除
+    pub ours: Option<FileEntry>,
+    /// 外部版本，None 表示外部已删除
+    pub theirs: Option<FileEntry>,
+}
+
+impl Conflict {
+    /// 用于提示的条目名称
+    pub fn display_name(&self) -> String {
+        self.ours
+            .as_ref()
+            .or(self.theirs.as_ref())
+            .map(|e| e.nickname.clone().unwrap_or_else(|| e.name.clone()))
+            .unwrap_or_else(|| self.id.clone())
+    }
+}
+
+pub struct MergeResult {
+    /// 合并后的条目；冲突条目暂时使用本地版本（本地已删除时使用外部版本）占位
+    pub entries: Vec<FileEntry>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergeResult {
+    /// 按照每个冲突的选择得到最终的条目列表
+    pub fn resolve(mut self, choices: &[Resolution]) -> Vec<FileEntry> {
+        for (conflict, choice) in self.conflicts.iter().zip(choices) {
+            let chosen = match choice {
+                Resolution::Ours => &conflict.ours,
+                Resolution::Theirs => &conflict.theirs,
+            };
+            let position = self.entries.iter().position(|e| e.id == conflict.id);
+            match (position, chosen) {
+                (Some(index), Some(entry)) => self.entries[index] = entry.clone(),
+                (Some(index), None) => {
+                    self.entries.remove(index);
+                }
+                (None, Some(entry)) => self.entries.push(entry.clone()),
+                (None, None) => {}
+            }
+        }
+        remove_dangling_children(&mut self.entries);
+        self.entries
+    }
+}
+
+/// 以 `base`（上次同步时的磁盘内容）为共同祖先，按 ID 三方合并本地和外部的条目
+///
+/// 只有一方修改的条目直接采用修改后的版本；双方做了不同修改的条目记为冲突。
+/// 顺序以本地为准，外部新增的条目追加在末尾。
+pub fn merge_entries(base: &[FileEntry], ours: &[FileEntry], theirs: &[FileEntry]) -> MergeResult {
+    let base_map: HashMap<&str, &FileEntry> = base.iter().map(|e| (e.id.as_str(), e)).collect();
+    let theirs_map: HashMap<&str, &FileEntry> = theirs.iter().map(|e| (e.id.as_str(), e)).collect();
+    let ours_ids: HashSet<&str> = ours.iter().map(|e| e.id.as_str()).collect();
+
+    let mut entries = Vec::with_capacity(ours.len().max(theirs.len()));
+    let mut conflicts = Vec::new();
+
+    for our in ours {
+        let base_entry = base_map.get(our.id.as_str()).copied();
+        let their = theirs_map.get(our.id.as_str()).copied();
+
+        match (base_entry, their) {
+            // 双方都有：谁改了用谁的，都改了且不同则冲突
+            (Some(base_entry), Some(their)) => {
+                if our == base_entry || our == their {
+                    entries.push(their.clone());
+                } else if their == base_entry {
+                    entries.push(our.clone());
+                } else if our.same_content(base_entry) || their.same_content(base_entry) {
+                    // 至少一方只是打开过条目，内容修改和打开记录可以合并
+                    entries.push(merge_usage(base_entry, our, their));
+                } else {
+                    entries.push(our.clone());
+                    conflicts.push(Conflict {
+                        id: our.id.clone(),
+                        ours: Some(our.clone()),
+                        theirs: Some(their.clone()),
+                    });
+                }
+            }
+            // 外部删除：本地未修改则一起删除，否则冲突
+            (Some(base_entry), None) => {
+                if our != base_entry {
+                    entries.push(our.clone());
+                    conflicts.push(Conflict {
+                        id: our.id.clone(),
+                        ours: Some(our.clone()),
+                        theirs: None,
+                    });
+                }
+            }
+            // 双方各自新增了同一ID（极少见）
+            (None, Some(their)) => {
+                entries.push(our.clone());
+                if our != their {
+                    conflicts.push(Conflict {
+                        id: our.id.clone(),
+                        ours: Some(our.clone()),
+                        theirs: Some(their.clone()),
+                    });
+                }
+            }
+            // 本地新增
+            (None, None) => entries.push(our.clone()),
+        }
+    }
+
+    for their in theirs {
+        if ours_ids.contains(their.id.as_str()) {
+            continue;
+        }
+        match base_map.get(their.id.as_str()) {
+            // 外部新增
+            None => entries.push(their.clone()),
+            // 本地删除：外部未修改则保持删除，否则冲突
+            Some(base_entry) => {
+                if their != *base_entry {
+                    entries.push(their.clone());
+                    conflicts.push(Conflict {
+                        id: their.id.clone(),
+                        ours: None,
+                        theirs: Some(their.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    if conflicts.is_empty() {
+        remove_dangling_children(&mut entries);
+    }
+    MergeResult { entries, conflicts }
+}
+
+/// 以 `base` 为共同祖先三方合并元数据（标签别名、标签显示信息、自动标签规则和未知字段），
+/// 结果写入 `ours`。只有一方修改的项采用修改后的值；双方做了不同修改的项保留本地的值，
+/// 返回这些项的说明
+pub fn merge_user_data(base: &UserData, ours: &mut UserData, theirs: &UserData) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    for key in merge_map(
+        &base.tag_aliases,
+        &mut ours.tag_aliases,
+        &theirs.tag_aliases,
+    ) {
+        conflicts.push(format!("标签别名 {}", key));
+    }
+    for key in merge_map(
+        &base.tag_registry,
+        &mut ours.tag_registry,
+        &theirs.tag_registry,
+    ) {
+        conflicts.push(format!("标签样式 {}", key));
+    }
+
+    // 规则有顺序，整体比较
+    if ours.auto_tag_rules == base.auto_tag_rules {
+        ours.auto_tag_rules = theirs.auto_tag_rules.clone();
+    } else if theirs.auto_tag_rules != base.auto_tag_rules
+        && theirs.auto_tag_rules != ours.auto_tag_rules
+    {
+        conflicts.push("自动标签规则".to_string());
+    }
+
+    let to_map = |extra: &serde_json::Map<String, Value>| -> BTreeMap<String, Value> {
+        extra.clone().into_iter().collect()
+    };
+    let mut extra = to_map(&ours.extra);
+    for key in merge_map(&to_map(&base.extra), &mut extra, &to_map(&theirs.extra)) {
+        conflicts.push(format!("字段 {}", key));
+    }
+    ours.extra = extra.into_iter().collect();
+    conflicts
+}
+
+/// 两份数据的元数据是否相同（不比较条目和版本号）
+pub fn same_metadata(a: &UserData, b: &UserData) -> bool {
+    a.tag_aliases == b.tag_aliases
+        && a.tag_registry == b.tag_registry
+        && a.auto_tag_rules == b.auto_tag_rules
+        && a.extra == b.extra
+}
+
+/// 按键三方合并，返回双方修改不同的键（保留本地的值）
+fn merge_map<V: Clone + PartialEq>(
+    base: &BTreeMap<String, V>,
+    ours: &mut BTreeMap<String, V>,
+    theirs: &BTreeMap<String, V>,
+) -> Vec<String> {
+    let keys: BTreeSet<String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .cloned()
+        .collect();
+    let mut conflicts = Vec::new();
+    for key in keys {
+        let (base_value, their_value) = (base.get(&key), theirs.get(&key));
+        let our_value = ours.get(&key);
+        if our_value == base_value {
+            match their_value {
+                Some(value) => ours.insert(key, value.clone()),
+                None => ours.remove(&key),
+            };
+        } else if their_value != base_value && their_value != our_value {
+            conflicts.push(key);
+        }
+    }
+    conflicts
+}
+
+/// 采用修改了内容的一方，打开次数累加双方新增的次数，打开时间取较新的
+fn merge_usage(base: &FileEntry, ours: &FileEntry, theirs: &FileEntry) -> FileEntry {
+    let mut merged = if ours.same_content(base) {
+        theirs.clone()
+    } else {
+        ours.clone()
+    };
+    merged.open_count = ours
+        .open_count
+        .saturating_add(theirs.open_count)
+        .saturating_sub(base.open_count);
+    merged.last_opened_at = ours.last_opened_at.max(theirs.last_opened_at);
+    merged.recent_opens = base.recent_opens.clone();
+    for &at in ours.recent_opens.iter().chain(&theirs.recent_opens) {
+        if !merged.recent_opens.contains(&at) {
+            frecency::push_recent_open(&mut merged.recent_opens, at);
+        }
+    }
+    merged
+}
+
+/// 移除集合中指向已不存在条目的引用
+fn remove_dangling_children(entries: &mut [FileEntry]) {
+    let ids: HashSet<String> = entries.iter().map(|e| e.id.clone()).collect();
+    for entry in entries.iter_mut() {
+        entry.child_entries.retain(|id| ids.contains(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections;
+    use crate::file_entry::EntryType;
+    use crate::test_support::entry;
+
+    fn names(entries: &[FileEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_merge_non_conflicting_changes() {
+        let base = vec![entry("a", "A"), entry("b", "B"), entry("c", "C")];
+        // 本地：修改 a，删除 c，新增 d
+        let ours = vec![entry("a", "A2"), entry("b", "B"), entry("d", "D")];
+        // 外部：修改 b，新增 e
+        let theirs = vec![
+            entry("a", "A"),
+            entry("b", "B2"),
+            entry("c", "C"),
+            entry("e", "E"),
+        ];
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(names(&merged.entries), vec!["A2", "B2", "D", "E"]);
+    }
+
+    #[test]
+    fn test_merge_conflicts_and_resolution() {
+        let base = vec![entry("a", "A"), entry("b", "B"), entry("c", "C")];
+        // a 双方都改；b 本地删除、外部修改；c 本地修改、外部删除
+        let ours = vec![entry("a", "A-ours"), entry("c", "C-ours")];
+        let theirs = vec![entry("a", "A-theirs"), entry("b", "B-theirs")];
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts.len(), 3);
+
+        let resolved = merged.resolve(&[Resolution::Theirs, Resolution::Ours, Resolution::Ours]);
+        assert_eq!(names(&resolved), vec!["A-theirs", "C-ours"]);
+    }
+
+    #[test]
+    fn test_merge_drops_references_to_deleted_entries() {
+        let mut collection = entry("col", "Col");
+        collection.child_entries = vec!["a".to_string(), "b".to_string()];
+        let base = vec![entry("a", "A"), entry("b", "B"), collection.clone()];
+        let ours = base.clone();
+        let theirs = vec![entry("a", "A"), collection];
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.entries[1].child_entries, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn test_merge_can_create_collection_cycle() {
+        let mut base = vec![entry("x", "X"), entry("y", "Y")];
+        for collection in &mut base {
+            collection.entry_type = EntryType::Collection;
+        }
+        // 本地把 y 放进 x，外部把 x 放进 y，两边都没有冲突
+        let mut ours = base.clone();
+        ours[0].child_entries = vec!["y".to_string()];
+        let mut theirs = base.clone();
+        theirs[1].child_entries = vec!["x".to_string()];
+
+        let mut merged = merge_entries(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(collections::break_cycles(&mut merged.entries), 1);
+        assert_eq!(collections::break_cycles(&mut merged.entries), 0);
+    }
+
+    #[test]
+    fn test_merge_open_stats_with_edits() {
+        let base = vec![entry("a", "A")];
+        let mut ours = base.clone();
+        ours[0].mark_opened(chrono::Utc::now());
+        let mut theirs = vec![entry("a", "A-theirs")];
+        theirs[0].mark_opened(chrono::Utc::now());
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(names(&merged.entries), vec!["A-theirs"]);
+        assert_eq!(merged.entries[0].open_count, 2);
+
+        // 打开次数很大时不会溢出
+        let mut ours = base.clone();
+        ours[0].open_count = u32::MAX;
+        let mut theirs = vec![entry("a", "A-theirs")];
+        theirs[0].open_count = 5;
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert_eq!(merged.entries[0].open_count, u32::MAX);
+    }
+
+    #[test]
+    fn test_merge_user_data() {
+        let mut base = UserData::default();
+        base.tag_aliases
+            .insert("#wrok".to_string(), "#work".to_string());
+        base.tag_aliases
+            .insert("#hmoe".to_string(), "#home".to_string());
+
+        // 本地新增一个别名，外部删除另一个，双方都修改了同一个别名
+        let mut ours = base.clone();
+        ours.tag_aliases
+            .insert("#todo".to_string(), "#task".to_string());
+        ours.tag_aliases
+            .insert("#wrok".to_string(), "#job".to_string());
+        let mut theirs = base.clone();
+        theirs.tag_aliases.remove("#hmoe");
+        theirs
+            .tag_aliases
+            .insert("#wrok".to_string(), "#office".to_string());
+        theirs.tag_registry.insert(
+            "#work".to_string(),
+            crate::tags::TagInfo {
+                hidden: true,
+                ..Default::default()
+            },
+        );
+
+        let conflicts = merge_user_data(&base, &mut ours, &theirs);
+        assert_eq!(conflicts, vec!["标签别名 #wrok"]);
+        assert_eq!(
+            ours.tag_aliases.keys().collect::<Vec<_>>(),
+            vec!["#todo", "#wrok"]
+        );
+        assert_eq!(ours.tag_aliases["#wrok"], "#job");
+        assert!(ours.tag_registry["#work"].hidden);
+        assert!(!same_metadata(&ours, &theirs));
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_path = dir.path().join("data.json");
+
+        let lock = DataLock::acquire(&data_path).unwrap();
+        // 同一进程中另开一个句柄也会被锁阻塞
+        let other = OpenOptions::new()
+            .write(true)
+            .open(lock_path(&data_path))
+            .unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+    }
+}
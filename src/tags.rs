@@ -0,0 +1,489 @@
+//! 层级标签：`#project/alpha/docs` 用 `/` 分隔各级，按上级标签筛选时包含所有下级
+
+use crate::file_entry::FileEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// 层级之间的分隔符
+pub const SEPARATOR: char = '/';
+
+/// 规范化一个标签：加上 `#` 前缀，去掉各级首尾的空白和多余的 `/`；为空时返回 None。
+/// 标签在输入框和搜索中以空白和逗号分隔，所以各级中间的空白和逗号换成 `-`
+pub fn normalize(tag: &str) -> Option<String> {
+    let segments: Vec<String> = tag
+        .trim()
+        .trim_start_matches('#')
+        .split(SEPARATOR)
+        .map(|segment| {
+            segment
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        None
+    } else {
+        Some(format!("#{}", segments.join("/")))
+    }
+}
+
+/// `tag` 是否为 `ancestor` 本身或它的下级；两者的 `#` 前缀可有可无，区分大小写
+pub fn is_within(tag: &str, ancestor: &str) -> bool {
+    let tag = tag.trim_start_matches('#');
+    let ancestor = ancestor.trim_start_matches('#');
+    tag.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(SEPARATOR))
+}
+
+/// 标签树的一个节点
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagNode {
+    /// 这一级的名称，例如 `alpha`
+    pub name: String,
+    /// 完整标签，例如 `#project/alpha`
+    pub path: String,
+    /// 直接带有这个标签的条目数
+    pub own_count: usize,
+    /// 带有这个标签或其任一下级的条目数（每个条目只算一次）
+    pub total_count: usize,
+    pub children: Vec<TagNode>,
+}
+
+/// 按条目的标签建立标签树；只出现在下级标签中的上级也会成为节点，子节点按名称排序
+pub fn build_tree(entries: &[FileEntry]) -> Vec<TagNode> {
+    // 每个标签（包括隐含的上级）→ (直接带有的条目数, 带有它或其下级的条目数)
+    let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for entry in entries {
+        let mut covered = HashSet::new();
+        for tag in entry.tags.iter().filter(|t| t.starts_with('#')) {
+            counts.entry(tag.clone()).or_default().0 += 1;
+            let mut path = String::from("#");
+            for (i, segment) in tag[1..].split(SEPARATOR).enumerate() {
+                if i > 0 {
+                    path.push(SEPARATOR);
+                }
+                path.push_str(segment);
+                covered.insert(path.clone());
+            }
+        }
+        for path in covered {
+            counts.entry(path).or_default().1 += 1;
+        }
+    }
+
+    let mut roots = Vec::new();
+    for (path, (own_count, total_count)) in counts {
+        let segments: Vec<&str> = path[1..].split(SEPARATOR).collect();
+        let mut level = &mut roots;
+        for depth in 0..segments.len() - 1 {
+            let parent = format!("#{}", segments[..=depth].join("/"));
+            // BTreeMap 按字典序遍历，上级总是先于下级出现
+            let position = level
+                .iter()
+                .position(|node: &TagNode| node.path == parent)
+                .expect("上级标签先于下级插入");
+            level = &mut level[position].children;
+        }
+        level.push(TagNode {
+            name: segments[segments.len() - 1].to_string(),
+            path,
+            own_count,
+            total_count,
+            children: Vec::new(),
+        });
+    }
+    roots
+}
+
+/// 带有 `tags` 中任一标签或其下级的条目位置，用于在修改前预览
+pub fn affected_entries(entries: &[FileEntry], tags: &[String]) -> Vec<usize> {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| {
+            e.tags
+                .iter()
+                .any(|t| tags.iter().any(|tag| is_within(t, tag)))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// `tag` 属于 `from` 的子树时，换成 `to` 下对应的标签
+fn moved(tag: &str, from: &str, to: &str) -> Option<String> {
+    if !is_within(tag, from) {
+        return None;
+    }
+    let rest = &tag.trim_start_matches('#')[from.trim_start_matches('#').len()..];
+    Some(format!("{}{}", to, rest))
+}
+
+/// 去掉重复的标签，保留第一次出现的位置，不改变用户排列的顺序
+pub fn dedup(tags: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    tags.retain(|tag| seen.insert(tag.clone()));
+}
+
+/// 对所有条目的标签应用 `map`（返回 None 表示去掉该标签），去重后返回被修改的条目数
+fn retag(entries: &mut [FileEntry], map: impl Fn(&str) -> Option<String>) -> usize {
+    let mut modified = 0;
+    for entry in entries.iter_mut() {
+        if entry
+            .tags
+            .iter()
+            .all(|t| map(t).as_deref() == Some(t.as_str()))
+        {
+            continue;
+        }
+        let mut tags: Vec<String> = entry.tags.iter().filter_map(|t| map(t)).collect();
+        dedup(&mut tags);
+        entry.tags = tags;
+        entry.touch();
+        modified += 1;
+    }
+    modified
+}
+
+/// 把 `from` 及其所有下级改到 `to` 下（重命名或移动整棵子树），例如把 `#project/alpha`
+/// 改为 `#archive/alpha` 时，`#project/alpha/docs` 变为 `#archive/alpha/docs`。
+///
+/// 目标标签已存在时合并。返回被修改的条目数。
+pub fn rename_subtree(entries: &mut [FileEntry], from: &str, to: &str) -> Result<usize, String> {
+    let from = normalize(from).ok_or("请选择要重命名的标签")?;
+    let to = normalize(to).ok_or("新标签不能为空")?;
+    if from == to {
+        return Err("新标签与原标签相同".to_string());
+    }
+    Ok(retag(entries, |tag| {
+        Some(moved(tag, &from, &to).unwrap_or_else(|| tag.to_string()))
+    }))
+}
+
+/// 把多个标签（连同下级）合并到 `target`，同一条目上重复的标签只保留一个
+pub fn merge_into(
+    entries: &mut [FileEntry],
+    sources: &[String],
+    target: &str,
+) -> Result<usize, String> {
+    let target = normalize(target).ok_or("请填写合并到的标签")?;
+    let sources: Vec<String> = sources
+        .iter()
+        .filter_map(|s| normalize(s))
+        .filter(|s| *s != target)
+        .collect();
+    if sources.is_empty() {
+        return Err("请至少选择一个与目标不同的标签".to_string());
+    }
+    Ok(retag(entries, |tag| {
+        Some(
+            sources
+                .iter()
+                .find_map(|source| moved(tag, source, &target))
+                .unwrap_or_else(|| tag.to_string()),
+        )
+    }))
+}
+
+/// 从所有条目中删除标签及其下级
+pub fn delete_subtree(entries: &mut [FileEntry], tag: &str) -> Result<usize, String> {
+    let tag = normalize(tag).ok_or("请选择要删除的标签")?;
+    Ok(retag(entries, |t| {
+        (!is_within(t, &tag)).then(|| t.to_string())
+    }))
+}
+
+/// 把别名换成标准标签；别名的下级同样适用，例如别名 `#proj` → `#project` 时
+/// `#proj/alpha` 变为 `#project/alpha`。不是别名的标签原样返回。
+pub fn resolve_alias(tag: &str, aliases: &BTreeMap<String, String>) -> String {
+    aliases
+        .iter()
+        .find_map(|(alias, canonical)| moved(tag, alias, canonical))
+        .unwrap_or_else(|| tag.to_string())
+}
+
+/// 添加别名，返回规范化后的别名和标准标签；已有指向 `alias` 的别名改为指向新的标准标签
+pub fn add_alias(
+    aliases: &mut BTreeMap<String, String>,
+    alias: &str,
+    canonical: &str,
+) -> Result<(String, String), String> {
+    let alias = normalize(alias).ok_or("别名不能为空")?;
+    let canonical = normalize(canonical).ok_or("标准标签不能为空")?;
+    let canonical = resolve_alias(&canonical, aliases);
+    if is_within(&alias, &canonical) || is_within(&canonical, &alias) {
+        return Err("别名和标准标签不能相同，也不能互为上下级".to_string());
+    }
+
+    for target in aliases.values_mut() {
+        if *target == alias {
+            *target = canonical.clone();
+        }
+    }
+    aliases.insert(alias.clone(), canonical.clone());
+    Ok((alias, canonical))
+}
+
+/// 标签的显示信息，保存在标签登记表中
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TagInfo {
+    /// 显示颜色（RGB）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[u8; 3]>,
+    /// 显示在标签前的 emoji 或图标字符
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 隐藏的标签不在条目列表和输入建议中显示，仍可搜索
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+}
+
+impl TagInfo {
+    /// 没有设置任何信息，不需要保存
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 显示用的文本：有图标时放在标签前
+    pub fn label(&self, tag: &str) -> String {
+        match &self.icon {
+            Some(icon) => format!("{} {}", icon, tag),
+            None => tag.to_string(),
+        }
+    }
+}
+
+/// 标签登记表：标准标签 → 显示信息
+pub type TagRegistry = BTreeMap<String, TagInfo>;
+
+/// 标签实际使用的显示信息：颜色和图标没有设置时沿用最近的上级，任一上级隐藏时下级也隐藏；
+/// 说明只取标签自身的
+pub fn info_for(registry: &TagRegistry, tag: &str) -> TagInfo {
+    let mut info = registry.get(tag).cloned().unwrap_or_default();
+    let mut path = tag;
+    while let Some(end) = path.rfind(SEPARATOR) {
+        path = &path[..end];
+        if let Some(parent) = registry.get(path) {
+            if info.color.is_none() {
+                info.color = parent.color;
+            }
+            if info.icon.is_none() {
+                info.icon = parent.icon.clone();
+            }
+            info.hidden |= parent.hidden;
+        }
+    }
+    info
+}
+
+/// 标签子树改名、合并或成为别名后，把 `from` 及其下级的显示信息移到 `to` 下；
+/// 目标已有信息时保留目标的
+pub fn move_info(registry: &mut TagRegistry, from: &str, to: &str) {
+    let (Some(from), Some(to)) = (normalize(from), normalize(to)) else {
+        return;
+    };
+    let (inside, rest): (TagRegistry, TagRegistry) = std::mem::take(registry)
+        .into_iter()
+        .partition(|(tag, _)| is_within(tag, &from));
+    *registry = rest;
+    for (tag, info) in inside {
+        if let Some(tag) = moved(&tag, &from, &to) {
+            registry.entry(tag).or_insert(info);
+        }
+    }
+}
+
+/// 删除标签子树时一并删除其显示信息
+pub fn remove_info(registry: &mut TagRegistry, tag: &str) {
+    if let Some(tag) = normalize(tag) {
+        registry.retain(|t, _| !is_within(t, &tag));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tagged;
+
+    #[test]
+    fn test_normalize_and_is_within() {
+        assert_eq!(
+            normalize(" project//alpha/ docs/").as_deref(),
+            Some("#project/alpha/docs")
+        );
+        assert_eq!(normalize("#/"), None);
+        assert_eq!(
+            normalize("project/new  name,v2").as_deref(),
+            Some("#project/new-name-v2")
+        );
+
+        assert!(is_within("#project/alpha", "#project"));
+        assert!(is_within("#project", "project"));
+        assert!(!is_within("#projects", "#project"));
+        assert!(!is_within("#project", "#project/alpha"));
+    }
+
+    #[test]
+    fn test_build_tree() {
+        let entries = vec![
+            tagged("a", &["#project/alpha/docs", "#project/alpha"]),
+            tagged("a", &["#project/beta"]),
+            tagged("a", &["#home"]),
+        ];
+        let tree = build_tree(&entries);
+        assert_eq!(
+            tree.iter().map(|n| n.path.as_str()).collect::<Vec<_>>(),
+            vec!["#home", "#project"]
+        );
+
+        let project = &tree[1];
+        assert_eq!((project.own_count, project.total_count), (0, 2));
+        let alpha = &project.children[0];
+        assert_eq!(alpha.name, "alpha");
+        assert_eq!((alpha.own_count, alpha.total_count), (1, 1));
+        assert_eq!(alpha.children[0].path, "#project/alpha/docs");
+        assert_eq!(
+            affected_entries(&entries, &["#project".to_string()]),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_rename_subtree() {
+        let mut entries = vec![
+            tagged("a", &["#project/alpha", "#project/alpha/docs"]),
+            tagged("a", &["#project/alphabet"]),
+            tagged("a", &["#archive/alpha", "#project/alpha/docs"]),
+        ];
+        let untouched = entries[1].clone();
+        assert_eq!(
+            rename_subtree(&mut entries, "#project/alpha", "archive/alpha"),
+            Ok(2)
+        );
+        assert_eq!(
+            entries[0].tags,
+            vec!["#archive/alpha", "#archive/alpha/docs"]
+        );
+        // 名称只是前缀相同的标签不受影响
+        assert_eq!(entries[1], untouched);
+        // 目标已存在时合并
+        assert_eq!(
+            entries[2].tags,
+            vec!["#archive/alpha", "#archive/alpha/docs"]
+        );
+
+        assert!(rename_subtree(&mut entries, "#archive", "#archive/").is_err());
+        // 新名称中的空白换成 `-`，之后仍能在输入框和搜索中使用
+        assert_eq!(rename_subtree(&mut entries, "#archive", "old stuff"), Ok(2));
+        assert_eq!(
+            entries[0].tags,
+            vec!["#old-stuff/alpha", "#old-stuff/alpha/docs"]
+        );
+        assert!(rename_subtree(&mut entries, "#archive", "").is_err());
+    }
+
+    #[test]
+    fn test_merge_and_delete() {
+        let mut entries = vec![
+            tagged("a", &["#zeta", "#wrok", "#work"]),
+            tagged("a", &["#job/reports"]),
+            tagged("a", &["#home"]),
+        ];
+        let sources = vec!["#wrok".to_string(), "#job".to_string()];
+        assert_eq!(affected_entries(&entries, &sources), vec![0, 1]);
+        assert_eq!(merge_into(&mut entries, &sources, "#work"), Ok(2));
+        // 去重时保持原来的顺序
+        assert_eq!(entries[0].tags, vec!["#zeta", "#work"]);
+        assert_eq!(entries[1].tags, vec!["#work/reports"]);
+        assert!(merge_into(&mut entries, &["#work".to_string()], "#work").is_err());
+
+        assert_eq!(delete_subtree(&mut entries, "#work"), Ok(2));
+        assert_eq!(entries[0].tags, vec!["#zeta"]);
+        assert!(entries[1].tags.is_empty());
+        assert_eq!(entries[2].tags, vec!["#home"]);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut aliases = BTreeMap::new();
+        assert_eq!(
+            add_alias(&mut aliases, "proj", "#project"),
+            Ok(("#proj".to_string(), "#project".to_string()))
+        );
+        assert_eq!(resolve_alias("#proj/alpha", &aliases), "#project/alpha");
+        assert_eq!(resolve_alias("#projects", &aliases), "#projects");
+
+        // 指向别名的别名会被展开，旧别名也随之更新
+        add_alias(&mut aliases, "#pj", "#proj").unwrap();
+        assert_eq!(aliases["#pj"], "#project");
+        add_alias(&mut aliases, "#project", "#work/project").unwrap();
+        assert_eq!(aliases["#proj"], "#work/project");
+
+        assert!(add_alias(&mut aliases, "#a", "#a/b").is_err());
+        assert!(add_alias(&mut aliases, "#a", "").is_err());
+    }
+
+    #[test]
+    fn test_info_inherits_from_ancestors() {
+        let mut registry = TagRegistry::new();
+        registry.insert(
+            "#project".to_string(),
+            TagInfo {
+                color: Some([200, 40, 40]),
+                icon: Some("📁".to_string()),
+                description: Some("所有项目".to_string()),
+                ..TagInfo::default()
+            },
+        );
+        registry.insert(
+            "#project/alpha".to_string(),
+            TagInfo {
+                color: Some([40, 40, 200]),
+                hidden: true,
+                ..TagInfo::default()
+            },
+        );
+
+        let info = info_for(&registry, "#project/alpha/docs");
+        assert_eq!(info.color, Some([40, 40, 200]));
+        assert_eq!(info.label("#project/alpha/docs"), "📁 #project/alpha/docs");
+        assert_eq!(info.description, None);
+        assert!(info.hidden);
+        assert!(!info_for(&registry, "#project/beta").hidden);
+        assert!(info_for(&registry, "#projects").is_empty());
+    }
+
+    #[test]
+    fn test_move_and_remove_info() {
+        let red = TagInfo {
+            color: Some([255, 0, 0]),
+            ..TagInfo::default()
+        };
+        let blue = TagInfo {
+            color: Some([0, 0, 255]),
+            ..TagInfo::default()
+        };
+        let mut registry = TagRegistry::new();
+        registry.insert("#job".to_string(), red.clone());
+        registry.insert("#job/reports".to_string(), red.clone());
+        registry.insert("#work".to_string(), blue.clone());
+
+        // 目标已有的信息优先
+        move_info(&mut registry, "#job", "#work");
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry["#work"], blue);
+        assert_eq!(registry["#work/reports"], red);
+
+        // 移到自己的下级也不会丢失信息
+        move_info(&mut registry, "#work", "#work/old");
+        assert_eq!(registry["#work/old"], blue);
+        assert_eq!(registry["#work/old/reports"], red);
+
+        remove_info(&mut registry, "#work");
+        assert!(registry.is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+//! 测试中共用的条目构造函数
+
+use crate::file_entry::FileEntry;
+use std::path::PathBuf;
+
+/// 路径为 `path` 的文件条目，名称取路径的最后一段
+pub fn file_at(path: &str) -> FileEntry {
+    let path = PathBuf::from(path);
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    FileEntry::new(path, name, None, Vec::new(), false)
+}
+
+/// 路径为 `/<name>` 的文件条目
+pub fn file(name: &str) -> FileEntry {
+    file_at(&format!("/{}", name))
+}
+
+/// 路径为 `/<name>` 并带有标签的文件条目
+pub fn tagged(name: &str, tags: &[&str]) -> FileEntry {
+    let mut entry = file(name);
+    entry.tags = tags.iter().map(|t| t.to_string()).collect();
+    entry
+}
+
+/// 指定 ID 的文件条目。不记录时间，分别构造的相同条目相等
+pub fn entry(id: &str, name: &str) -> FileEntry {
+    let mut entry = file(id);
+    entry.name = name.to_string();
+    entry.id = id.to_string();
+    entry.created_at = None;
+    entry.updated_at = None;
+    entry
+}